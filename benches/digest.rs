@@ -0,0 +1,88 @@
+//! Throughput of digesting a file read through a buffer and mapped into memory,
+//! and of the Keccak sponge.
+//!
+//! Run with `cargo bench --bench digest`.
+
+use std::{
+    fs::File,
+    io::{Seek, SeekFrom, Write},
+};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use hashy::{
+    algorithms::{
+        sha2::{Sha2, Sha2Variant},
+        sha3::{Sha3, Sha3Variant, Shake, ShakeVariant},
+        Algorithm,
+    },
+    chunked_stream::ChunkedStream,
+};
+
+const FILE_SIZE: usize = 16 * 1024 * 1024;
+
+fn test_file() -> File {
+    let mut file = tempfile::tempfile().unwrap();
+    let mut state = 0x9e3779b97f4a7c15u64;
+    let data: Vec<u8> = (0..FILE_SIZE)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect();
+    file.write_all(&data).unwrap();
+    file
+}
+
+fn reopen(file: &File) -> File {
+    let mut file = file.try_clone().unwrap();
+    file.seek(SeekFrom::Start(0)).unwrap();
+    file
+}
+
+fn file_sources(c: &mut Criterion) {
+    let file = test_file();
+    let algorithms: [(&str, Box<dyn Algorithm>); 2] = [
+        ("sha-256", Box::new(Sha2::new(Sha2Variant::_256))),
+        ("sha3-256", Box::new(Sha3::new(Sha3Variant::_256))),
+    ];
+
+    let mut group = c.benchmark_group("file");
+    group.throughput(Throughput::Bytes(FILE_SIZE as u64));
+    group.sample_size(10);
+    for (name, algorithm) in &algorithms {
+        group.bench_function(BenchmarkId::new(*name, "buffered"), |b| {
+            b.iter(|| algorithm.digest(ChunkedStream::from(reopen(&file))))
+        });
+        group.bench_function(BenchmarkId::new(*name, "mapped"), |b| {
+            b.iter(|| algorithm.digest(ChunkedStream::mapped(reopen(&file))))
+        });
+    }
+    group.finish();
+}
+
+/// Keccak sponge over data in memory, without reading files
+fn keccak(c: &mut Criterion) {
+    let data = vec![0xa5; 1024 * 1024];
+    let algorithms: [(&str, Box<dyn Algorithm>); 2] = [
+        ("sha3-256", Box::new(Sha3::new(Sha3Variant::_256))),
+        (
+            "shake256",
+            Box::new(Shake::new(ShakeVariant::_256, 512).unwrap()),
+        ),
+    ];
+
+    let mut group = c.benchmark_group("keccak");
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    for (name, algorithm) in &algorithms {
+        group.bench_function(*name, |b| {
+            b.iter(|| algorithm.digest(ChunkedStream::Bytes(data.clone())))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, file_sources, keccak);
+criterion_main!(benches);
@@ -0,0 +1,66 @@
+use super::{helpers::digest_incremental, Algorithm, DigestResult, Incremental};
+use crate::chunked_stream::ChunkedStream;
+
+const MOD_ADLER: u32 = 65521;
+
+/// Largest number of bytes that can be summed before the sums overflow a u32,
+/// so the modulo only has to be taken once per run of bytes
+const NMAX: usize = 5552;
+
+/// Adler-32 checksum, as used by zlib
+///
+/// Reference: https://www.rfc-editor.org/info/rfc1950
+pub struct Adler32;
+
+/// Running sums of Adler-32
+struct Adler32State {
+    a: u32,
+    b: u32,
+}
+
+impl Incremental for Adler32State {
+    fn update(&mut self, data: &[u8]) {
+        for run in data.chunks(NMAX) {
+            for &byte in run {
+                self.a += byte as u32;
+                self.b += self.a;
+            }
+
+            self.a %= MOD_ADLER;
+            self.b %= MOD_ADLER;
+        }
+    }
+
+    fn finalize(&self) -> Vec<u8> {
+        ((self.b << 16) | self.a).to_be_bytes().to_vec()
+    }
+}
+
+impl Algorithm for Adler32 {
+    fn digest(&self, data: ChunkedStream) -> DigestResult {
+        digest_incremental(&mut Adler32State { a: 1, b: 0 }, data, NMAX)
+    }
+
+    fn incremental(&self) -> Option<Box<dyn Incremental>> {
+        Some(Box::new(Adler32State { a: 1, b: 0 }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::algorithms::helpers::test::assert_digest;
+
+    use super::*;
+
+    #[test]
+    fn adler32_correct() {
+        for (input, expected) in [
+            ("", "00000001"),
+            ("Wikipedia", "11e60398"),
+            ("123456789", "091e01de"),
+            (&"a".repeat(100_000), "79660b4d"),
+        ] {
+            assert_digest(&Adler32, input, expected);
+        }
+    }
+}
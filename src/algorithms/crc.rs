@@ -0,0 +1,379 @@
+// Reference: https://reveng.sourceforge.io/crc-catalogue/all.htm
+
+use rayon::prelude::*;
+
+use super::{Algorithm, DigestResult, Incremental};
+use crate::chunked_stream::ChunkedStream;
+
+/// Size of the blocks read from the stream, which are split into
+/// pieces that are processed in parallel and combined afterwards.
+const BLOCK_SIZE: usize = 1 << 20;
+const PIECE_SIZE: usize = 1 << 16;
+
+/// Parameters of a CRC in the Rocksoft model, as used by the CRC catalogue
+#[derive(Clone, Copy)]
+pub struct CrcParams {
+    /// Width in bits, from 8 to 64
+    pub width: u32,
+    /// Generator polynomial, without the top bit
+    pub poly: u64,
+    pub init: u64,
+    /// Whether input bytes are reflected (processed LSB first)
+    pub refin: bool,
+    /// Whether the register is reflected before the final XOR
+    pub refout: bool,
+    pub xorout: u64,
+}
+
+pub const CRC_32_ISO_HDLC: CrcParams = CrcParams {
+    width: 32,
+    poly: 0x04C11DB7,
+    init: 0xFFFFFFFF,
+    refin: true,
+    refout: true,
+    xorout: 0xFFFFFFFF,
+};
+
+pub const CRC_32_ISCSI: CrcParams = CrcParams {
+    width: 32,
+    poly: 0x1EDC6F41,
+    init: 0xFFFFFFFF,
+    refin: true,
+    refout: true,
+    xorout: 0xFFFFFFFF,
+};
+
+/// Also known as CRC-16/CCITT (or CRC-16/KERMIT in the catalogue)
+pub const CRC_16_CCITT: CrcParams = CrcParams {
+    width: 16,
+    poly: 0x1021,
+    init: 0x0000,
+    refin: true,
+    refout: true,
+    xorout: 0x0000,
+};
+
+pub const CRC_64_XZ: CrcParams = CrcParams {
+    width: 64,
+    poly: 0x42F0E1EBA9EA3693,
+    init: 0xFFFFFFFFFFFFFFFF,
+    refin: true,
+    refout: true,
+    xorout: 0xFFFFFFFFFFFFFFFF,
+};
+
+/// Table-driven CRC engine for any width from 8 to 64 bits.
+///
+/// The register is kept reflected if `refin` is set, so that each byte
+/// can be processed with a single table lookup either way.
+#[derive(Clone)]
+pub struct Crc {
+    params: CrcParams,
+    table: [u64; 256],
+}
+
+impl Crc {
+    pub fn new(params: CrcParams) -> Self {
+        let table = make_table(&params);
+        Self { params, table }
+    }
+
+    fn mask(&self) -> u64 {
+        u64::MAX >> (64 - self.params.width)
+    }
+
+    /// Initial value of the register
+    pub fn init(&self) -> u64 {
+        if self.params.refin {
+            reflect(self.params.init, self.params.width)
+        } else {
+            self.params.init
+        }
+    }
+
+    /// Processes bytes into the register
+    pub fn update(&self, mut register: u64, bytes: &[u8]) -> u64 {
+        let width = self.params.width;
+
+        if self.params.refin {
+            for &byte in bytes {
+                register = self.table[((register ^ byte as u64) & 0xFF) as usize] ^ (register >> 8);
+            }
+        } else {
+            for &byte in bytes {
+                let index = ((register >> (width - 8)) ^ byte as u64) & 0xFF;
+                register = (self.table[index as usize] ^ (register << 8)) & self.mask();
+            }
+        }
+
+        register
+    }
+
+    /// Converts the register into the CRC value
+    pub fn finalize(&self, register: u64) -> u64 {
+        let register = if self.params.refin != self.params.refout {
+            reflect(register, self.params.width)
+        } else {
+            register
+        };
+
+        register ^ self.params.xorout
+    }
+
+    /// Big-endian bytes of the CRC value, as many as the width takes
+    fn to_bytes(&self, crc: u64) -> Vec<u8> {
+        let byte_count = self.params.width as usize / 8;
+        crc.to_be_bytes()[8 - byte_count..].to_vec()
+    }
+
+    pub fn checksum(&self, bytes: &[u8]) -> u64 {
+        self.finalize(self.update(self.init(), bytes))
+    }
+
+    /// Returns the CRC of the concatenation of two pieces, given the CRC of
+    /// each piece and the length of the second one, without the data itself.
+    ///
+    /// Processing the second piece after the first only differs from processing
+    /// it from the initial register by the first register shifted through
+    /// `len2` zero bytes, which is done in O(log len2) with GF(2) matrices.
+    pub fn combine(&self, crc1: u64, crc2: u64, len2: u64) -> u64 {
+        let to_register = |crc: u64| {
+            let register = crc ^ self.params.xorout;
+            if self.params.refin != self.params.refout {
+                reflect(register, self.params.width)
+            } else {
+                register
+            }
+        };
+
+        let shifted = self.shift_zeros(to_register(crc1) ^ self.init(), len2);
+        self.finalize(shifted ^ to_register(crc2))
+    }
+
+    /// Applies the effect of processing `count` zero bytes on a register,
+    /// when starting from a register of 0
+    fn shift_zeros(&self, register: u64, mut count: u64) -> u64 {
+        let width = self.params.width as usize;
+
+        // Operator for a single zero byte, column i is the image of bit i
+        let mut operator: Vec<u64> = (0..width).map(|i| self.update(1 << i, &[0])).collect();
+
+        let mut register = register;
+        while count > 0 {
+            if count & 1 == 1 {
+                register = gf2_matrix_times(&operator, register);
+            }
+
+            operator = gf2_matrix_square(&operator);
+            count >>= 1;
+        }
+
+        register
+    }
+}
+
+fn gf2_matrix_times(matrix: &[u64], vector: u64) -> u64 {
+    matrix
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| (vector >> i) & 1 == 1)
+        .fold(0, |sum, (_, column)| sum ^ column)
+}
+
+fn gf2_matrix_square(matrix: &[u64]) -> Vec<u64> {
+    matrix
+        .iter()
+        .map(|&column| gf2_matrix_times(matrix, column))
+        .collect()
+}
+
+fn make_table(params: &CrcParams) -> [u64; 256] {
+    let width = params.width;
+    let mask = u64::MAX >> (64 - width);
+    let mut table = [0; 256];
+
+    for (byte, entry) in table.iter_mut().enumerate() {
+        *entry = if params.refin {
+            let poly = reflect(params.poly, width);
+            (0..8).fold(byte as u64, |crc, _| {
+                if crc & 1 == 1 {
+                    (crc >> 1) ^ poly
+                } else {
+                    crc >> 1
+                }
+            })
+        } else {
+            let top_bit = 1 << (width - 1);
+            (0..8).fold((byte as u64) << (width - 8), |crc, _| {
+                if crc & top_bit != 0 {
+                    ((crc << 1) ^ params.poly) & mask
+                } else {
+                    (crc << 1) & mask
+                }
+            })
+        };
+    }
+
+    table
+}
+
+/// Reverses the lowest `width` bits of the value
+fn reflect(value: u64, width: u32) -> u64 {
+    value.reverse_bits() >> (64 - width)
+}
+
+impl Algorithm for Crc {
+    fn digest(&self, data: ChunkedStream) -> DigestResult {
+        let mut crc = self.checksum(&[]);
+
+        data.for_each_chunk(BLOCK_SIZE, |block| {
+            // Pieces are independent, so their CRCs are computed in parallel and combined
+            let block_crc = block
+                .par_chunks(PIECE_SIZE)
+                .map(|piece| (self.checksum(piece), piece.len() as u64))
+                .reduce(
+                    || (self.checksum(&[]), 0),
+                    |(crc1, len1), (crc2, len2)| (self.combine(crc1, crc2, len2), len1 + len2),
+                );
+
+            crc = self.combine(crc, block_crc.0, block_crc.1);
+        })?;
+
+        Ok(self.to_bytes(crc))
+    }
+
+    fn incremental(&self) -> Option<Box<dyn Incremental>> {
+        Some(Box::new(CrcState {
+            register: self.init(),
+            crc: self.clone(),
+        }))
+    }
+}
+
+/// Register of a CRC fed input piece by piece
+struct CrcState {
+    crc: Crc,
+    register: u64,
+}
+
+impl Incremental for CrcState {
+    fn update(&mut self, data: &[u8]) {
+        self.register = self.crc.update(self.register, data);
+    }
+
+    fn finalize(&self) -> Vec<u8> {
+        self.crc.to_bytes(self.crc.finalize(self.register))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::algorithms::helpers::test::assert_digest;
+
+    use super::*;
+
+    #[test]
+    fn check_values() {
+        // Check values from the CRC catalogue, the CRC of "123456789"
+        for (params, expected) in [
+            (CRC_32_ISO_HDLC, "cbf43926"),
+            (CRC_32_ISCSI, "e3069283"),
+            (CRC_16_CCITT, "2189"),
+            (CRC_64_XZ, "995dc9bbdf1939fa"),
+            (
+                // CRC-16/IBM-3740, not reflected
+                CrcParams {
+                    width: 16,
+                    poly: 0x1021,
+                    init: 0xFFFF,
+                    refin: false,
+                    refout: false,
+                    xorout: 0x0000,
+                },
+                "29b1",
+            ),
+            (
+                // CRC-32/BZIP2, not reflected
+                CrcParams {
+                    width: 32,
+                    poly: 0x04C11DB7,
+                    init: 0xFFFFFFFF,
+                    refin: false,
+                    refout: false,
+                    xorout: 0xFFFFFFFF,
+                },
+                "fc891918",
+            ),
+        ] {
+            assert_digest(&Crc::new(params), "123456789", expected);
+        }
+    }
+
+    #[test]
+    fn crc32_correct() {
+        let crc32 = Crc::new(CRC_32_ISO_HDLC);
+
+        for (input, expected) in [
+            ("", "00000000"),
+            ("The quick brown fox jumps over the lazy dog", "414fa339"),
+        ] {
+            assert_digest(&crc32, input, expected);
+        }
+    }
+
+    #[test]
+    fn digest_across_blocks() {
+        let crc32c = Crc::new(CRC_32_ISCSI);
+        let data: Vec<u8> = (0..(BLOCK_SIZE * 2 + PIECE_SIZE + 7))
+            .map(|i| (i * 31 % 251) as u8)
+            .collect();
+
+        let digest = crc32c.digest(ChunkedStream::Bytes(data.clone())).unwrap();
+        assert_eq!(
+            digest,
+            (crc32c.checksum(&data) as u32).to_be_bytes().to_vec()
+        );
+    }
+
+    #[test]
+    fn combine_works() {
+        let data = b"The quick brown fox jumps over the lazy dog";
+
+        for params in [
+            CRC_32_ISO_HDLC,
+            CRC_32_ISCSI,
+            CRC_16_CCITT,
+            CRC_64_XZ,
+            CrcParams {
+                width: 16,
+                poly: 0x1021,
+                init: 0xFFFF,
+                refin: false,
+                refout: false,
+                xorout: 0x0000,
+            },
+        ] {
+            let crc = Crc::new(params);
+            let expected = crc.checksum(data);
+
+            for split in [0, 1, 20, data.len()] {
+                let (a, b) = data.split_at(split);
+                assert_eq!(
+                    crc.combine(crc.checksum(a), crc.checksum(b), b.len() as u64),
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn crc32c_combine_works() {
+        let crc32c = Crc::new(CRC_32_ISCSI);
+        let (a, b) = (b"12345".as_slice(), b"6789".as_slice());
+
+        assert_eq!(
+            crc32c.combine(crc32c.checksum(a), crc32c.checksum(b), b.len() as u64),
+            0xe3069283
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use super::{helpers::digest_incremental, Algorithm, DigestResult, Incremental};
+use crate::chunked_stream::ChunkedStream;
+
+/// Chunk size in bytes, even so that 16-bit words never straddle two chunks.
+/// Small enough for the sums not to overflow a u32 before taking the modulo.
+const CHUNK_SIZE: usize = 256;
+
+/// Fletcher checksum over 8-bit (Fletcher-16) or 16-bit (Fletcher-32) words
+///
+/// Reference: https://en.wikipedia.org/wiki/Fletcher%27s_checksum
+#[derive(Clone, Copy)]
+pub enum Fletcher {
+    _16,
+    _32,
+}
+
+/// Running sums of a Fletcher checksum, along with the first byte of a 16-bit word
+/// split between two updates
+struct FletcherState {
+    variant: Fletcher,
+    sum1: u32,
+    sum2: u32,
+    odd_byte: Option<u8>,
+}
+
+impl FletcherState {
+    fn new(variant: Fletcher) -> Self {
+        Self {
+            variant,
+            sum1: 0,
+            sum2: 0,
+            odd_byte: None,
+        }
+    }
+}
+
+impl Incremental for FletcherState {
+    fn update(&mut self, mut data: &[u8]) {
+        match self.variant {
+            Fletcher::_16 => {
+                for chunk in data.chunks(CHUNK_SIZE) {
+                    for &byte in chunk {
+                        self.sum1 += byte as u32;
+                        self.sum2 += self.sum1;
+                    }
+
+                    self.sum1 %= 255;
+                    self.sum2 %= 255;
+                }
+            }
+            Fletcher::_32 => {
+                if let (Some(low), Some((&high, rest))) = (self.odd_byte, data.split_first()) {
+                    self.sum1 = (self.sum1 + u16::from_le_bytes([low, high]) as u32) % 65535;
+                    self.sum2 = (self.sum2 + self.sum1) % 65535;
+                    self.odd_byte = None;
+                    data = rest;
+                }
+
+                for chunk in data.chunks(CHUNK_SIZE) {
+                    // Words are little-endian, only the last chunk can end with an odd byte
+                    let words = chunk.chunks_exact(2);
+                    if let [byte] = words.remainder() {
+                        self.odd_byte = Some(*byte);
+                    }
+                    for word in words {
+                        self.sum1 += u16::from_le_bytes([word[0], word[1]]) as u32;
+                        self.sum2 += self.sum1;
+                    }
+
+                    self.sum1 %= 65535;
+                    self.sum2 %= 65535;
+                }
+            }
+        }
+    }
+
+    fn finalize(&self) -> Vec<u8> {
+        match self.variant {
+            Fletcher::_16 => vec![self.sum2 as u8, self.sum1 as u8],
+            Fletcher::_32 => {
+                let (mut sum1, mut sum2) = (self.sum1, self.sum2);
+                // An odd trailing byte is padded with zero
+                if let Some(byte) = self.odd_byte {
+                    sum1 = (sum1 + byte as u32) % 65535;
+                    sum2 = (sum2 + sum1) % 65535;
+                }
+
+                ((sum2 << 16) | sum1).to_be_bytes().to_vec()
+            }
+        }
+    }
+}
+
+impl Algorithm for Fletcher {
+    fn digest(&self, data: ChunkedStream) -> DigestResult {
+        digest_incremental(&mut FletcherState::new(*self), data, CHUNK_SIZE)
+    }
+
+    fn incremental(&self) -> Option<Box<dyn Incremental>> {
+        Some(Box::new(FletcherState::new(*self)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::algorithms::helpers::test::assert_digest;
+
+    use super::*;
+
+    #[test]
+    fn fletcher16_correct() {
+        for (input, expected) in [
+            ("", "0000"),
+            ("abcde", "c8f0"),
+            ("abcdef", "2057"),
+            ("abcdefgh", "0627"),
+        ] {
+            assert_digest(&Fletcher::_16, input, expected);
+        }
+    }
+
+    #[test]
+    fn fletcher32_correct() {
+        for (input, expected) in [
+            ("", "00000000"),
+            ("abcde", "f04fc729"),
+            ("abcdef", "56502d2a"),
+            ("abcdefgh", "ebe19591"),
+        ] {
+            assert_digest(&Fletcher::_32, input, expected);
+        }
+    }
+}
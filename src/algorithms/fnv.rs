@@ -0,0 +1,145 @@
+use super::{helpers::digest_incremental, Algorithm, DigestResult, Incremental};
+use crate::chunked_stream::ChunkedStream;
+
+const CHUNK_SIZE: usize = 1024;
+
+const OFFSET_BASIS_32: u64 = 0x811c9dc5;
+const PRIME_32: u64 = 0x01000193;
+const OFFSET_BASIS_64: u64 = 0xcbf29ce484222325;
+const PRIME_64: u64 = 0x100000001b3;
+
+/// Fowler–Noll–Vo hash, FNV-1 multiplies before XORing each byte and FNV-1a after.
+///
+/// The offset basis can be replaced to seed the hash.
+///
+/// Reference: https://datatracker.ietf.org/doc/draft-eastlake-fnv/
+pub struct Fnv {
+    variant: FnvVariant,
+    width: FnvWidth,
+    offset_basis: Option<u64>,
+}
+
+#[derive(Clone, Copy)]
+pub enum FnvVariant {
+    Fnv1,
+    Fnv1a,
+}
+
+#[derive(Clone, Copy)]
+pub enum FnvWidth {
+    _32,
+    _64,
+}
+
+impl Fnv {
+    pub fn new(variant: FnvVariant, width: FnvWidth, offset_basis: Option<u64>) -> Self {
+        Self {
+            variant,
+            width,
+            offset_basis,
+        }
+    }
+
+    fn state(&self) -> FnvState {
+        let (default_basis, prime, mask) = match self.width {
+            FnvWidth::_32 => (OFFSET_BASIS_32, PRIME_32, u32::MAX as u64),
+            FnvWidth::_64 => (OFFSET_BASIS_64, PRIME_64, u64::MAX),
+        };
+
+        FnvState {
+            variant: self.variant,
+            width: self.width,
+            prime,
+            mask,
+            hash: self.offset_basis.unwrap_or(default_basis) & mask,
+        }
+    }
+}
+
+struct FnvState {
+    variant: FnvVariant,
+    width: FnvWidth,
+    prime: u64,
+    mask: u64,
+    hash: u64,
+}
+
+impl Incremental for FnvState {
+    fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.hash = match self.variant {
+                FnvVariant::Fnv1 => (self.hash.wrapping_mul(self.prime) & self.mask) ^ byte as u64,
+                FnvVariant::Fnv1a => (self.hash ^ byte as u64).wrapping_mul(self.prime) & self.mask,
+            };
+        }
+    }
+
+    fn finalize(&self) -> Vec<u8> {
+        match self.width {
+            FnvWidth::_32 => (self.hash as u32).to_be_bytes().to_vec(),
+            FnvWidth::_64 => self.hash.to_be_bytes().to_vec(),
+        }
+    }
+}
+
+impl Algorithm for Fnv {
+    fn digest(&self, data: ChunkedStream) -> DigestResult {
+        digest_incremental(&mut self.state(), data, CHUNK_SIZE)
+    }
+
+    fn incremental(&self) -> Option<Box<dyn Incremental>> {
+        Some(Box::new(self.state()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::algorithms::helpers::test::assert_digest;
+
+    use super::*;
+
+    #[test]
+    fn fnv_correct() {
+        for (variant, width, input, expected) in [
+            (FnvVariant::Fnv1, FnvWidth::_32, "", "811c9dc5"),
+            (FnvVariant::Fnv1, FnvWidth::_32, "a", "050c5d7e"),
+            (FnvVariant::Fnv1, FnvWidth::_32, "foobar", "31f0b262"),
+            (FnvVariant::Fnv1a, FnvWidth::_32, "", "811c9dc5"),
+            (FnvVariant::Fnv1a, FnvWidth::_32, "a", "e40c292c"),
+            (FnvVariant::Fnv1a, FnvWidth::_32, "foobar", "bf9cf968"),
+            (FnvVariant::Fnv1, FnvWidth::_64, "", "cbf29ce484222325"),
+            (FnvVariant::Fnv1, FnvWidth::_64, "a", "af63bd4c8601b7be"),
+            (
+                FnvVariant::Fnv1,
+                FnvWidth::_64,
+                "foobar",
+                "340d8765a4dda9c2",
+            ),
+            (FnvVariant::Fnv1a, FnvWidth::_64, "", "cbf29ce484222325"),
+            (FnvVariant::Fnv1a, FnvWidth::_64, "a", "af63dc4c8601ec8c"),
+            (
+                FnvVariant::Fnv1a,
+                FnvWidth::_64,
+                "foobar",
+                "85944171f73967e8",
+            ),
+        ] {
+            assert_digest(&Fnv::new(variant, width, None), input, expected);
+        }
+    }
+
+    #[test]
+    fn custom_offset_basis() {
+        // FNV-0 (offset basis of 0) of "a" is the byte itself, multiplied for FNV-1a
+        assert_digest(
+            &Fnv::new(FnvVariant::Fnv1, FnvWidth::_32, Some(0)),
+            "a",
+            "00000061",
+        );
+        assert_digest(
+            &Fnv::new(FnvVariant::Fnv1a, FnvWidth::_32, Some(0)),
+            "a",
+            "610098b3",
+        );
+    }
+}
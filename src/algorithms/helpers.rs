@@ -1,111 +1,317 @@
+use std::io;
+
+use super::{DigestResult, Incremental, Resumable};
+use crate::chunked_stream::ChunkedStream;
+
 pub enum Endianness {
     Little,
     Big,
 }
 
-/// Pads the given last chunk according to the MD spec.
-/// Returns a Vec of chunks (can contain 1 or 2 chunks)
+/// Last one or two blocks of N bytes of a message, after MD padding
+pub struct PaddedBlocks<const N: usize> {
+    blocks: [[u8; N]; 2],
+    count: usize,
+}
+
+impl<const N: usize> PaddedBlocks<N> {
+    /// Appends bits "10000..." to the last chunk (shorter than N bytes), then the
+    /// length field in the last bytes of the block, spilling over to a second
+    /// block if the length does not fit after the appended byte
+    fn new(last_chunk: &[u8], length_field: &[u8]) -> Self {
+        let mut blocks = [[0; N]; 2];
+        let count = match last_chunk.len() + 1 + length_field.len() <= N {
+            true => 1,
+            false => 2,
+        };
+
+        blocks[0][..last_chunk.len()].copy_from_slice(last_chunk);
+        blocks[0][last_chunk.len()] = 128;
+        blocks[count - 1][N - length_field.len()..].copy_from_slice(length_field);
+
+        Self { blocks, count }
+    }
+}
+
+impl<const N: usize> IntoIterator for PaddedBlocks<N> {
+    type Item = [u8; N];
+    type IntoIter = std::iter::Take<std::array::IntoIter<[u8; N], 2>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.blocks.into_iter().take(self.count)
+    }
+}
+
+/// Pads the given last chunk (shorter than 64 bytes) according to the MD spec.
+/// Returns 1 or 2 blocks of 64 bytes.
 ///
 /// Bits "10000..." are appended until message length is 56 mod 64 bytes.
 /// Then message length is appended as a u64 with the chosen endianness.
 pub fn md_length_padding(
-    last_chunk: &Vec<u8>,
+    last_chunk: &[u8],
     message_length: u64,
     endianness: Endianness,
-) -> Vec<Vec<u8>> {
-    let mut appended = last_chunk.to_owned();
+) -> PaddedBlocks<64> {
     let len_bytes = match endianness {
         Endianness::Little => message_length.to_le_bytes(),
         Endianness::Big => message_length.to_be_bytes(),
     };
 
-    appended.push(128);
-    while appended.len() % 64 != 56 {
-        appended.push(0);
-    }
-    for len_byte in len_bytes {
-        appended.push(len_byte)
-    }
-
-    appended.chunks(64).map(|chunk| chunk.to_owned()).collect()
+    PaddedBlocks::new(last_chunk, &len_bytes)
 }
 
-/// Pads the given last chunk according to the MD spec.
-/// Returns a Vec of 64-byte chunks (can contain 1 or 2 chunks)
+/// Pads the given last chunk (shorter than 128 bytes) according to the MD spec.
+/// Returns 1 or 2 blocks of 128 bytes.
 ///
 /// Bits "10000..." are appended until message length is 112 mod 128 bytes.
 /// Then message length is appended as a u128 with the chosen endianness.
 pub fn md_length_padding_64(
-    last_chunk: &Vec<u8>,
+    last_chunk: &[u8],
     message_length: u128,
     endianness: Endianness,
-) -> Vec<Vec<u8>> {
-    let mut appended = last_chunk.to_owned();
+) -> PaddedBlocks<128> {
     let len_bytes = match endianness {
         Endianness::Little => message_length.to_le_bytes(),
         Endianness::Big => message_length.to_be_bytes(),
     };
 
-    appended.push(128);
-    while appended.len() % 128 != 112 {
-        appended.push(0);
-    }
-    for len_byte in len_bytes {
-        appended.push(len_byte)
+    PaddedBlocks::new(last_chunk, &len_bytes)
+}
+
+/// Returns the first N 32-bit words in the given endianness from a byte slice.
+///
+/// Panics if the slice is shorter than N words, trailing bytes are ignored.
+pub fn exact_32_bit_words<const N: usize>(bytes: &[u8], endianness: Endianness) -> [u32; N] {
+    let mut words = [0; N];
+    for (word, bytes) in words.iter_mut().zip(bytes[..N * 4].chunks_exact(4)) {
+        let bytes = bytes.try_into().unwrap();
+        *word = match endianness {
+            Endianness::Little => u32::from_le_bytes(bytes),
+            Endianness::Big => u32::from_be_bytes(bytes),
+        };
     }
 
-    appended.chunks(128).map(|chunk| chunk.to_owned()).collect()
+    words
 }
 
-/// Returns all 32-bit words in the given endianness from a byte vector.
+/// Returns the first N 64-bit words in the given endianness from a byte slice.
 ///
-/// Trailing bytes that are insufficient to make up a u32 are ignored.
-pub fn exact_32_bit_words(bytes: &[u8], endianness: Endianness) -> Vec<u32> {
-    let mut words = vec![];
-    let limit = (bytes.len() - bytes.len() % 4) / 4;
-    let is_le = matches!(endianness, Endianness::Little);
-
-    for i in 0..limit {
-        let j = i * 4;
-        let u32_bytes = [bytes[j], bytes[j + 1], bytes[j + 2], bytes[j + 3]];
-        words.push(if is_le {
-            u32::from_le_bytes(u32_bytes)
-        } else {
-            u32::from_be_bytes(u32_bytes)
-        });
+/// Panics if the slice is shorter than N words, trailing bytes are ignored.
+pub fn exact_64_bit_words<const N: usize>(bytes: &[u8], endianness: Endianness) -> [u64; N] {
+    let mut words = [0; N];
+    for (word, bytes) in words.iter_mut().zip(bytes[..N * 8].chunks_exact(8)) {
+        let bytes = bytes.try_into().unwrap();
+        *word = match endianness {
+            Endianness::Little => u64::from_le_bytes(bytes),
+            Endianness::Big => u64::from_be_bytes(bytes),
+        };
     }
 
     words
 }
 
-/// Returns all 64-bit words in the given endianness from a byte vector.
-///
-/// Trailing bytes that are insufficient to make up a u64 are ignored.
-pub fn exact_64_bit_words(bytes: &[u8], endianness: Endianness) -> Vec<u64> {
-    let mut words = vec![];
-    let limit = (bytes.len() - bytes.len() % 8) / 8;
-    let is_le = matches!(endianness, Endianness::Little);
-
-    for i in 0..limit {
-        let j = i * 8;
-        let u64_bytes = [
-            bytes[j],
-            bytes[j + 1],
-            bytes[j + 2],
-            bytes[j + 3],
-            bytes[j + 4],
-            bytes[j + 5],
-            bytes[j + 6],
-            bytes[j + 7],
-        ];
-        words.push(if is_le {
-            u64::from_le_bytes(u64_bytes)
-        } else {
-            u64::from_be_bytes(u64_bytes)
+/// Completes the pending partial block of `block_size` bytes with the data, calls
+/// `process` with the whole blocks (several at once where the data holds them),
+/// then keeps the bytes after the last one as the pending block
+fn split_blocks(
+    pending: &mut Vec<u8>,
+    block_size: usize,
+    mut data: &[u8],
+    mut process: impl FnMut(&[u8]),
+) {
+    if !pending.is_empty() {
+        let taken = (block_size - pending.len()).min(data.len());
+        pending.extend_from_slice(&data[..taken]);
+        data = &data[taken..];
+
+        if pending.len() < block_size {
+            return;
+        }
+        process(pending);
+        pending.clear();
+    }
+
+    let whole = data.len() - data.len() % block_size;
+    if whole > 0 {
+        process(&data[..whole]);
+    }
+    pending.extend_from_slice(&data[whole..]);
+}
+
+/// Splits input given in arbitrary pieces into blocks of N bytes,
+/// keeping the bytes that do not make up a whole block yet.
+#[derive(Clone)]
+pub struct BlockBuffer<const N: usize> {
+    pending: Vec<u8>,
+    /// Total number of bytes given so far
+    pub length: u64,
+}
+
+impl<const N: usize> BlockBuffer<N> {
+    pub fn new() -> Self {
+        Self {
+            pending: Vec::with_capacity(N),
+            length: 0,
+        }
+    }
+
+    /// Calls `process` for every block completed by the data
+    pub fn update(&mut self, data: &[u8], mut process: impl FnMut(&[u8])) {
+        self.length += data.len() as u64;
+        split_blocks(&mut self.pending, N, data, |blocks| {
+            blocks.chunks_exact(N).for_each(&mut process)
         });
     }
 
-    words
+    /// Bytes given after the last whole block
+    pub fn remainder(&self) -> &[u8] {
+        &self.pending
+    }
+}
+
+/// Incremental state over a resumable one, keeping the bytes that do not make up
+/// a whole block yet
+pub struct Blockwise {
+    state: Box<dyn Resumable>,
+    pending: Vec<u8>,
+}
+
+impl Blockwise {
+    pub fn new(state: Box<dyn Resumable>) -> Self {
+        Self {
+            pending: Vec::with_capacity(state.block_size()),
+            state,
+        }
+    }
+}
+
+impl Incremental for Blockwise {
+    fn update(&mut self, data: &[u8]) {
+        let block_size = self.state.block_size();
+        split_blocks(&mut self.pending, block_size, data, |blocks| {
+            self.state.update_blocks(blocks)
+        });
+    }
+
+    fn finalize(&self) -> Vec<u8> {
+        self.state.finalize(&self.pending)
+    }
+}
+
+/// Bytes of a message after its last whole block of N bytes, to be padded
+pub struct MessageTail<const N: usize> {
+    block: [u8; N],
+    filled: usize,
+    /// Length of the whole message in bytes
+    pub length: u64,
+}
+
+impl<const N: usize> MessageTail<N> {
+    pub fn bytes(&self) -> &[u8] {
+        &self.block[..self.filled]
+    }
+}
+
+/// Calls `process` with every whole block of N bytes of the stream as it is read,
+/// and returns the bytes left after the last one (possibly none)
+pub fn process_blocks<const N: usize>(
+    data: ChunkedStream,
+    mut process: impl FnMut(&[u8; N]),
+) -> io::Result<MessageTail<N>> {
+    let mut tail = MessageTail {
+        block: [0; N],
+        filled: 0,
+        length: 0,
+    };
+
+    data.for_each_chunk(N, |chunk| {
+        tail.length += chunk.len() as u64;
+        match chunk.try_into() {
+            Ok(block) => process(block),
+            Err(_) => {
+                tail.block[..chunk.len()].copy_from_slice(chunk);
+                tail.filled = chunk.len();
+            }
+        }
+    })?;
+
+    Ok(tail)
+}
+
+/// Word of a saved state, stored in little-endian
+pub trait StateWord: Copy + Default {
+    const BYTES: usize;
+
+    fn write_le(self, out: &mut Vec<u8>);
+    fn read_le(bytes: &[u8]) -> Self;
+}
+
+impl StateWord for u32 {
+    const BYTES: usize = 4;
+
+    fn write_le(self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn read_le(bytes: &[u8]) -> Self {
+        u32::from_le_bytes(bytes.try_into().unwrap())
+    }
+}
+
+impl StateWord for u64 {
+    const BYTES: usize = 8;
+
+    fn write_le(self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn read_le(bytes: &[u8]) -> Self {
+        u64::from_le_bytes(bytes.try_into().unwrap())
+    }
+}
+
+/// Serializes the words of a state followed by the number of bytes processed,
+/// all in little-endian
+pub fn save_words<W: StateWord>(words: &[W], length: u64) -> Vec<u8> {
+    let mut saved = Vec::with_capacity(words.len() * W::BYTES + 8);
+    for word in words {
+        word.write_le(&mut saved);
+    }
+    saved.extend_from_slice(&length.to_le_bytes());
+    saved
+}
+
+/// Parses the words and the number of bytes processed saved by `save_words`
+pub fn restore_words<W: StateWord, const S: usize>(saved: &[u8]) -> io::Result<([W; S], u64)> {
+    if saved.len() != S * W::BYTES + 8 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "saved state is {} bytes long instead of {}",
+                saved.len(),
+                S * W::BYTES + 8
+            ),
+        ));
+    }
+
+    let mut words = [W::default(); S];
+    for (word, bytes) in words.iter_mut().zip(saved.chunks_exact(W::BYTES)) {
+        *word = W::read_le(bytes);
+    }
+    let length = u64::from_le_bytes(saved[S * W::BYTES..].try_into().unwrap());
+
+    Ok((words, length))
+}
+
+/// Digests a stream by feeding each chunk to an incremental state
+pub fn digest_incremental(
+    state: &mut dyn Incremental,
+    data: ChunkedStream,
+    chunk_size: usize,
+) -> DigestResult {
+    data.for_each_chunk(chunk_size, |chunk| state.update(chunk))?;
+
+    Ok(state.finalize())
 }
 
 #[cfg(test)]
@@ -125,60 +331,163 @@ pub mod test {
 mod tests {
     use super::*;
 
+    #[test]
+    fn block_buffer_works() {
+        let mut buffer = BlockBuffer::<4>::new();
+        let mut blocks = vec![];
+
+        for piece in [&b"ab"[..], b"cdefghi", b"", b"j", b"kl"] {
+            buffer.update(piece, |block| blocks.push(block.to_vec()));
+        }
+
+        assert_eq!(
+            blocks,
+            vec![b"abcd".to_vec(), b"efgh".to_vec(), b"ijkl".to_vec()]
+        );
+        assert_eq!(buffer.remainder(), b"");
+        assert_eq!(buffer.length, 12);
+    }
+
+    #[test]
+    fn blockwise_works() {
+        use crate::algorithms::{md5::Md5, Algorithm};
+
+        let data: Vec<u8> = (0..300).map(|i| i as u8).collect();
+        let expected = Md5.digest(ChunkedStream::Bytes(data.clone())).unwrap();
+
+        let mut state = Blockwise::new(Md5.resumable().unwrap());
+        for piece in [&data[..10], &data[10..60], &data[60..64], &data[64..], &[]] {
+            state.update(piece);
+        }
+        assert_eq!(state.finalize(), expected);
+    }
+
+    #[test]
+    fn process_blocks_keeps_tail() {
+        let mut blocks = vec![];
+        let data = ChunkedStream::Bytes(b"abcdefghij".to_vec());
+        let tail = process_blocks::<4>(data, |block| blocks.push(*block)).unwrap();
+
+        assert_eq!(blocks, [*b"abcd", *b"efgh"]);
+        assert_eq!(tail.bytes(), b"ij");
+        assert_eq!(tail.length, 10);
+    }
+
+    #[test]
+    fn resumes_from_saved_states() {
+        use crate::algorithms::{
+            md4::Md4,
+            md5::Md5,
+            sha1::Sha1,
+            sha2::{Sha2, Sha2Variant},
+            sha3::{Sha3, Sha3Variant, Shake, ShakeVariant},
+            Algorithm,
+        };
+
+        let data: Vec<u8> = (0..1000).map(|i| (i * 7 % 251) as u8).collect();
+        let algorithms: [Box<dyn Algorithm>; 7] = [
+            Box::new(Md4),
+            Box::new(Md5),
+            Box::new(Sha1),
+            Box::new(Sha2::new(Sha2Variant::_224)),
+            Box::new(Sha2::new(Sha2Variant::_512_256)),
+            Box::new(Sha3::new(Sha3Variant::_256)),
+            Box::new(Shake::new(ShakeVariant::_128, 400).unwrap()),
+        ];
+        for algorithm in &algorithms {
+            let expected = algorithm
+                .digest(ChunkedStream::Bytes(data.clone()))
+                .unwrap();
+
+            let mut state = algorithm.resumable().unwrap();
+            let block_size = state.block_size();
+            state.update_blocks(&data[..3 * block_size]);
+            let saved = state.save();
+
+            let mut state = algorithm.resumable().unwrap();
+            state.restore(&saved).unwrap();
+            let whole = data.len() - data.len() % block_size;
+            state.update_blocks(&data[3 * block_size..whole]);
+            assert_eq!(state.finalize(&data[whole..]), expected);
+
+            assert_eq!(
+                state.restore(&saved[1..]).unwrap_err().kind(),
+                io::ErrorKind::InvalidData
+            );
+        }
+    }
+
     #[test]
     fn md_padding_works() {
-        let chunk = "abc".as_bytes().to_owned();
+        let chunk = b"abc";
+        let mut expected = [0; 64];
+        expected[..4].copy_from_slice(&[97, 98, 99, 128]);
+
+        expected[56] = 3;
         assert_eq!(
-            md_length_padding(&chunk, 3, Endianness::Little),
-            vec![vec![
-                97, 98, 99, 128, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-                0, 0, 3, 0, 0, 0, 0, 0, 0, 0
-            ]]
+            md_length_padding(chunk, 3, Endianness::Little)
+                .into_iter()
+                .collect::<Vec<_>>(),
+            [expected]
         );
 
+        expected[56] = 0;
+        expected[63] = 3;
         assert_eq!(
-            md_length_padding(&chunk, 3, Endianness::Big),
-            vec![vec![
-                97, 98, 99, 128, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-                0, 0, 0, 0, 0, 0, 0, 0, 0, 3
-            ]]
+            md_length_padding(chunk, 3, Endianness::Big)
+                .into_iter()
+                .collect::<Vec<_>>(),
+            [expected]
         );
+
+        // The length no longer fits after 56 bytes
+        let blocks: Vec<_> = md_length_padding(&[1; 56], 448, Endianness::Big)
+            .into_iter()
+            .collect();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0][55..], [1, 128, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(blocks[1][..56], [0; 56]);
+        assert_eq!(blocks[1][56..], 448u64.to_be_bytes());
+
+        let blocks: Vec<_> = md_length_padding_64(&[], 0, Endianness::Big)
+            .into_iter()
+            .collect();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0][0], 128);
     }
 
     #[test]
     fn exact_32_bit_words_works() {
         assert_eq!(
-            exact_32_bit_words(&[0, 0, 0, 5], Endianness::Big),
-            vec![5u32]
+            exact_32_bit_words::<1>(&[0, 0, 0, 5], Endianness::Big),
+            [5u32]
         );
         assert_eq!(
-            exact_32_bit_words(&[0, 0, 0, 5, 25, 3, 192, 40, 3], Endianness::Little),
-            vec![5u32.to_be(), 0x1903c028u32.to_be()]
+            exact_32_bit_words::<2>(&[0, 0, 0, 5, 25, 3, 192, 40, 3], Endianness::Little),
+            [5u32.to_be(), 0x1903c028u32.to_be()]
         );
         assert_eq!(
-            exact_32_bit_words(&[0, 0, 0, 5, 25, 3, 192, 40, 3, 0, 0], Endianness::Little),
-            vec![5u32.to_be(), 0x1903c028u32.to_be()]
+            exact_32_bit_words::<2>(&[0, 0, 0, 5, 25, 3, 192, 40, 3, 0, 0], Endianness::Little),
+            [5u32.to_be(), 0x1903c028u32.to_be()]
         );
     }
 
     #[test]
     fn exact_64_bit_words_works() {
         assert_eq!(
-            exact_64_bit_words(&[0, 0, 0, 0, 0, 0, 0, 5], Endianness::Big),
-            vec![5u64]
+            exact_64_bit_words::<1>(&[0, 0, 0, 0, 0, 0, 0, 5], Endianness::Big),
+            [5u64]
         );
         assert_eq!(
-            exact_64_bit_words(&[0, 0, 0, 0, 0, 0, 0, 5, 23], Endianness::Big),
-            vec![5u64]
+            exact_64_bit_words::<1>(&[0, 0, 0, 0, 0, 0, 0, 5, 23], Endianness::Big),
+            [5u64]
         );
         assert_eq!(
-            exact_64_bit_words(
+            exact_64_bit_words::<2>(
                 &[0, 0, 0, 0, 0, 0, 0, 5, 0, 0, 0, 0, 0, 0, 0, 6],
                 Endianness::Big
             ),
-            vec![5u64, 6u64]
+            [5u64, 6u64]
         );
     }
 }
@@ -2,192 +2,261 @@
 // - https://nvlpubs.nist.gov/nistpubs/FIPS/NIST.FIPS.202.pdf
 // - https://keccak.team/keccak_specs_summary.html
 
+use std::io;
+
 use super::{
-    helpers::{exact_64_bit_words, Endianness},
-    DigestResult,
+    helpers::{restore_words, save_words},
+    DigestResult, Resumable,
 };
 use crate::chunked_stream::ChunkedStream;
 
-type KState = [u8; 200];
-type KLanes = [[u64; 5]; 5];
+/// State of Keccak-f\[1600\] as 25 lanes of 64 bits, lane (x, y) at index x + 5 * y.
+/// Bytes of the state map to lanes in little-endian order.
+type Lanes = [u64; 25];
+
+/// Rotation offsets of rho, in the order lanes are visited by pi
+const ROTATIONS: [u32; 24] = [
+    1, 3, 6, 10, 15, 21, 28, 36, 45, 55, 2, 14, 27, 41, 56, 8, 25, 43, 62, 18, 39, 61, 20, 44,
+];
+
+/// Lanes visited by pi starting from lane (1, 0), each one moving to the previous one
+const PI_LANES: [usize; 24] = [
+    10, 7, 11, 17, 18, 3, 5, 16, 8, 21, 24, 4, 15, 23, 19, 13, 12, 2, 20, 14, 22, 9, 6, 1,
+];
+
+/// Round constants of iota, as generated by its LFSR
+const ROUND_CONSTANTS: [u64; 24] = [
+    0x0000000000000001,
+    0x0000000000008082,
+    0x800000000000808a,
+    0x8000000080008000,
+    0x000000000000808b,
+    0x0000000080000001,
+    0x8000000080008081,
+    0x8000000000008009,
+    0x000000000000008a,
+    0x0000000000000088,
+    0x0000000080008009,
+    0x000000008000000a,
+    0x000000008000808b,
+    0x800000000000008b,
+    0x8000000000008089,
+    0x8000000000008003,
+    0x8000000000008002,
+    0x8000000000000080,
+    0x000000000000800a,
+    0x800000008000000a,
+    0x8000000080008081,
+    0x8000000000008080,
+    0x0000000080000001,
+    0x8000000080008008,
+];
 
 /// Step-mapping functions required for the Keccak-f\[1600\] function.
 mod step_mapping_funs {
-    use super::KLanes;
-
-    pub fn theta(lanes: &mut KLanes) {
-        let c: Vec<u64> = (0..5)
-            .map(|x| lanes[x][0] ^ lanes[x][1] ^ lanes[x][2] ^ lanes[x][3] ^ lanes[x][4])
-            .collect();
-        let d: Vec<u64> = (0..5)
-            .map(|x| c[(x + 4) % 5] ^ c[(x + 1) % 5].rotate_left(1))
-            .collect();
+    use super::{Lanes, PI_LANES, ROTATIONS, ROUND_CONSTANTS};
+
+    #[inline(always)]
+    pub fn theta(lanes: &mut Lanes) {
+        let c = [
+            lanes[0] ^ lanes[5] ^ lanes[10] ^ lanes[15] ^ lanes[20],
+            lanes[1] ^ lanes[6] ^ lanes[11] ^ lanes[16] ^ lanes[21],
+            lanes[2] ^ lanes[7] ^ lanes[12] ^ lanes[17] ^ lanes[22],
+            lanes[3] ^ lanes[8] ^ lanes[13] ^ lanes[18] ^ lanes[23],
+            lanes[4] ^ lanes[9] ^ lanes[14] ^ lanes[19] ^ lanes[24],
+        ];
 
         for x in 0..5 {
-            for y in 0..5 {
-                lanes[x][y] ^= d[x]
-            }
+            let d = c[(x + 4) % 5] ^ c[(x + 1) % 5].rotate_left(1);
+            lanes[x] ^= d;
+            lanes[x + 5] ^= d;
+            lanes[x + 10] ^= d;
+            lanes[x + 15] ^= d;
+            lanes[x + 20] ^= d;
         }
     }
 
-    pub fn rho_and_pi(lanes: &mut KLanes) {
-        let (mut x, mut y) = (1, 0);
-        let mut current = lanes[x][y];
-
-        for t in 0..24 {
-            let temp = y;
-            y = (2 * x + 3 * y) % 5;
-            x = temp;
-
-            let temp = lanes[x][y];
-            lanes[x][y] = current.rotate_left(((t + 1) * (t + 2)) / 2);
-            current = temp;
+    #[inline(always)]
+    pub fn rho_and_pi(lanes: &mut Lanes) {
+        let mut current = lanes[1];
+        for (&lane, &rotation) in PI_LANES.iter().zip(&ROTATIONS) {
+            let next = lanes[lane];
+            lanes[lane] = current.rotate_left(rotation);
+            current = next;
         }
     }
 
-    pub fn chi(lanes: &mut KLanes) {
-        for y in 0..5 {
-            let t: Vec<u64> = (0..5).map(|x| lanes[x][y]).collect();
-
-            for x in 0..5 {
-                lanes[x][y] = t[x] ^ (!t[(x + 1) % 5] & t[(x + 2) % 5]);
-            }
+    #[inline(always)]
+    pub fn chi(lanes: &mut Lanes) {
+        for y in (0..25).step_by(5) {
+            let row = [
+                lanes[y],
+                lanes[y + 1],
+                lanes[y + 2],
+                lanes[y + 3],
+                lanes[y + 4],
+            ];
+            lanes[y] = row[0] ^ (!row[1] & row[2]);
+            lanes[y + 1] = row[1] ^ (!row[2] & row[3]);
+            lanes[y + 2] = row[2] ^ (!row[3] & row[4]);
+            lanes[y + 3] = row[3] ^ (!row[4] & row[0]);
+            lanes[y + 4] = row[4] ^ (!row[0] & row[1]);
         }
     }
 
-    pub fn iota(lanes: &mut KLanes, r: &mut u8) {
-        for j in 0..7u8 {
-            *r = (*r << 1) ^ ((*r >> 7) * 0x71);
-            if *r & 2 == 2 {
-                lanes[0][0] ^= 1 << ((1 << j) - 1);
-            }
-        }
+    #[inline(always)]
+    pub fn iota(lanes: &mut Lanes, round: usize) {
+        lanes[0] ^= ROUND_CONSTANTS[round];
     }
 }
 
-/// Represents the Keccak\[r, c\] sponge function, but with c omitted.
-/// r + c is assumed to be 1600. Each lane in the state would be a u64.
-///
-/// # Arguments
-///
-/// * `r` - bitrate (multiple of 8)
-/// * `data` - the DataType struct which holds the input data
-/// * `d_suffix` - delimited suffix byte, unique to certain hash functions
-/// * `out_len` - intended number of output bits (has to be multiple of 8)
-pub fn keccak(r: usize, data: ChunkedStream, d_suffix: u8, out_len: usize) -> DigestResult {
-    let mut state = [0; 200];
-    let r_bytes = r / 8;
-    let out_byte_len = out_len / 8;
-
-    // Absorbing phase
-    let mut last_block: Option<Vec<u8>> = None;
-    for block in data.into_iter(r_bytes) {
-        if last_block.is_some() {
-            let last_block = last_block.unwrap();
-            for i in 0..r_bytes {
-                state[i] ^= last_block[i];
-            }
-
-            state = keccak_f_1600(state);
-        }
+/// Keccak-f\[1600\] function for w = 6 (64-bit) with 24 rounds
+fn keccak_f_1600(lanes: &mut Lanes) {
+    use step_mapping_funs::*;
 
-        last_block = Some(block?);
+    for round in 0..24 {
+        theta(lanes);
+        rho_and_pi(lanes);
+        chi(lanes);
+        iota(lanes, round);
     }
+}
 
-    // Process last block(s)
-    let mut last_bytes = last_block.unwrap_or_default();
-    last_bytes.push(d_suffix);
-    while last_bytes.len() % r_bytes != r_bytes - 1 {
-        last_bytes.push(0);
+/// XORs a block of input into the first lanes of the state
+fn absorb(lanes: &mut Lanes, block: &[u8]) {
+    for (lane, bytes) in lanes.iter_mut().zip(block.chunks_exact(8)) {
+        *lane ^= u64::from_le_bytes(bytes.try_into().unwrap());
     }
-    last_bytes.push(0x80);
-
-    for block in last_bytes.chunks(r_bytes) {
-        for i in 0..r_bytes {
-            state[i] ^= block[i];
-        }
+}
 
-        state = keccak_f_1600(state);
-    }
+/// State of the Keccak\[r, c\] sponge function between blocks of r bits, with c
+/// omitted as r + c is assumed to be 1600
+pub struct Sponge {
+    lanes: Lanes,
+    /// Bitrate in bytes
+    r_bytes: usize,
+    d_suffix: u8,
+    out_len: usize,
+    /// Number of bytes absorbed
+    length: u64,
+}
 
-    // Squeezing phase
-    let mut out = vec![];
-    let mut bytes_left = out_byte_len;
-    while bytes_left > 0 {
-        let block_size = out_byte_len.min(r_bytes);
-        for i in 0..block_size {
-            out.push(state[i]);
+impl Sponge {
+    /// # Arguments
+    ///
+    /// * `r` - bitrate (multiple of 64)
+    /// * `d_suffix` - delimited suffix byte, unique to certain hash functions
+    /// * `out_len` - intended number of output bits (has to be multiple of 8)
+    pub fn new(r: usize, d_suffix: u8, out_len: usize) -> Self {
+        debug_assert!(r.is_multiple_of(64) && r < 1600);
+
+        Self {
+            lanes: [0; 25],
+            r_bytes: r / 8,
+            d_suffix,
+            out_len,
+            length: 0,
         }
-        bytes_left -= block_size;
+    }
 
-        if bytes_left > 0 {
-            state = keccak_f_1600(state);
-        }
+    fn absorb_block(&mut self, block: &[u8]) {
+        absorb(&mut self.lanes, block);
+        keccak_f_1600(&mut self.lanes);
+        self.length += self.r_bytes as u64;
     }
 
-    Ok(out)
-}
+    /// Absorbs the whole stream, keeping the last partial block for padding, then
+    /// squeezes the output
+    pub fn digest(mut self, data: ChunkedStream) -> DigestResult {
+        let r_bytes = self.r_bytes;
+        let mut last_block = [0; 200];
+        let mut last_len = 0;
+        data.for_each_chunk(r_bytes, |block| {
+            if block.len() < r_bytes {
+                last_block[..block.len()].copy_from_slice(block);
+                last_len = block.len();
+                return;
+            }
 
-/// Represents the Keccak-f\[1600\] function for w = 6 (64-bit)
-/// with 24 rounds. Accepts a 5x5 array of u64s defined as lanes.
-fn keccak_f_1600_on_lanes(lanes: &mut KLanes) {
-    let mut r: u8 = 1;
-    use step_mapping_funs::*;
+            self.absorb_block(block);
+        })?;
 
-    for _round in 0..24 {
-        theta(lanes);
-        rho_and_pi(lanes);
-        chi(lanes);
-        iota(lanes, &mut r);
+        Ok(self.finalize(&last_block[..last_len]))
     }
 }
 
-/// Intermediary function for Keccak-f\[1600\] that accepts state
-/// (200-byte array) instead of lanes.
-fn keccak_f_1600(state: KState) -> KState {
-    let mut lanes = state_to_lanes(state);
-    keccak_f_1600_on_lanes(&mut lanes);
-
-    lanes_to_state(lanes)
-}
-
-fn state_to_lanes(state: KState) -> KLanes {
-    let u64_words = exact_64_bit_words(&state.to_vec(), Endianness::Little);
+impl Resumable for Sponge {
+    fn block_size(&self) -> usize {
+        self.r_bytes
+    }
 
-    let mut lanes = [[0; 5]; 5];
-    for x in 0..5 {
-        for y in 0..5 {
-            lanes[x][y] = u64_words[x + 5 * y];
+    fn update_blocks(&mut self, blocks: &[u8]) {
+        for block in blocks.chunks_exact(self.r_bytes) {
+            self.absorb_block(block);
         }
     }
 
-    lanes
-}
+    fn finalize(&self, tail: &[u8]) -> Vec<u8> {
+        let mut lanes = self.lanes;
+        let r_bytes = self.r_bytes;
+
+        // The suffix and the final bit of the padding share a byte if only one is left
+        let mut last_block = [0; 200];
+        last_block[..tail.len()].copy_from_slice(tail);
+        last_block[tail.len()] ^= self.d_suffix;
+        last_block[r_bytes - 1] ^= 0x80;
+        absorb(&mut lanes, &last_block[..r_bytes]);
+        keccak_f_1600(&mut lanes);
+
+        // Squeezing phase
+        let out_bytes = self.out_len / 8;
+        let mut out = Vec::with_capacity(out_bytes);
+        loop {
+            let block_size = (out_bytes - out.len()).min(r_bytes);
+            let start = out.len();
+            for lane in &lanes[..block_size.div_ceil(8)] {
+                out.extend_from_slice(&lane.to_le_bytes());
+            }
+            out.truncate(start + block_size);
 
-fn lanes_to_state(lanes: KLanes) -> KState {
-    let mut u64_words = [0; 25];
-    for x in 0..5 {
-        for y in 0..5 {
-            u64_words[x + 5 * y] = lanes[x][y]
+            if out.len() == out_bytes {
+                return out;
+            }
+            keccak_f_1600(&mut lanes);
         }
     }
 
-    let mut state = [0; 200];
-    for i in 0..25 {
-        let bytes = u64_words[i].to_le_bytes();
-        for j in 0..8 {
-            state[8 * i + j] = bytes[j];
-        }
+    fn save(&self) -> Vec<u8> {
+        save_words(&self.lanes, self.length)
     }
 
-    state
+    fn restore(&mut self, saved: &[u8]) -> io::Result<()> {
+        (self.lanes, self.length) = restore_words(saved)?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::*;
+    use super::{
+        super::helpers::{exact_64_bit_words, Endianness},
+        *,
+    };
+
+    fn state_to_lanes(state: [u8; 200]) -> Lanes {
+        exact_64_bit_words(&state, Endianness::Little)
+    }
+
+    fn lanes_to_state(lanes: Lanes) -> [u8; 200] {
+        let mut state = [0; 200];
+        for (bytes, lane) in state.chunks_exact_mut(8).zip(lanes) {
+            bytes.copy_from_slice(&lane.to_le_bytes());
+        }
+        state
+    }
 
     #[test]
+    #[allow(clippy::manual_memcpy)]
     fn state_to_lanes_conversion() {
         let mut state = [0; 200];
         let zero_zeroth: [u8; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
@@ -201,18 +270,17 @@ mod test {
         }
 
         let lanes = state_to_lanes(state);
-        assert_eq!(0x807060504030201, lanes[0][0]);
-        assert_eq!(0x102030405060708, lanes[2][4]);
+        assert_eq!(0x807060504030201, lanes[0]);
+        assert_eq!(0x102030405060708, lanes[2 + 5 * 4]);
     }
 
     #[test]
     fn lanes_to_state_conversion() {
-        let mut lanes = [[0; 5]; 5];
-        lanes[0][0] = 0x807060504030201;
-        lanes[2][4] = 0x102030405060708;
+        let mut lanes = [0; 25];
+        lanes[0] = 0x807060504030201;
+        lanes[2 + 5 * 4] = 0x102030405060708;
 
         let state = lanes_to_state(lanes);
-        println!("{:?}", state);
         assert_eq!(1, state[0]);
         assert_eq!(2, state[1]);
         assert_eq!(3, state[2]);
@@ -226,6 +294,7 @@ mod test {
 
     // Tests for step mapping functions from  https://csrc.nist.gov/CSRC/media/Projects/Cryptographic-Standards-and-Guidelines/documents/examples/SHA3-224_Msg0.pdf
     #[test]
+    #[allow(clippy::zero_prefixed_literal)]
     fn correct_theta() {
         let state = [
             0x06, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
@@ -256,6 +325,7 @@ mod test {
     }
 
     #[test]
+    #[allow(clippy::zero_prefixed_literal)]
     fn correct_rho_and_pi() {
         let state = [
             0x06, 0, 0, 0, 0, 0, 0, 0, 0x07, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
@@ -333,8 +403,7 @@ mod test {
         ];
 
         let mut lanes = state_to_lanes(state);
-        let mut r = 1;
-        step_mapping_funs::iota(&mut lanes, &mut r);
+        step_mapping_funs::iota(&mut lanes, 0);
 
         assert_eq!(
             [
@@ -354,11 +423,36 @@ mod test {
 
     #[test]
     fn keccak_f_1600_correct() {
-        let mut lanes: KLanes = [[0; 5]; 5];
-        lanes[0][0] = 0x06;
-        lanes[2][3] = 0x8000000000000000;
+        let mut lanes = [0; 25];
+        lanes[0] = 0x06;
+        lanes[2 + 5 * 3] = 0x8000000000000000;
+
+        keccak_f_1600(&mut lanes);
+        assert_eq!(0xb7db673642034e6b, lanes[0]);
+    }
+
+    #[test]
+    fn round_constants_match_lfsr() {
+        let mut r: u8 = 1;
+        for constant in ROUND_CONSTANTS {
+            let mut expected = 0u64;
+            for j in 0..7 {
+                r = (r << 1) ^ ((r >> 7) * 0x71);
+                if r & 2 == 2 {
+                    expected ^= 1 << ((1 << j) - 1);
+                }
+            }
+            assert_eq!(constant, expected);
+        }
+    }
 
-        keccak_f_1600_on_lanes(&mut lanes);
-        assert_eq!(0xb7db673642034e6b, lanes[0][0]);
+    #[test]
+    fn pads_in_a_single_byte() {
+        // One byte short of the rate, the suffix and the final bit share the last byte
+        let data = ChunkedStream::Bytes(vec![0; 135]);
+        assert_eq!(
+            hex::encode(Sponge::new(1088, 0x06, 256).digest(data).unwrap()),
+            "7d080d7ba978a75c8a7d1f9be566c859084509c9c2b4928435c225d5777d98e3"
+        );
     }
 }
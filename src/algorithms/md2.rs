@@ -1,4 +1,7 @@
-use super::Algorithm;
+use super::{
+    helpers::{digest_incremental, BlockBuffer},
+    Algorithm, Incremental,
+};
 use crate::chunked_stream::ChunkedStream;
 
 const CHUNK_SIZE: usize = 16;
@@ -27,33 +30,46 @@ const S_TABLE: [u8; 256] = [
 /// Reference: https://www.rfc-editor.org/info/rfc1319 (refer errata for correction)
 pub struct Md2;
 
+#[derive(Clone)]
 struct Md2State {
     md_buffer: [u8; 48],
     checksum: [u8; 16],
     l: u8,
 }
 
-impl Algorithm for Md2 {
-    fn digest(&self, data: ChunkedStream) -> super::DigestResult {
-        let mut state = Md2State {
-            md_buffer: [0; 48],
-            checksum: [0; 16],
-            l: 0,
-        };
+/// MD2 state fed input piece by piece, keeping the bytes of the chunk in progress
+struct Md2Incremental {
+    state: Md2State,
+    buffer: BlockBuffer<CHUNK_SIZE>,
+}
+
+impl Md2Incremental {
+    fn new() -> Self {
+        Self {
+            state: Md2State {
+                md_buffer: [0; 48],
+                checksum: [0; 16],
+                l: 0,
+            },
+            buffer: BlockBuffer::new(),
+        }
+    }
+}
 
-        // Process each chunk via last_chunk, mutating md_buf,
+impl Incremental for Md2Incremental {
+    fn update(&mut self, data: &[u8]) {
+        // Process each whole chunk, mutating md_buf,
         // checksum and l at the same time
-        let mut last_chunk: Option<Vec<u8>> = None;
-        for chunk in data.into_iter(CHUNK_SIZE) {
-            if let Some(last_chunk) = last_chunk {
-                process_chunk(&last_chunk, &mut state);
-            }
+        let state = &mut self.state;
+        self.buffer
+            .update(data, |chunk| process_chunk(chunk, state));
+    }
 
-            last_chunk = Some(chunk?);
-        }
+    fn finalize(&self) -> Vec<u8> {
+        let mut state = self.state.clone();
 
-        // Pad the last chunk of the stream
-        let padded_last_chunk = pad_md2(last_chunk.unwrap_or_default());
+        // Pad the bytes left after the last whole chunk, a whole chunk of padding if none
+        let padded_last_chunk = pad_md2(self.buffer.remainder().to_vec());
         for chunk in padded_last_chunk.chunks(CHUNK_SIZE) {
             process_chunk(chunk, &mut state);
         }
@@ -62,13 +78,22 @@ impl Algorithm for Md2 {
         let checksum_chunk = state.checksum.to_vec();
         process_chunk(&checksum_chunk, &mut state);
 
-        Ok(state.md_buffer[0..16].to_vec())
+        state.md_buffer[0..16].to_vec()
+    }
+}
+
+impl Algorithm for Md2 {
+    fn digest(&self, data: ChunkedStream) -> super::DigestResult {
+        digest_incremental(&mut Md2Incremental::new(), data, 65536)
+    }
+
+    fn incremental(&self) -> Option<Box<dyn Incremental>> {
+        Some(Box::new(Md2Incremental::new()))
     }
 }
 
 fn process_chunk(chunk: &[u8], state: &mut Md2State) {
-    for i in 0..16 {
-        let c = chunk[i];
+    for (i, &c) in chunk.iter().enumerate().take(16) {
         state.md_buffer[i + 16] = c;
         state.md_buffer[i + 32] = state.md_buffer[i] ^ c;
 
@@ -1,8 +1,13 @@
 // Reference: https://en.wikipedia.org/wiki/MD5
 
+use std::io;
+
 use super::{
-    helpers::{exact_32_bit_words, md_length_padding, Endianness},
-    Algorithm,
+    helpers::{
+        exact_32_bit_words, md_length_padding, process_blocks, restore_words, save_words,
+        Endianness,
+    },
+    Algorithm, Resumable,
 };
 use crate::chunked_stream::ChunkedStream;
 
@@ -36,43 +41,83 @@ pub struct Md5;
 
 impl Algorithm for Md5 {
     fn digest(&self, data: ChunkedStream) -> super::DigestResult {
-        // Initial MD buffer
-        let mut md_buf = INIT_MD_BUFFER;
-
-        // Process each chunk via last_chunk and increments length
-        let mut last_chunk: Option<Vec<u8>> = None;
-        let mut len: u64 = 0;
-        for chunk in data.into_iter(CHUNK_SIZE) {
-            if let Some(last_chunk) = last_chunk {
-                process_chunk(&last_chunk, &mut md_buf);
-            }
-
-            let chunk_bytes = chunk?;
-            len = len.wrapping_add((chunk_bytes.len() * 8) as u64);
-            last_chunk = Some(chunk_bytes);
+        let mut state = Md5State::new();
+
+        // Process whole chunks as they are read, then the padded last chunk(s)
+        let tail = process_blocks::<CHUNK_SIZE>(data, |chunk| state.process(chunk))?;
+        Ok(state.finalize(tail.bytes()))
+    }
+
+    fn resumable(&self) -> Option<Box<dyn Resumable>> {
+        Some(Box::new(Md5State::new()))
+    }
+}
+
+/// MD buffer between chunks, along with the number of bytes processed
+struct Md5State {
+    md_buf: MdBuffer,
+    length: u64,
+}
+
+impl Md5State {
+    fn new() -> Self {
+        Self {
+            md_buf: INIT_MD_BUFFER,
+            length: 0,
+        }
+    }
+
+    fn process(&mut self, chunk: &[u8; CHUNK_SIZE]) {
+        process_chunk(chunk, &mut self.md_buf);
+        self.length += CHUNK_SIZE as u64;
+    }
+}
+
+impl Resumable for Md5State {
+    fn block_size(&self) -> usize {
+        CHUNK_SIZE
+    }
+
+    fn update_blocks(&mut self, blocks: &[u8]) {
+        for chunk in blocks.chunks_exact(CHUNK_SIZE) {
+            self.process(chunk.try_into().unwrap());
         }
+    }
 
-        // Process remaining chunk(s) after padding the last chunk
-        let last_chunk = last_chunk.unwrap_or_default();
-        for chunk in md_length_padding(&last_chunk, len, Endianness::Little) {
+    fn finalize(&self, tail: &[u8]) -> Vec<u8> {
+        let mut md_buf = self.md_buf;
+        let len = (self.length + tail.len() as u64).wrapping_mul(8);
+        for chunk in md_length_padding(tail, len, Endianness::Little) {
             process_chunk(&chunk, &mut md_buf);
         }
 
         let (a, b, c, d) = md_buf;
-        Ok([
+        [
             a.to_le_bytes(),
             b.to_le_bytes(),
             c.to_le_bytes(),
             d.to_le_bytes(),
         ]
-        .concat())
+        .concat()
+    }
+
+    fn save(&self) -> Vec<u8> {
+        let (a, b, c, d) = self.md_buf;
+        save_words(&[a, b, c, d], self.length)
+    }
+
+    fn restore(&mut self, saved: &[u8]) -> io::Result<()> {
+        let ([a, b, c, d], length) = restore_words(saved)?;
+        self.md_buf = (a, b, c, d);
+        self.length = length;
+        Ok(())
     }
 }
 
 /// Processes a chunk and mutates the MD buffer accordingly.
 /// Ignores if the chunk is None.
-fn process_chunk(chunk: &[u8], (a0, b0, c0, d0): &mut MdBuffer) {
-    let words = exact_32_bit_words(&chunk, Endianness::Little);
+fn process_chunk(chunk: &[u8; CHUNK_SIZE], (a0, b0, c0, d0): &mut MdBuffer) {
+    let words: [u32; 16] = exact_32_bit_words(chunk, Endianness::Little);
 
     // Main loop
     let (a_n, b_n, c_n, d_n) = (0..64).fold((*a0, *b0, *c0, *d0), |(a, b, c, d), i: usize| {
@@ -1,5 +1,3 @@
-use std::io;
-
 use rayon::prelude::*;
 
 use crate::{
@@ -7,7 +5,7 @@ use crate::{
     chunked_stream::ChunkedStream,
 };
 
-use super::{Algorithm, DigestResult};
+use super::{Algorithm, DigestResult, Incremental};
 
 // Represents fractional part of sqrt(6)
 const Q: [u64; 15] = [
@@ -39,6 +37,10 @@ const S_STAR: u64 = 0x7311c2812425cfa0;
 // Mode parameter
 const L: u64 = 64;
 
+const BLOCK_SIZE_BYTES: usize = 64 * 8;
+/// Size of the output of a compression, 16 words
+const OUTPUT_SIZE_BYTES: usize = 16 * 8;
+
 struct Key {
     /// Padded key value in words
     value: [u64; 8],
@@ -53,20 +55,16 @@ impl From<Vec<u8>> for Key {
         if length >= 64 {
             // Truncate keys larger than 64 bytes
             Self {
-                value: exact_64_bit_words(&key[..64], Endianness::Big)
-                    .try_into()
-                    .unwrap(),
+                value: exact_64_bit_words(&key[..64], Endianness::Big),
                 length: 64,
             }
         } else {
             let pad_byte_count = 64 - key.len();
 
-            key.extend(std::iter::repeat(0).take(pad_byte_count));
+            key.extend(std::iter::repeat_n(0, pad_byte_count));
 
             Self {
-                value: exact_64_bit_words(&key, Endianness::Big)
-                    .try_into()
-                    .unwrap(),
+                value: exact_64_bit_words(&key, Endianness::Big),
                 length,
             }
         }
@@ -100,14 +98,120 @@ impl Algorithm for Md6 {
     fn digest(&self, data: ChunkedStream) -> DigestResult {
         self.digest_keyed(data, None)
     }
+
+    fn incremental(&self) -> Option<Box<dyn Incremental>> {
+        Some(Box::new(Md6State::new(self, None)))
+    }
+}
+
+/// Blocks of a level of the tree which are not compressed yet
+#[derive(Clone, Default)]
+struct Level {
+    pending: Vec<u8>,
+    /// Number of blocks of the level compressed so far
+    compressed: usize,
+}
+
+/// State of MD6 for input given in pieces, which builds the tree as the input comes
+/// in rather than compressing every level at once.
+///
+/// The last block of a level is compressed differently, with its padding and whether
+/// it is the root, so every level keeps a whole block until more input reaches it.
+struct Md6State {
+    md6: Md6,
+    key: Key,
+    /// Levels of the tree from the message up
+    levels: Vec<Level>,
+}
+
+impl Md6State {
+    fn new(md6: &Md6, key: Option<Vec<u8>>) -> Self {
+        Self {
+            md6: Md6 {
+                output_length: md6.output_length,
+                rounds: md6.rounds,
+            },
+            key: key.map(|k| k.into()).unwrap_or(Key {
+                value: [0; 8],
+                length: 0,
+            }),
+            levels: vec![Level::default()],
+        }
+    }
+
+    /// Adds data to the level at `index`, compressing its blocks but the last
+    fn push(&self, levels: &mut Vec<Level>, index: usize, mut data: &[u8]) {
+        while !data.is_empty() {
+            let level = &mut levels[index];
+            if level.pending.len() == BLOCK_SIZE_BYTES {
+                let output = self.md6.compress_block(
+                    &level.pending,
+                    &self.key,
+                    index + 1,
+                    level.compressed,
+                    0,
+                    false,
+                );
+                level.pending.clear();
+                level.compressed += 1;
+
+                if levels.len() == index + 1 {
+                    levels.push(Level::default());
+                }
+                self.push(levels, index + 1, &output);
+                continue;
+            }
+
+            let taken = (BLOCK_SIZE_BYTES - level.pending.len()).min(data.len());
+            level.pending.extend_from_slice(&data[..taken]);
+            data = &data[taken..];
+        }
+    }
+}
+
+impl Incremental for Md6State {
+    fn update(&mut self, data: &[u8]) {
+        let mut levels = std::mem::take(&mut self.levels);
+        self.push(&mut levels, 0, data);
+        self.levels = levels;
+    }
+
+    fn finalize(&self) -> Vec<u8> {
+        let mut levels = self.levels.clone();
+        let mut index = 0;
+
+        loop {
+            let level = &levels[index];
+            let padding = BLOCK_SIZE_BYTES - level.pending.len();
+            let mut block = level.pending.clone();
+            block.resize(BLOCK_SIZE_BYTES, 0);
+
+            let root = level.compressed == 0;
+            let output = self.md6.compress_block(
+                &block,
+                &self.key,
+                index + 1,
+                level.compressed,
+                padding,
+                root,
+            );
+            if root {
+                return output[(OUTPUT_SIZE_BYTES - self.md6.output_length / 8)..].to_vec();
+            }
+
+            if levels.len() == index + 1 {
+                levels.push(Level::default());
+            }
+            self.push(&mut levels, index + 1, &output);
+            index += 1;
+        }
+    }
 }
 
 impl Md6 {
     fn digest_keyed(&self, data: ChunkedStream, key: Option<Vec<u8>>) -> DigestResult {
-        let message_chunks = data
-            .into_iter(65536)
-            .collect::<io::Result<Vec<Vec<u8>>>>()?;
-        let mut message: Vec<u8> = message_chunks.into_iter().flatten().collect();
+        let mut message = vec![];
+        data.for_each_chunk(65536, |chunk| message.extend_from_slice(chunk))?;
         let key: Key = key.map(|k| k.into()).unwrap_or(Key {
             value: [0; 8],
             length: 0,
@@ -116,21 +220,19 @@ impl Md6 {
         let mut l = 0;
 
         loop {
-            l = l + 1;
+            l += 1;
 
             message = self.par(&message, &key, l);
 
-            if message.len() == 16 * 8 {
-                return Ok(message[(16 * 8 - self.output_length / 8)..].to_vec());
+            if message.len() == OUTPUT_SIZE_BYTES {
+                return Ok(message[(OUTPUT_SIZE_BYTES - self.output_length / 8)..].to_vec());
             }
         }
     }
 
     fn par(&self, message: &[u8], key: &Key, level: usize) -> Vec<u8> {
-        const BLOCK_SIZE_BYTES: usize = 64 * 8;
-
         let len_modulo = message.len() % BLOCK_SIZE_BYTES;
-        let padding_byte_count = if message.len() == 0 || len_modulo > 0 {
+        let padding_byte_count = if message.is_empty() || len_modulo > 0 {
             BLOCK_SIZE_BYTES - len_modulo
         } else {
             0
@@ -138,49 +240,69 @@ impl Md6 {
 
         let mut padded_message = message.to_vec();
 
-        padded_message.extend(std::iter::repeat(0).take(padding_byte_count));
+        padded_message.extend(std::iter::repeat_n(0, padding_byte_count));
 
-        let words = exact_64_bit_words(&padded_message, Endianness::Big);
-        let blocks: Vec<&[u64]> = words.chunks(BLOCK_SIZE_BYTES / 8).collect();
+        let block_count = padded_message.len() / BLOCK_SIZE_BYTES;
 
-        blocks
-            .par_iter()
+        padded_message
+            .par_chunks(BLOCK_SIZE_BYTES)
             .enumerate()
             .map(|(i, block)| {
-                let p: u64 = if i == blocks.len() - 1 {
-                    (padding_byte_count as u64) * 8
-                } else {
-                    0
+                let padding = match i == block_count - 1 {
+                    true => padding_byte_count,
+                    false => 0,
                 };
-                let z: u64 = if blocks.len() == 1 { 1 } else { 0 };
-
-                let v = (0 << 60)
-                    | ((self.rounds as u64 & 0xFFF) << 48)
-                    | ((L & 0xFF) << 40)
-                    | ((z & 0xF) << 36)
-                    | ((p & 0xFFFF) << 20)
-                    | ((key.length as u64 & 0xFF) << 12)
-                    | (self.output_length as u64 & 0xFFF);
-
-                let u = ((level as u64) << 56) + (i as u64);
-
-                let mut compress_input = [0u64; N];
-
-                compress_input[..15].copy_from_slice(&Q);
-                compress_input[15..23].copy_from_slice(&key.value);
-                compress_input[23] = u;
-                compress_input[24] = v;
-                compress_input[25..].copy_from_slice(block);
-
-                self.compress(compress_input)
-                    .map(|word| word.to_be_bytes())
-                    .to_vec()
-                    .into_flattened()
+
+                self.compress_block(block, key, level, i, padding, block_count == 1)
             })
-            .flatten()
+            .flatten_iter()
             .collect()
     }
 
+    /// Compresses the block at `index` of a level. `padding` is the number of zero
+    /// bytes at the end of the block, and `root` whether it is the only block of its level.
+    fn compress_block(
+        &self,
+        block: &[u8],
+        key: &Key,
+        level: usize,
+        index: usize,
+        padding: usize,
+        root: bool,
+    ) -> [u8; OUTPUT_SIZE_BYTES] {
+        let p = (padding as u64) * 8;
+        let z: u64 = if root { 1 } else { 0 };
+
+        let v = ((self.rounds as u64 & 0xFFF) << 48)
+            | ((L & 0xFF) << 40)
+            | ((z & 0xF) << 36)
+            | ((p & 0xFFFF) << 20)
+            | ((key.length as u64 & 0xFF) << 12)
+            | (self.output_length as u64 & 0xFFF);
+
+        let u = ((level as u64) << 56) + (index as u64);
+
+        let mut compress_input = [0u64; N];
+
+        compress_input[..15].copy_from_slice(&Q);
+        compress_input[15..23].copy_from_slice(&key.value);
+        compress_input[23] = u;
+        compress_input[24] = v;
+        compress_input[25..].copy_from_slice(&exact_64_bit_words::<{ BLOCK_SIZE_BYTES / 8 }>(
+            block,
+            Endianness::Big,
+        ));
+
+        let mut output = [0; OUTPUT_SIZE_BYTES];
+        for (bytes, word) in output
+            .chunks_exact_mut(8)
+            .zip(self.compress(compress_input))
+        {
+            bytes.copy_from_slice(&word.to_be_bytes());
+        }
+        output
+    }
+
     fn compress(&self, input: [u64; N]) -> [u64; 16] {
         let t = self.rounds * 16;
         let mut a = vec![0; t + N];
@@ -217,7 +339,9 @@ fn get_next_s(s: u64) -> u64 {
 mod test {
     use crate::chunked_stream::ChunkedStream;
 
-    use super::Md6;
+    use crate::algorithms::Incremental;
+
+    use super::{Md6, Md6State};
 
     struct TestCase {
         data: ChunkedStream,
@@ -272,4 +396,27 @@ mod test {
             assert_eq!(test_case.expected, digest_hex);
         }
     }
+
+    #[test]
+    fn incremental_matches_digest() {
+        let md6 = Md6::new(256, Some(5));
+        let key = b"abcde12345".to_vec();
+
+        // Up to three levels of the tree, with partial blocks at every level
+        for length in [0, 1, 511, 512, 513, 2048, 2049, 512 * 16 + 1] {
+            let data: Vec<u8> = (0..length).map(|i| (i * 31 % 251) as u8).collect();
+
+            for key in [None, Some(key.clone())] {
+                let mut state = Md6State::new(&md6, key.clone());
+                for piece in data.chunks(100) {
+                    state.update(piece);
+                }
+
+                let expected = md6
+                    .digest_keyed(ChunkedStream::Bytes(data.clone()), key)
+                    .unwrap();
+                assert_eq!(state.finalize(), expected, "length {}", length);
+            }
+        }
+    }
 }
@@ -2,19 +2,72 @@ use std::io;
 
 use crate::chunked_stream::ChunkedStream;
 
+use helpers::Blockwise;
+
+pub mod adler32;
+pub mod crc;
+pub mod fletcher;
+pub mod fnv;
 pub mod md2;
 pub mod md4;
 pub mod md5;
 pub mod md6;
+pub mod murmur3;
 pub mod sha1;
 pub mod sha2;
 pub mod sha3;
+pub mod siphash;
+pub mod ssdeep;
+pub mod tlsh;
+pub mod xxhash;
 
 mod helpers;
 mod keccak;
 
 type DigestResult = Result<Vec<u8>, io::Error>;
 
-pub trait Algorithm {
+pub trait Algorithm: Send + Sync {
     fn digest(&self, data: ChunkedStream) -> DigestResult;
+
+    /// Returns a state that can be fed input piece by piece, None if the
+    /// algorithm only supports digesting a whole stream. Defaults to the
+    /// resumable state, if any, fed whole blocks.
+    fn incremental(&self) -> Option<Box<dyn Incremental>> {
+        self.resumable()
+            .map(|state| Box::new(Blockwise::new(state)) as Box<dyn Incremental>)
+    }
+
+    /// Returns a state that can be saved between blocks and restored later to
+    /// carry on hashing, None if the algorithm does not support checkpoints
+    fn resumable(&self) -> Option<Box<dyn Resumable>> {
+        None
+    }
+}
+
+/// Incremental state of an algorithm
+pub trait Incremental: Send + Sync {
+    fn update(&mut self, data: &[u8]);
+
+    /// Digest of the input so far, the state can still be updated afterwards
+    fn finalize(&self) -> Vec<u8>;
+}
+
+/// State of an algorithm which processes its input in blocks, which can be saved
+/// after any whole block and restored to resume from the same point
+pub trait Resumable: Send + Sync {
+    /// Number of bytes in a block
+    fn block_size(&self) -> usize;
+
+    /// Processes whole blocks, the length of `blocks` must be a multiple of the block size
+    fn update_blocks(&mut self, blocks: &[u8]);
+
+    /// Digest of the blocks processed so far followed by `tail`, which is shorter
+    /// than a block
+    fn finalize(&self, tail: &[u8]) -> Vec<u8>;
+
+    /// Internal state, including the number of bytes processed if the algorithm needs it
+    fn save(&self) -> Vec<u8>;
+
+    /// Restores a state returned by `save`, failing with `InvalidData` if it is not one
+    fn restore(&mut self, saved: &[u8]) -> io::Result<()>;
 }
@@ -0,0 +1,226 @@
+use super::{
+    helpers::{digest_incremental, BlockBuffer},
+    Algorithm, DigestResult, Incremental,
+};
+use crate::chunked_stream::ChunkedStream;
+
+const CHUNK_SIZE: usize = 1024;
+
+const C1_32: u32 = 0xcc9e2d51;
+const C2_32: u32 = 0x1b873593;
+const C1_64: u64 = 0x87c37b91114253d5;
+const C2_64: u64 = 0x4cf5ad432745937f;
+
+/// MurmurHash3 with a 32-bit seed, the 128-bit variant is printed as
+/// `h2 || h1` like the integer returned by most bindings
+///
+/// Reference: https://github.com/aappleby/smhasher/blob/master/src/MurmurHash3.cpp
+pub struct Murmur3 {
+    variant: Murmur3Variant,
+    seed: u32,
+}
+
+pub enum Murmur3Variant {
+    X86_32,
+    X64_128,
+}
+
+impl Murmur3 {
+    pub fn new(variant: Murmur3Variant, seed: u32) -> Self {
+        Self { variant, seed }
+    }
+
+    fn state(&self) -> Box<dyn Incremental> {
+        match self.variant {
+            Murmur3Variant::X86_32 => Box::new(X86State {
+                h1: self.seed,
+                buffer: BlockBuffer::new(),
+            }),
+            Murmur3Variant::X64_128 => Box::new(X64State {
+                h1: self.seed as u64,
+                h2: self.seed as u64,
+                buffer: BlockBuffer::new(),
+            }),
+        }
+    }
+}
+
+impl Algorithm for Murmur3 {
+    fn digest(&self, data: ChunkedStream) -> DigestResult {
+        digest_incremental(self.state().as_mut(), data, CHUNK_SIZE)
+    }
+
+    fn incremental(&self) -> Option<Box<dyn Incremental>> {
+        Some(self.state())
+    }
+}
+
+/// Reads little-endian words from up to `N` bytes, padding with zeroes
+fn read_le<const N: usize>(bytes: &[u8]) -> [u8; N] {
+    let mut word = [0; N];
+    word[..bytes.len()].copy_from_slice(bytes);
+    word
+}
+
+fn mix_k1_32(k1: u32) -> u32 {
+    k1.wrapping_mul(C1_32).rotate_left(15).wrapping_mul(C2_32)
+}
+
+fn mix_k1_64(k1: u64) -> u64 {
+    k1.wrapping_mul(C1_64).rotate_left(31).wrapping_mul(C2_64)
+}
+
+fn mix_k2_64(k2: u64) -> u64 {
+    k2.wrapping_mul(C2_64).rotate_left(33).wrapping_mul(C1_64)
+}
+
+struct X86State {
+    h1: u32,
+    buffer: BlockBuffer<4>,
+}
+
+impl Incremental for X86State {
+    fn update(&mut self, data: &[u8]) {
+        let h1 = &mut self.h1;
+        self.buffer.update(data, |block| {
+            *h1 ^= mix_k1_32(u32::from_le_bytes(read_le(block)));
+            *h1 = h1.rotate_left(13).wrapping_mul(5).wrapping_add(0xe6546b64);
+        });
+    }
+
+    fn finalize(&self) -> Vec<u8> {
+        let mut h1 = self.h1;
+
+        let tail = self.buffer.remainder();
+        if !tail.is_empty() {
+            h1 ^= mix_k1_32(u32::from_le_bytes(read_le(tail)));
+        }
+
+        h1 ^= self.buffer.length as u32;
+        fmix32(h1).to_be_bytes().to_vec()
+    }
+}
+
+struct X64State {
+    h1: u64,
+    h2: u64,
+    buffer: BlockBuffer<16>,
+}
+
+impl Incremental for X64State {
+    fn update(&mut self, data: &[u8]) {
+        let (h1, h2) = (&mut self.h1, &mut self.h2);
+        self.buffer.update(data, |block| {
+            let k1 = u64::from_le_bytes(read_le(&block[..8]));
+            let k2 = u64::from_le_bytes(read_le(&block[8..]));
+
+            *h1 ^= mix_k1_64(k1);
+            *h1 = h1.rotate_left(27).wrapping_add(*h2);
+            *h1 = h1.wrapping_mul(5).wrapping_add(0x52dce729);
+
+            *h2 ^= mix_k2_64(k2);
+            *h2 = h2.rotate_left(31).wrapping_add(*h1);
+            *h2 = h2.wrapping_mul(5).wrapping_add(0x38495ab5);
+        });
+    }
+
+    fn finalize(&self) -> Vec<u8> {
+        let (mut h1, mut h2) = (self.h1, self.h2);
+
+        let tail = self.buffer.remainder();
+        if tail.len() > 8 {
+            h2 ^= mix_k2_64(u64::from_le_bytes(read_le(&tail[8..])));
+        }
+        if !tail.is_empty() {
+            h1 ^= mix_k1_64(u64::from_le_bytes(read_le(&tail[..tail.len().min(8)])));
+        }
+
+        let length = self.buffer.length;
+        h1 ^= length;
+        h2 ^= length;
+        h1 = h1.wrapping_add(h2);
+        h2 = h2.wrapping_add(h1);
+
+        h1 = fmix64(h1);
+        h2 = fmix64(h2);
+        h1 = h1.wrapping_add(h2);
+        h2 = h2.wrapping_add(h1);
+
+        ((h2 as u128) << 64 | h1 as u128).to_be_bytes().to_vec()
+    }
+}
+
+fn fmix32(mut h: u32) -> u32 {
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85ebca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2ae35);
+    h ^ (h >> 16)
+}
+
+fn fmix64(mut k: u64) -> u64 {
+    k ^= k >> 33;
+    k = k.wrapping_mul(0xff51afd7ed558ccd);
+    k ^= k >> 33;
+    k = k.wrapping_mul(0xc4ceb9fe1a85ec53);
+    k ^ (k >> 33)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::algorithms::helpers::test::assert_digest;
+
+    use super::*;
+
+    #[test]
+    fn murmur3_x86_32_correct() {
+        for (input, seed, expected) in [
+            ("", 0, "00000000"),
+            ("", 42, "087fcd5c"),
+            ("a", 0, "3c2569b2"),
+            ("abc", 42, "4e4f1e68"),
+            ("The quick brown fox jumps over the lazy dog", 0, "2e4ff723"),
+        ] {
+            assert_digest(&Murmur3::new(Murmur3Variant::X86_32, seed), input, expected);
+        }
+    }
+
+    #[test]
+    fn murmur3_x64_128_correct() {
+        for (input, seed, expected) in [
+            ("", 0, "00000000000000000000000000000000"),
+            ("", 42, "d1016610da11cbb9f02aa77dfa1b8523"),
+            ("a", 0, "e6b53a48510e895a85555565f6597889"),
+            ("abc", 42, "7510712b42353d300d85089fb3cff7d6"),
+            (
+                "The quick brown fox jumps over the lazy dog",
+                0,
+                "7a433ca9c49a9347e34bbc7bbc071b6c",
+            ),
+        ] {
+            assert_digest(
+                &Murmur3::new(Murmur3Variant::X64_128, seed),
+                input,
+                expected,
+            );
+        }
+    }
+
+    #[test]
+    fn digest_across_chunks() {
+        let data: Vec<u8> = (0..5000).map(|i| (i * 31 % 251) as u8).collect();
+
+        for (algorithm, expected) in [
+            (Murmur3::new(Murmur3Variant::X86_32, 0), "760dda26"),
+            (
+                Murmur3::new(Murmur3Variant::X64_128, 42),
+                "8292927e0f31b6482b674a39b48c3711",
+            ),
+        ] {
+            let digest = algorithm
+                .digest(ChunkedStream::Bytes(data.clone()))
+                .unwrap();
+            assert_eq!(hex::encode(digest), expected);
+        }
+    }
+}
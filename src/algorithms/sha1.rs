@@ -1,6 +1,11 @@
+use std::io;
+
 use super::{
-    helpers::{exact_32_bit_words, md_length_padding, Endianness},
-    Algorithm,
+    helpers::{
+        exact_32_bit_words, md_length_padding, process_blocks, restore_words, save_words,
+        Endianness,
+    },
+    Algorithm, Resumable,
 };
 use crate::chunked_stream::ChunkedStream;
 
@@ -16,45 +21,87 @@ pub struct Sha1;
 
 impl Algorithm for Sha1 {
     fn digest(&self, data: ChunkedStream) -> super::DigestResult {
-        let mut buf = INIT_BUFFER;
-
-        // Process each chunk via last_chunk
-        let mut last_chunk: Option<Vec<u8>> = None;
-        let mut len: u64 = 0;
-        for chunk in data.into_iter(CHUNK_SIZE) {
-            if let Some(last_chunk) = last_chunk {
-                process_chunk(&last_chunk, &mut buf);
-            }
-
-            let chunk_bytes = chunk?;
-            len = len.wrapping_add((chunk_bytes.len() * 8) as u64);
-            last_chunk = Some(chunk_bytes);
+        let mut state = Sha1State::new();
+
+        // Process whole chunks as they are read, then the padded last chunk(s)
+        let tail = process_blocks::<CHUNK_SIZE>(data, |chunk| state.process(chunk))?;
+        Ok(state.finalize(tail.bytes()))
+    }
+
+    fn resumable(&self) -> Option<Box<dyn Resumable>> {
+        Some(Box::new(Sha1State::new()))
+    }
+}
+
+/// Buffer between chunks, along with the number of bytes processed
+struct Sha1State {
+    buf: Buffer,
+    length: u64,
+}
+
+impl Sha1State {
+    fn new() -> Self {
+        Self {
+            buf: INIT_BUFFER,
+            length: 0,
+        }
+    }
+
+    fn process(&mut self, chunk: &[u8; CHUNK_SIZE]) {
+        process_chunk(chunk, &mut self.buf);
+        self.length += CHUNK_SIZE as u64;
+    }
+}
+
+impl Resumable for Sha1State {
+    fn block_size(&self) -> usize {
+        CHUNK_SIZE
+    }
+
+    fn update_blocks(&mut self, blocks: &[u8]) {
+        for chunk in blocks.chunks_exact(CHUNK_SIZE) {
+            self.process(chunk.try_into().unwrap());
         }
+    }
 
-        // Process remaining chunks
-        let last_chunk = last_chunk.unwrap_or_default();
-        for chunk in md_length_padding(&last_chunk, len, Endianness::Big) {
+    fn finalize(&self, tail: &[u8]) -> Vec<u8> {
+        let mut buf = self.buf;
+        let len = (self.length + tail.len() as u64).wrapping_mul(8);
+        for chunk in md_length_padding(tail, len, Endianness::Big) {
             process_chunk(&chunk, &mut buf);
         }
 
         let (h0, h1, h2, h3, h4) = buf;
-        Ok([
+        [
             h0.to_be_bytes(),
             h1.to_be_bytes(),
             h2.to_be_bytes(),
             h3.to_be_bytes(),
             h4.to_be_bytes(),
         ]
-        .concat())
+        .concat()
+    }
+
+    fn save(&self) -> Vec<u8> {
+        let (h0, h1, h2, h3, h4) = self.buf;
+        save_words(&[h0, h1, h2, h3, h4], self.length)
+    }
+
+    fn restore(&mut self, saved: &[u8]) -> io::Result<()> {
+        let ([h0, h1, h2, h3, h4], length) = restore_words(saved)?;
+        self.buf = (h0, h1, h2, h3, h4);
+        self.length = length;
+        Ok(())
     }
 }
 
 /// Processes each chunk according to the SHA1 spec.
 /// Ignores chunk if it is None.
-fn process_chunk(chunk: &[u8], (h0, h1, h2, h3, h4): &mut Buffer) {
-    let mut words = exact_32_bit_words(chunk, Endianness::Big);
+fn process_chunk(chunk: &[u8; CHUNK_SIZE], (h0, h1, h2, h3, h4): &mut Buffer) {
+    let mut words = [0; 80];
+    words[..16].copy_from_slice(&exact_32_bit_words::<16>(chunk, Endianness::Big));
     for i in 16..80 {
-        words.push((words[i - 3] ^ words[i - 8] ^ words[i - 14] ^ words[i - 16]).rotate_left(1));
+        words[i] = (words[i - 3] ^ words[i - 8] ^ words[i - 14] ^ words[i - 16]).rotate_left(1);
     }
 
     let (a_n, b_n, c_n, d_n, e_n) =
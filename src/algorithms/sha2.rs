@@ -1,8 +1,11 @@
+use std::io;
+
 use super::{
     helpers::{
-        exact_32_bit_words, exact_64_bit_words, md_length_padding, md_length_padding_64, Endianness,
+        exact_32_bit_words, exact_64_bit_words, md_length_padding, md_length_padding_64,
+        process_blocks, restore_words, save_words, Endianness,
     },
-    Algorithm, DigestResult,
+    Algorithm, DigestResult, Resumable,
 };
 use crate::chunked_stream::ChunkedStream;
 
@@ -175,91 +178,186 @@ impl Sha2 {
     pub fn new(variant: Sha2Variant) -> Self {
         Self { variant }
     }
+
+    /// Initial state of the variant, which knows the length of its digest
+    fn state(&self) -> Sha2State {
+        match self.variant {
+            Sha2Variant::_224 => Sha2State::Words32(State32::new(INIT_BUFFER_224, 28)),
+            Sha2Variant::_256 => Sha2State::Words32(State32::new(INIT_BUFFER_256, 32)),
+            Sha2Variant::_384 => Sha2State::Words64(State64::new(INIT_BUFFER_384, 48)),
+            Sha2Variant::_512 => Sha2State::Words64(State64::new(INIT_BUFFER_512, 64)),
+            Sha2Variant::_512_224 => Sha2State::Words64(State64::new(INIT_BUFFER_512_224, 28)),
+            Sha2Variant::_512_256 => Sha2State::Words64(State64::new(INIT_BUFFER_512_256, 32)),
+        }
+    }
 }
 
 impl Algorithm for Sha2 {
     fn digest(&self, data: ChunkedStream) -> DigestResult {
-        match self.variant {
-            Sha2Variant::_224 => Ok(digest_32(data, INIT_BUFFER_224)?[..28].to_vec()),
-            Sha2Variant::_256 => digest_32(data, INIT_BUFFER_256),
-            Sha2Variant::_384 => Ok(digest_64(data, INIT_BUFFER_384)?[..48].to_vec()),
-            Sha2Variant::_512 => digest_64(data, INIT_BUFFER_512),
-            Sha2Variant::_512_224 => Ok(digest_64(data, INIT_BUFFER_512_224)?[..28].to_vec()),
-            Sha2Variant::_512_256 => Ok(digest_64(data, INIT_BUFFER_512_256)?[..32].to_vec()),
+        match self.state() {
+            Sha2State::Words32(state) => digest_32(data, state),
+            Sha2State::Words64(state) => digest_64(data, state),
+        }
+    }
+
+    fn resumable(&self) -> Option<Box<dyn Resumable>> {
+        match self.state() {
+            Sha2State::Words32(state) => Some(Box::new(state)),
+            Sha2State::Words64(state) => Some(Box::new(state)),
         }
     }
 }
 
-/// Generalized SHA2 function for variants which use u32s (state size of 256).
-fn digest_32(data: ChunkedStream, init_buffer: [u32; 8]) -> DigestResult {
-    let mut buf = init_buffer;
-
-    // Process each chunk via last_chunk
-    let mut last_chunk: Option<Vec<u8>> = None;
-    let mut len: u64 = 0;
-    for chunk in data.into_iter(CHUNK_SIZE_256) {
-        if let Some(last_chunk) = last_chunk {
-            process_chunk_32(&last_chunk, &mut buf);
+enum Sha2State {
+    Words32(State32),
+    Words64(State64),
+}
+
+/// Buffer of the variants which use u32s (state size of 256) between chunks,
+/// along with the number of bytes processed
+struct State32 {
+    buf: [u32; 8],
+    length: u64,
+    /// Length of the digest in bytes, the buffer is truncated to it
+    digest_length: usize,
+}
+
+/// Buffer of the variants which use u64s (state size of 512) between chunks,
+/// along with the number of bytes processed
+struct State64 {
+    buf: [u64; 8],
+    length: u64,
+    /// Length of the digest in bytes, the buffer is truncated to it
+    digest_length: usize,
+}
+
+impl State32 {
+    fn new(init_buffer: [u32; 8], digest_length: usize) -> Self {
+        Self {
+            buf: init_buffer,
+            length: 0,
+            digest_length,
+        }
+    }
+
+    fn process(&mut self, chunk: &[u8; CHUNK_SIZE_256]) {
+        process_chunk_32(chunk, &mut self.buf);
+        self.length += CHUNK_SIZE_256 as u64;
+    }
+}
+
+impl State64 {
+    fn new(init_buffer: [u64; 8], digest_length: usize) -> Self {
+        Self {
+            buf: init_buffer,
+            length: 0,
+            digest_length,
         }
+    }
+
+    fn process(&mut self, chunk: &[u8; CHUNK_SIZE_512]) {
+        process_chunk_64(chunk, &mut self.buf);
+        self.length += CHUNK_SIZE_512 as u64;
+    }
+}
+
+impl Resumable for State32 {
+    fn block_size(&self) -> usize {
+        CHUNK_SIZE_256
+    }
 
-        let chunk_bytes = chunk?;
-        len = len.wrapping_add((chunk_bytes.len() * 8) as u64);
-        last_chunk = Some(chunk_bytes);
+    fn update_blocks(&mut self, blocks: &[u8]) {
+        for chunk in blocks.chunks_exact(CHUNK_SIZE_256) {
+            self.process(chunk.try_into().unwrap());
+        }
     }
 
-    // Default last_chunk to empty Vec if None
-    let last_chunk = last_chunk.unwrap_or_default();
+    fn finalize(&self, tail: &[u8]) -> Vec<u8> {
+        let mut buf = self.buf;
+        let len = (self.length + tail.len() as u64).wrapping_mul(8);
+        for chunk in md_length_padding(tail, len, Endianness::Big) {
+            process_chunk_32(&chunk, &mut buf);
+        }
+
+        let mut out = [0; 8 * 4];
+        for (bytes, word) in out.chunks_exact_mut(4).zip(buf) {
+            bytes.copy_from_slice(&word.to_be_bytes());
+        }
+        out[..self.digest_length].to_vec()
+    }
 
-    // Process remaining padded chunk(s)
-    for chunk in md_length_padding(&last_chunk, len, Endianness::Big) {
-        process_chunk_32(&chunk, &mut buf);
+    fn save(&self) -> Vec<u8> {
+        save_words(&self.buf, self.length)
     }
 
-    let out: Vec<[u8; 4]> = buf.iter().map(|word| word.to_be_bytes()).collect();
-    Ok(out.concat())
+    fn restore(&mut self, saved: &[u8]) -> io::Result<()> {
+        (self.buf, self.length) = restore_words(saved)?;
+        Ok(())
+    }
 }
 
-/// Generalized SHA2 function for variants which use u64s (state size of 512).
-fn digest_64(data: ChunkedStream, init_buffer: [u64; 8]) -> DigestResult {
-    let mut buf = init_buffer;
-
-    // Process each chunk via last_chunk
-    let mut last_chunk: Option<Vec<u8>> = None;
-    let mut len: u128 = 0;
-    for chunk in data.into_iter(CHUNK_SIZE_512) {
-        if let Some(last_chunk) = last_chunk {
-            process_chunk_64(&last_chunk, &mut buf);
+impl Resumable for State64 {
+    fn block_size(&self) -> usize {
+        CHUNK_SIZE_512
+    }
+
+    fn update_blocks(&mut self, blocks: &[u8]) {
+        for chunk in blocks.chunks_exact(CHUNK_SIZE_512) {
+            self.process(chunk.try_into().unwrap());
+        }
+    }
+
+    fn finalize(&self, tail: &[u8]) -> Vec<u8> {
+        let mut buf = self.buf;
+        let len = (self.length + tail.len() as u64) as u128 * 8;
+        for chunk in md_length_padding_64(tail, len, Endianness::Big) {
+            process_chunk_64(&chunk, &mut buf);
+        }
+
+        let mut out = [0; 8 * 8];
+        for (bytes, word) in out.chunks_exact_mut(8).zip(buf) {
+            bytes.copy_from_slice(&word.to_be_bytes());
         }
+        out[..self.digest_length].to_vec()
+    }
 
-        let chunk_bytes = chunk?;
-        len = len.wrapping_add((chunk_bytes.len() * 8) as u128);
-        last_chunk = Some(chunk_bytes);
+    fn save(&self) -> Vec<u8> {
+        save_words(&self.buf, self.length)
     }
 
-    // Process remaining padded chunk(s)
-    let last_chunk = last_chunk.unwrap_or_default();
-    for chunk in md_length_padding_64(&last_chunk, len, Endianness::Big) {
-        process_chunk_64(&chunk, &mut buf);
+    fn restore(&mut self, saved: &[u8]) -> io::Result<()> {
+        (self.buf, self.length) = restore_words(saved)?;
+        Ok(())
     }
+}
+
+/// Generalized SHA2 function for variants which use u32s (state size of 256).
+fn digest_32(data: ChunkedStream, mut state: State32) -> DigestResult {
+    // Process whole chunks as they are read, then the padded last chunk(s)
+    let tail = process_blocks::<CHUNK_SIZE_256>(data, |chunk| state.process(chunk))?;
+    Ok(state.finalize(tail.bytes()))
+}
 
-    let out: Vec<[u8; 8]> = buf.iter().map(|word| word.to_be_bytes()).collect();
-    Ok(out.concat())
+/// Generalized SHA2 function for variants which use u64s (state size of 512).
+fn digest_64(data: ChunkedStream, mut state: State64) -> DigestResult {
+    // Process whole chunks as they are read, then the padded last chunk(s)
+    let tail = process_blocks::<CHUNK_SIZE_512>(data, |chunk| state.process(chunk))?;
+    Ok(state.finalize(tail.bytes()))
 }
 
-fn process_chunk_32(chunk: &[u8], buffer: &mut [u32; 8]) {
+fn process_chunk_32(chunk: &[u8; CHUNK_SIZE_256], buffer: &mut [u32; 8]) {
     // Extend 16 words into 64
-    let mut words = exact_32_bit_words(chunk, Endianness::Big);
+    let mut words = [0; 64];
+    words[..16].copy_from_slice(&exact_32_bit_words::<16>(chunk, Endianness::Big));
     for i in 16..64 {
         let s0 =
             words[i - 15].rotate_right(7) ^ words[i - 15].rotate_right(18) ^ (words[i - 15] >> 3);
         let s1 =
             words[i - 2].rotate_right(17) ^ words[i - 2].rotate_right(19) ^ (words[i - 2] >> 10);
-        words.push(
-            words[i - 16]
-                .wrapping_add(s0)
-                .wrapping_add(words[i - 7])
-                .wrapping_add(s1),
-        );
+        words[i] = words[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(words[i - 7])
+            .wrapping_add(s1);
     }
 
     // Main loop
@@ -294,20 +392,19 @@ fn process_chunk_32(chunk: &[u8], buffer: &mut [u32; 8]) {
     }
 }
 
-fn process_chunk_64(chunk: &[u8], buffer: &mut [u64; 8]) {
+fn process_chunk_64(chunk: &[u8; CHUNK_SIZE_512], buffer: &mut [u64; 8]) {
     // Extend 16 words into 80
-    let mut words = exact_64_bit_words(chunk, Endianness::Big);
+    let mut words = [0; 80];
+    words[..16].copy_from_slice(&exact_64_bit_words::<16>(chunk, Endianness::Big));
     for i in 16..80 {
         let s0 =
             words[i - 15].rotate_right(1) ^ words[i - 15].rotate_right(8) ^ (words[i - 15] >> 7);
         let s1 =
             words[i - 2].rotate_right(19) ^ words[i - 2].rotate_right(61) ^ (words[i - 2] >> 6);
-        words.push(
-            words[i - 16]
-                .wrapping_add(s0)
-                .wrapping_add(words[i - 7])
-                .wrapping_add(s1),
-        );
+        words[i] = words[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(words[i - 7])
+            .wrapping_add(s1);
     }
 
     // Main loop
@@ -1,5 +1,5 @@
-use super::keccak::keccak;
-use super::{Algorithm, DigestResult};
+use super::keccak::Sponge;
+use super::{Algorithm, DigestResult, Resumable};
 use crate::chunked_stream::ChunkedStream;
 
 pub enum Sha3Variant {
@@ -49,7 +49,7 @@ impl Sha3 {
 
 impl Shake {
     pub fn new(variant: ShakeVariant, output_length: usize) -> Result<Self, InvalidOutputLength> {
-        if output_length % 8 == 0 {
+        if output_length.is_multiple_of(8) {
             Ok(Self {
                 variant,
                 output_length,
@@ -60,8 +60,8 @@ impl Shake {
     }
 }
 
-impl Algorithm for Sha3 {
-    fn digest(&self, data: ChunkedStream) -> DigestResult {
+impl Sha3 {
+    fn sponge(&self) -> Sponge {
         let output_length = match self.variant {
             Sha3Variant::_224 => 224,
             Sha3Variant::_256 => 256,
@@ -70,18 +70,38 @@ impl Algorithm for Sha3 {
         };
         let bitrate = 1600 - 2 * output_length;
 
-        keccak(bitrate, data, 0x06, output_length)
+        Sponge::new(bitrate, 0x06, output_length)
     }
 }
 
-impl Algorithm for Shake {
-    fn digest(&self, data: ChunkedStream) -> DigestResult {
+impl Shake {
+    fn sponge(&self) -> Sponge {
         let bitrate = match self.variant {
             ShakeVariant::_128 => 1344,
             ShakeVariant::_256 => 1088,
         };
 
-        keccak(bitrate, data, 0x1F, self.output_length)
+        Sponge::new(bitrate, 0x1F, self.output_length)
+    }
+}
+
+impl Algorithm for Sha3 {
+    fn digest(&self, data: ChunkedStream) -> DigestResult {
+        self.sponge().digest(data)
+    }
+
+    fn resumable(&self) -> Option<Box<dyn Resumable>> {
+        Some(Box::new(self.sponge()))
+    }
+}
+
+impl Algorithm for Shake {
+    fn digest(&self, data: ChunkedStream) -> DigestResult {
+        self.sponge().digest(data)
+    }
+
+    fn resumable(&self) -> Option<Box<dyn Resumable>> {
+        Some(Box::new(self.sponge()))
     }
 }
 
@@ -215,6 +235,13 @@ mod test {
         }
     }
 
+    #[test]
+    fn shake_output_longer_than_rate() {
+        let shake128_1400 = Shake::new(ShakeVariant::_128, 1400).unwrap();
+
+        assert_digest(&shake128_1400, "abc", "5881092dd818bf5cf8a3ddb793fbcba74097d5c526a6d35f97b83351940f2cc844c50af32acd3f2cdd066568706f509bc1bdde58295dae3f891a9a0fca5783789a41f8611214ce612394df286a62d1a2252aa94db9c538956c717dc2bed4f232a0294c857c730aa16067ac1062f1201fb0d377cfb9cde4c63599b27f3462bba4a0ed296c801f9ff7f57302bb3076ee145f97a32ae68e76ab66c48d51675bd49acc29082f5647584e6aa01b3f5af057");
+    }
+
     #[test]
     fn shake_invalid_output_length() {
         let result = Shake::new(ShakeVariant::_128, 65);
@@ -0,0 +1,138 @@
+use super::{
+    helpers::{digest_incremental, BlockBuffer},
+    Algorithm, DigestResult, Incremental,
+};
+use crate::chunked_stream::ChunkedStream;
+
+const CHUNK_SIZE: usize = 1024;
+
+/// SipHash-2-4 keyed with 128 bits, printed as the 64-bit output word
+///
+/// Reference: https://www.aumasson.jp/siphash/siphash.pdf
+pub struct SipHash {
+    key: [u8; 16],
+}
+
+impl SipHash {
+    pub fn new(key: [u8; 16]) -> Self {
+        Self { key }
+    }
+}
+
+struct SipState {
+    v: [u64; 4],
+    buffer: BlockBuffer<8>,
+}
+
+impl SipState {
+    fn new(key: &[u8; 16]) -> Self {
+        let k0 = u64::from_le_bytes(key[..8].try_into().unwrap());
+        let k1 = u64::from_le_bytes(key[8..].try_into().unwrap());
+
+        Self {
+            v: [
+                k0 ^ 0x736f6d6570736575,
+                k1 ^ 0x646f72616e646f6d,
+                k0 ^ 0x6c7967656e657261,
+                k1 ^ 0x7465646279746573,
+            ],
+            buffer: BlockBuffer::new(),
+        }
+    }
+}
+
+fn round(v: &mut [u64; 4]) {
+    v[0] = v[0].wrapping_add(v[1]);
+    v[1] = v[1].rotate_left(13) ^ v[0];
+    v[0] = v[0].rotate_left(32);
+    v[2] = v[2].wrapping_add(v[3]);
+    v[3] = v[3].rotate_left(16) ^ v[2];
+    v[0] = v[0].wrapping_add(v[3]);
+    v[3] = v[3].rotate_left(21) ^ v[0];
+    v[2] = v[2].wrapping_add(v[1]);
+    v[1] = v[1].rotate_left(17) ^ v[2];
+    v[2] = v[2].rotate_left(32);
+}
+
+/// Compresses a message word with 2 rounds
+fn compress(v: &mut [u64; 4], m: u64) {
+    v[3] ^= m;
+    round(v);
+    round(v);
+    v[0] ^= m;
+}
+
+impl Incremental for SipState {
+    fn update(&mut self, data: &[u8]) {
+        let v = &mut self.v;
+        self.buffer.update(data, |word| {
+            compress(v, u64::from_le_bytes(word.try_into().unwrap()))
+        });
+    }
+
+    fn finalize(&self) -> Vec<u8> {
+        let mut v = self.v;
+
+        // Last word holds the remaining bytes and the length modulo 256 in the top byte
+        let tail = self.buffer.remainder();
+        let mut last_word = [0; 8];
+        last_word[..tail.len()].copy_from_slice(tail);
+        last_word[7] = self.buffer.length as u8;
+        compress(&mut v, u64::from_le_bytes(last_word));
+
+        // Finalization with 4 rounds
+        v[2] ^= 0xff;
+        for _ in 0..4 {
+            round(&mut v);
+        }
+
+        let result = v.iter().fold(0, |result, v| result ^ v);
+        result.to_be_bytes().to_vec()
+    }
+}
+
+impl Algorithm for SipHash {
+    fn digest(&self, data: ChunkedStream) -> DigestResult {
+        digest_incremental(&mut SipState::new(&self.key), data, CHUNK_SIZE)
+    }
+
+    fn incremental(&self) -> Option<Box<dyn Incremental>> {
+        Some(Box::new(SipState::new(&self.key)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::algorithms::helpers::test::assert_digest;
+
+    use super::*;
+
+    /// Key 00 01 02 ... 0f used by the reference test vectors
+    fn key() -> [u8; 16] {
+        std::array::from_fn(|i| i as u8)
+    }
+
+    #[test]
+    fn siphash_correct() {
+        for (input, expected) in [
+            ("", "726fdb47dd0e0e31"),
+            ("a", "2ba3e8e9a71148ca"),
+            ("abc", "5dbcfa53aa2007a5"),
+            (
+                "The quick brown fox jumps over the lazy dog",
+                "52276105dc1f6fe4",
+            ),
+        ] {
+            assert_digest(&SipHash::new(key()), input, expected);
+        }
+    }
+
+    #[test]
+    fn paper_test_vector() {
+        // Appendix A of the paper, the 15 bytes 00 01 02 ... 0e
+        let digest = SipHash::new(key())
+            .digest(ChunkedStream::Bytes((0..15).collect()))
+            .unwrap();
+        assert_eq!(hex::encode(digest), "a129ca6149be45e5");
+    }
+}
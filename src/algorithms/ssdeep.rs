@@ -0,0 +1,461 @@
+use super::{helpers::digest_incremental, Algorithm, DigestResult, Incremental};
+use crate::chunked_stream::ChunkedStream;
+
+const CHUNK_SIZE: usize = 65536;
+
+const SPAMSUM_LENGTH: usize = 64;
+const MIN_BLOCKSIZE: u32 = 3;
+const ROLLING_WINDOW: usize = 7;
+const HASH_PRIME: u32 = 0x01000193;
+/// Initial value of the piece hashes, only the low 6 bits of which are ever used
+const HASH_INIT: u8 = 0x27;
+const NUM_BLOCKHASHES: usize = 31;
+/// Inputs larger than this cannot be given a block size
+const TOTAL_SIZE_MAX: u64 =
+    ((MIN_BLOCKSIZE as u64) << (NUM_BLOCKHASHES - 1)) * SPAMSUM_LENGTH as u64;
+
+const B64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Context triggered piecewise hash, compatible with ssdeep 2.14.
+///
+/// The digest is the ASCII signature `blocksize:hash1:hash2`, where a piece ends
+/// whenever the rolling hash of the last 7 bytes hits the block size, and every
+/// piece adds a base64 character of its FNV hash to the signature. Two signatures
+/// are compared with `compare`.
+///
+/// Reference: https://github.com/ssdeep-project/ssdeep/blob/master/fuzzy.c
+pub struct Ssdeep;
+
+fn block_size(index: usize) -> u64 {
+    (MIN_BLOCKSIZE as u64) << index
+}
+
+/// FNV step, reduced to the 6 bits which end up in the signature
+fn sum_hash(c: u8, h: u8) -> u8 {
+    ((h as u32).wrapping_mul(HASH_PRIME) ^ c as u32) as u8 & 0x3f
+}
+
+/// Adler-style rolling hash over the last `ROLLING_WINDOW` bytes
+#[derive(Clone, Default)]
+struct RollState {
+    window: [u8; ROLLING_WINDOW],
+    h1: u32,
+    h2: u32,
+    h3: u32,
+    n: usize,
+}
+
+impl RollState {
+    fn update(&mut self, c: u8) {
+        self.h2 = self.h2.wrapping_sub(self.h1);
+        self.h2 = self.h2.wrapping_add(ROLLING_WINDOW as u32 * c as u32);
+        self.h1 = self.h1.wrapping_add(c as u32);
+        self.h1 = self.h1.wrapping_sub(self.window[self.n] as u32);
+        self.window[self.n] = c;
+        self.n = (self.n + 1) % ROLLING_WINDOW;
+        self.h3 = (self.h3 << 5) ^ c as u32;
+    }
+
+    fn sum(&self) -> u32 {
+        self.h1.wrapping_add(self.h2).wrapping_add(self.h3)
+    }
+}
+
+/// Signature being built for one block size
+#[derive(Clone)]
+struct BlockHash {
+    /// Characters of the signature, the one at `index` is only set once the
+    /// signature is full and its last piece is the rest of the input
+    digest: [u8; SPAMSUM_LENGTH],
+    index: usize,
+    /// Last character of the signature truncated to half its length
+    half_digest: Option<u8>,
+    h: u8,
+    half_h: u8,
+}
+
+impl BlockHash {
+    fn new(h: u8, half_h: u8) -> Self {
+        Self {
+            digest: [0; SPAMSUM_LENGTH],
+            index: 0,
+            half_digest: None,
+            h,
+            half_h,
+        }
+    }
+}
+
+/// Computes the signatures of all block sizes at once, dropping the small
+/// ones once they are too long to be chosen.
+#[derive(Clone)]
+struct SsdeepState {
+    total_size: u64,
+    start: usize,
+    blocks: Vec<BlockHash>,
+    /// Mask of the reset points skipped for the dropped block sizes
+    roll_mask: u32,
+    roll: RollState,
+    /// Piece hash of the largest block size, kept once no more block sizes can be added
+    last_h: Option<u8>,
+}
+
+impl SsdeepState {
+    fn new() -> Self {
+        Self {
+            total_size: 0,
+            start: 0,
+            blocks: vec![BlockHash::new(HASH_INIT, HASH_INIT)],
+            roll_mask: 0,
+            roll: RollState::default(),
+            last_h: None,
+        }
+    }
+
+    /// Starts the signature of the next block size from the current piece
+    fn try_fork(&mut self) {
+        let last = self.blocks.last().unwrap();
+        if self.blocks.len() < NUM_BLOCKHASHES {
+            self.blocks.push(BlockHash::new(last.h, last.half_h));
+        } else if self.last_h.is_none() {
+            self.last_h = Some(last.h);
+        }
+    }
+
+    /// Drops the smallest block size if the next one is long enough to be chosen instead
+    fn try_reduce(&mut self) {
+        if self.blocks.len() - self.start < 2
+            || block_size(self.start) * SPAMSUM_LENGTH as u64 >= self.total_size
+            || self.blocks[self.start + 1].index < SPAMSUM_LENGTH / 2
+        {
+            return;
+        }
+
+        self.start += 1;
+        self.roll_mask = self.roll_mask * 2 + 1;
+    }
+
+    fn step(&mut self, c: u8) {
+        self.roll.update(c);
+        let sum = self.roll.sum().wrapping_add(1);
+
+        for block in &mut self.blocks[self.start..] {
+            block.h = sum_hash(c, block.h);
+            block.half_h = sum_hash(c, block.half_h);
+        }
+        if let Some(last_h) = &mut self.last_h {
+            *last_h = sum_hash(c, *last_h);
+        }
+
+        // A piece ends for block size 3 * 2^i when the rolling sum + 1 is a multiple of it
+        let mut h = sum / MIN_BLOCKSIZE;
+        if sum == 0 || h & self.roll_mask != 0 || !sum.is_multiple_of(MIN_BLOCKSIZE) {
+            return;
+        }
+        h >>= self.start;
+
+        let mut i = self.start;
+        while i < self.blocks.len() {
+            if self.blocks[i].index == 0 {
+                self.try_fork();
+            }
+
+            let block = &mut self.blocks[i];
+            block.digest[block.index] = B64[block.h as usize];
+            block.half_digest = Some(B64[block.half_h as usize]);
+            if block.index < SPAMSUM_LENGTH - 1 {
+                // Once the signature is full, the remaining pieces are merged into the last one
+                block.index += 1;
+                block.digest[block.index] = 0;
+                block.h = HASH_INIT;
+                if block.index < SPAMSUM_LENGTH / 2 {
+                    block.half_h = HASH_INIT;
+                    block.half_digest = None;
+                }
+            } else {
+                self.try_reduce();
+            }
+
+            if h & 1 != 0 {
+                break;
+            }
+            h >>= 1;
+            i += 1;
+        }
+    }
+
+    fn signature(&self) -> String {
+        // Smallest block size for which the input fits in a signature, unless it is too short
+        let mut i = self.start;
+        while block_size(i) * (SPAMSUM_LENGTH as u64) < self.total_size {
+            i += 1;
+        }
+        i = i.min(self.blocks.len() - 1);
+        while i > self.start && self.blocks[i].index < SPAMSUM_LENGTH / 2 {
+            i -= 1;
+        }
+
+        // The piece in progress is added unless the input ended right after a reset point
+        let in_progress = self.roll.sum() != 0;
+
+        let block = &self.blocks[i];
+        let mut hash1 = block.digest[..block.index].to_vec();
+        if in_progress {
+            hash1.push(B64[block.h as usize]);
+        } else if block.digest[block.index] != 0 {
+            hash1.push(block.digest[block.index]);
+        }
+
+        let mut hash2 = vec![];
+        if let Some(next) = self.blocks.get(i + 1) {
+            hash2.extend_from_slice(&next.digest[..next.index.min(SPAMSUM_LENGTH / 2 - 1)]);
+            if in_progress {
+                hash2.push(B64[next.half_h as usize]);
+            } else if let Some(half_digest) = next.half_digest {
+                hash2.push(half_digest);
+            }
+        } else if in_progress {
+            let h = match i {
+                0 => block.h,
+                _ => self.last_h.unwrap_or(block.h),
+            };
+            hash2.push(B64[h as usize]);
+        }
+
+        format!(
+            "{}:{}:{}",
+            block_size(i),
+            String::from_utf8(hash1).unwrap(),
+            String::from_utf8(hash2).unwrap()
+        )
+    }
+}
+
+impl Incremental for SsdeepState {
+    fn update(&mut self, data: &[u8]) {
+        self.total_size = self.total_size.saturating_add(data.len() as u64);
+        for &c in data {
+            self.step(c);
+        }
+    }
+
+    fn finalize(&self) -> Vec<u8> {
+        self.signature().into_bytes()
+    }
+}
+
+impl Algorithm for Ssdeep {
+    fn digest(&self, data: ChunkedStream) -> DigestResult {
+        let mut state = SsdeepState::new();
+        let signature = digest_incremental(&mut state, data, CHUNK_SIZE)?;
+
+        if state.total_size > TOTAL_SIZE_MAX {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "input is too large for an ssdeep signature",
+            ));
+        }
+
+        Ok(signature)
+    }
+
+    fn incremental(&self) -> Option<Box<dyn Incremental>> {
+        Some(Box::new(SsdeepState::new()))
+    }
+}
+
+/// Signature split into its block size and both hashes, with runs of more
+/// than 3 identical characters shortened to 3 like ssdeep does before comparing.
+struct Signature {
+    block_size: u64,
+    hash1: Vec<u8>,
+    hash2: Vec<u8>,
+}
+
+fn eliminate_sequences(hash: &str) -> Vec<u8> {
+    let mut result: Vec<u8> = vec![];
+    for &c in hash.as_bytes() {
+        if result.len() < 3 || result[result.len() - 3..].iter().any(|&prev| prev != c) {
+            result.push(c);
+        }
+    }
+    result
+}
+
+impl Signature {
+    /// Parses `blocksize:hash1:hash2`, which may be followed by `,"filename"` as in ssdeep output
+    fn parse(signature: &str) -> Option<Self> {
+        let (block_size, hashes) = signature.trim().split_once(':')?;
+        let (hash1, hash2) = hashes.split_once(':')?;
+        let hash2 = hash2.split(',').next().unwrap();
+
+        let valid =
+            |hash: &str| hash.len() <= SPAMSUM_LENGTH && hash.bytes().all(|c| B64.contains(&c));
+        if !valid(hash1) || !valid(hash2) {
+            return None;
+        }
+
+        Some(Self {
+            block_size: block_size.parse().ok().filter(|size| *size > 0)?,
+            hash1: eliminate_sequences(hash1),
+            hash2: eliminate_sequences(hash2),
+        })
+    }
+}
+
+/// Edit distance where replacing a character costs 2, as an insertion and a deletion
+fn edit_distance(a: &[u8], b: &[u8]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, &ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, &cb) in b.iter().enumerate() {
+            let replace = previous[j] + if ca == cb { 0 } else { 2 };
+            current[j + 1] = replace.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// Scores two hashes of the same block size from 0 to 100
+fn score_hashes(a: &[u8], b: &[u8], block_size: u64) -> u32 {
+    // Hashes need a common substring as long as the rolling window to be related at all
+    let has_common_substring = a.len() >= ROLLING_WINDOW
+        && b.len() >= ROLLING_WINDOW
+        && a.windows(ROLLING_WINDOW)
+            .any(|window| b.windows(ROLLING_WINDOW).any(|other| window == other));
+    if !has_common_substring {
+        return 0;
+    }
+
+    // Edit distance scaled from 0 (identical) to 64, then turned into a score out of 100
+    let distance = edit_distance(a, b) * SPAMSUM_LENGTH / (a.len() + b.len());
+    let score = 100 - (100 * distance / SPAMSUM_LENGTH) as u32;
+
+    // Small block sizes should not exaggerate the match of short hashes
+    let threshold = (99 + ROLLING_WINDOW as u64) / ROLLING_WINDOW as u64 * MIN_BLOCKSIZE as u64;
+    if block_size >= threshold {
+        return score;
+    }
+    let cap = block_size / MIN_BLOCKSIZE as u64 * a.len().min(b.len()) as u64;
+    score.min(cap as u32)
+}
+
+/// Scores the similarity of two ssdeep signatures from 0 (unrelated) to 100 (identical
+/// or nearly), None if either is malformed.
+///
+/// Only signatures with the same or adjacent block sizes can be compared, others score 0.
+pub fn compare(first: &str, second: &str) -> Option<u32> {
+    let (first, second) = (Signature::parse(first)?, Signature::parse(second)?);
+    let (size1, size2) = (first.block_size, second.block_size);
+
+    if size1 == size2 && first.hash1 == second.hash1 && first.hash2 == second.hash2 {
+        return Some(100);
+    }
+
+    Some(if size1 == size2 {
+        score_hashes(&first.hash1, &second.hash1, size1).max(score_hashes(
+            &first.hash2,
+            &second.hash2,
+            size1 * 2,
+        ))
+    } else if size1 * 2 == size2 {
+        score_hashes(&second.hash1, &first.hash2, size2)
+    } else if size2 * 2 == size1 {
+        score_hashes(&first.hash1, &second.hash2, size1)
+    } else {
+        0
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::algorithms::helpers::test::assert_digest;
+
+    use super::*;
+
+    fn signature(data: &[u8]) -> String {
+        String::from_utf8(Ssdeep.digest(ChunkedStream::Bytes(data.to_vec())).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn ssdeep_correct() {
+        // From the python-ssdeep documentation
+        for (input, expected) in [
+            ("", "3::"),
+            (
+                "Also called fuzzy hashes, Ctph can match inputs that have homologies.",
+                "3:AXGBicFlgVNhBGcL6wCrFQEv:AXGHsNhxLsr2C",
+            ),
+            (
+                "Also called fuzzy hashes, CTPH can match inputs that have homologies.",
+                "3:AXGBicFlIHBGcL6wCrFQEv:AXGH6xLsr2C",
+            ),
+        ] {
+            assert_digest(&Ssdeep, input, &hex::encode(expected));
+        }
+    }
+
+    #[test]
+    fn larger_inputs() {
+        // Xorshift bytes, which have reset points as often as real data
+        let mut x: u32 = 2463534242;
+        let data: Vec<u8> = (0..200_000)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                x as u8
+            })
+            .collect();
+        let sig = signature(&data);
+
+        let (block_size, hashes) = sig.split_once(':').unwrap();
+        let (hash1, hash2) = hashes.split_once(':').unwrap();
+        assert_eq!(block_size, "6144");
+        assert!(hash1.len() > SPAMSUM_LENGTH / 2 && hash1.len() <= SPAMSUM_LENGTH);
+        assert!(hash2.len() <= SPAMSUM_LENGTH / 2);
+
+        // Streamed in uneven pieces
+        let mut state = Ssdeep.incremental().unwrap();
+        for piece in data.chunks(999) {
+            state.update(piece);
+        }
+        assert_eq!(String::from_utf8(state.finalize()).unwrap(), sig);
+
+        // A small change keeps the signatures similar
+        let mut changed = data.clone();
+        changed[100_000..100_010].fill(0);
+        let score = compare(&sig, &signature(&changed)).unwrap();
+        assert!(score > 50 && score < 100, "{}", score);
+    }
+
+    #[test]
+    fn compares_signatures() {
+        let first = "3:AXGBicFlgVNhBGcL6wCrFQEv:AXGHsNhxLsr2C";
+        let second = "3:AXGBicFlIHBGcL6wCrFQEv:AXGH6xLsr2C";
+
+        assert_eq!(compare(first, second), Some(22));
+        assert_eq!(compare(first, first), Some(100));
+        assert_eq!(compare("3::", "3::"), Some(100));
+        assert_eq!(
+            compare(first, "12:AXGBicFlgVNhBGcL6wCrFQEv:AXGHsNhxLsr2C"),
+            Some(0)
+        );
+        assert_eq!(compare(&format!("{},\"a.txt\"", first), second), Some(22));
+        assert_eq!(compare(first, "3:AXG"), None);
+        assert_eq!(compare(first, "x:AXG:AXG"), None);
+    }
+
+    #[test]
+    fn eliminates_sequences() {
+        assert_eq!(eliminate_sequences("AAAAAbBBBBc"), b"AAAbBBBc");
+    }
+
+    #[test]
+    fn edit_distance_works() {
+        assert_eq!(edit_distance(b"kitten", b"sitting"), 5);
+        assert_eq!(edit_distance(b"", b"abc"), 3);
+    }
+}
@@ -0,0 +1,397 @@
+use super::{helpers::digest_incremental, Algorithm, DigestResult, Incremental};
+use crate::chunked_stream::ChunkedStream;
+
+const CHUNK_SIZE: usize = 65536;
+
+const WINDOW_SIZE: usize = 5;
+const BUCKETS: usize = 128;
+/// Bytes of bucket quartiles in the digest, 2 bits per bucket
+const CODE_SIZE: usize = BUCKETS / 4;
+/// Shorter inputs do not fill enough buckets to be compared meaningfully
+const MIN_DATA_LENGTH: u64 = 50;
+/// Digest of inputs which cannot be given a hash, as printed by the reference implementation
+const NULL_DIGEST: &str = "TNULL";
+
+/// Pearson hashing permutation
+const V_TABLE: [u8; 256] = [
+    1, 87, 49, 12, 176, 178, 102, 166, 121, 193, 6, 84, 249, 230, 44, 163, 14, 197, 213, 181, 161,
+    85, 218, 80, 64, 239, 24, 226, 236, 142, 38, 200, 110, 177, 104, 103, 141, 253, 255, 50, 77,
+    101, 81, 18, 45, 96, 31, 222, 25, 107, 190, 70, 86, 237, 240, 34, 72, 242, 20, 214, 244, 227,
+    149, 235, 97, 234, 57, 22, 60, 250, 82, 175, 208, 5, 127, 199, 111, 62, 135, 248, 174, 169,
+    211, 58, 66, 154, 106, 195, 245, 171, 17, 187, 182, 179, 0, 243, 132, 56, 148, 75, 128, 133,
+    158, 100, 130, 126, 91, 13, 153, 246, 216, 219, 119, 68, 223, 78, 83, 88, 201, 99, 122, 11, 92,
+    32, 136, 114, 52, 10, 138, 30, 48, 183, 156, 35, 61, 26, 143, 74, 251, 94, 129, 162, 63, 152,
+    170, 7, 115, 167, 241, 206, 3, 150, 55, 59, 151, 220, 90, 53, 23, 131, 125, 173, 15, 238, 79,
+    95, 89, 16, 105, 137, 225, 224, 217, 160, 37, 123, 118, 73, 2, 157, 46, 116, 9, 145, 134, 228,
+    207, 212, 202, 215, 69, 229, 27, 188, 67, 124, 168, 252, 42, 4, 29, 108, 21, 247, 19, 205, 39,
+    203, 233, 40, 186, 147, 198, 192, 155, 33, 164, 191, 98, 204, 165, 180, 117, 76, 140, 36, 210,
+    172, 41, 54, 159, 8, 185, 232, 113, 196, 231, 47, 146, 120, 51, 65, 28, 144, 254, 221, 93, 189,
+    194, 139, 112, 43, 71, 109, 184, 209,
+];
+
+/// Trend Micro locality sensitive hash, compatible with TLSH 4.x in its default
+/// configuration (128 buckets, 1 byte checksum).
+///
+/// Every 5 byte window adds to 6 buckets, picked by Pearson hashes of byte
+/// triplets. The digest is the ASCII `T1` followed by 70 hex digits: a checksum,
+/// the logarithm of the input length, the ratios of the bucket quartiles and
+/// 2 bits per bucket for the quartile it falls into. Two digests are compared
+/// with `distance`.
+///
+/// Inputs shorter than 50 bytes, or which fill less than half of the buckets,
+/// cannot be hashed.
+///
+/// Reference: https://github.com/trendmicro/tlsh/blob/master/src/tlsh_impl.cpp
+pub struct Tlsh;
+
+fn pearson(salt: u8, i: u8, j: u8, k: u8) -> u8 {
+    let h = V_TABLE[salt as usize];
+    let h = V_TABLE[(h ^ i) as usize];
+    let h = V_TABLE[(h ^ j) as usize];
+    V_TABLE[(h ^ k) as usize]
+}
+
+fn swap_nibbles(byte: u8) -> u8 {
+    byte.rotate_left(4)
+}
+
+/// Logarithmic length, with a finer scale for larger inputs
+fn length_capture(length: u64) -> u8 {
+    const LOG_1_5: f64 = 0.4054651;
+    const LOG_1_3: f64 = 0.26236426;
+    const LOG_1_1: f64 = 0.095310180;
+
+    // The reference takes the logarithm of the length as a float
+    let log = (length as f32 as f64).ln();
+    let capture = if length <= 656 {
+        (log / LOG_1_5).floor()
+    } else if length <= 3199 {
+        (log / LOG_1_3 - 8.72777).floor()
+    } else {
+        (log / LOG_1_1 - 62.5472).floor()
+    };
+
+    (capture as i64 & 0xff) as u8
+}
+
+struct TlshState {
+    buckets: [u32; BUCKETS],
+    window: [u8; WINDOW_SIZE],
+    checksum: u8,
+    length: u64,
+}
+
+impl TlshState {
+    fn new() -> Self {
+        Self {
+            buckets: [0; BUCKETS],
+            window: [0; WINDOW_SIZE],
+            checksum: 0,
+            length: 0,
+        }
+    }
+
+    fn add(&mut self, bucket: u8) {
+        // Only the lower half of the 256 possible buckets make it into the digest
+        if let Some(count) = self.buckets.get_mut(bucket as usize) {
+            *count += 1;
+        }
+    }
+
+    fn step(&mut self, c: u8) {
+        let index = (self.length % WINDOW_SIZE as u64) as usize;
+        self.window[index] = c;
+        self.length += 1;
+        if self.length < WINDOW_SIZE as u64 {
+            return;
+        }
+
+        // Bytes from the current one back to the oldest in the window
+        let w = |back: usize| self.window[(index + WINDOW_SIZE - back) % WINDOW_SIZE];
+        let (c0, c1, c2, c3, c4) = (w(0), w(1), w(2), w(3), w(4));
+
+        self.checksum = pearson(0, c0, c1, self.checksum);
+        self.add(pearson(2, c0, c1, c2));
+        self.add(pearson(3, c0, c1, c3));
+        self.add(pearson(5, c0, c2, c3));
+        self.add(pearson(7, c0, c2, c4));
+        self.add(pearson(11, c0, c1, c4));
+        self.add(pearson(13, c0, c3, c4));
+    }
+
+    /// Digest bytes in the order they are printed, None if the input cannot be hashed
+    fn hash(&self) -> Option<[u8; 3 + CODE_SIZE]> {
+        if self.length < MIN_DATA_LENGTH {
+            return None;
+        }
+
+        let nonzero = self.buckets.iter().filter(|&&count| count > 0).count();
+        if nonzero <= BUCKETS / 2 {
+            return None;
+        }
+
+        let mut sorted = self.buckets;
+        sorted.sort_unstable();
+        let (q1, q2, q3) = (
+            sorted[BUCKETS / 4 - 1],
+            sorted[BUCKETS / 2 - 1],
+            sorted[BUCKETS * 3 / 4 - 1],
+        );
+
+        // The reference computes the ratios with floats
+        let ratio = |q: u32| ((q as u64 * 100) as f32 / q3 as f32) as u32 as u8 % 16;
+
+        let mut hash = [0; 3 + CODE_SIZE];
+        hash[0] = swap_nibbles(self.checksum);
+        hash[1] = swap_nibbles(length_capture(self.length));
+        hash[2] = ratio(q1) << 4 | ratio(q2);
+
+        // Buckets are stored in reverse, 4 to a byte with the first in the lowest bits
+        for (i, group) in self.buckets.chunks(4).enumerate() {
+            hash[3 + CODE_SIZE - 1 - i] = group.iter().enumerate().fold(0, |code, (j, &count)| {
+                let quartile = if count > q3 {
+                    3
+                } else if count > q2 {
+                    2
+                } else if count > q1 {
+                    1
+                } else {
+                    0
+                };
+                code | quartile << (j * 2)
+            });
+        }
+
+        Some(hash)
+    }
+}
+
+impl Incremental for TlshState {
+    fn update(&mut self, data: &[u8]) {
+        for &c in data {
+            self.step(c);
+        }
+    }
+
+    /// `TNULL` if the input so far cannot be hashed
+    fn finalize(&self) -> Vec<u8> {
+        match self.hash() {
+            Some(hash) => format!("T1{}", hex::encode_upper(hash)).into_bytes(),
+            None => NULL_DIGEST.as_bytes().to_vec(),
+        }
+    }
+}
+
+impl Algorithm for Tlsh {
+    fn digest(&self, data: ChunkedStream) -> DigestResult {
+        let mut state = TlshState::new();
+        let digest = digest_incremental(&mut state, data, CHUNK_SIZE)?;
+
+        if digest == NULL_DIGEST.as_bytes() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "input is too short or too uniform for a TLSH digest",
+            ));
+        }
+
+        Ok(digest)
+    }
+
+    fn incremental(&self) -> Option<Box<dyn Incremental>> {
+        Some(Box::new(TlshState::new()))
+    }
+}
+
+/// Digest fields, with the nibbles of the header bytes swapped back
+struct Digest {
+    checksum: u8,
+    length: u8,
+    q1_ratio: u8,
+    q2_ratio: u8,
+    code: [u8; CODE_SIZE],
+}
+
+impl Digest {
+    /// Parses a `T1` digest, the version prefix being optional as in older TLSH output
+    fn parse(digest: &str) -> Option<Self> {
+        let digest = digest.trim();
+        let digest = digest.strip_prefix("T1").unwrap_or(digest);
+
+        let mut bytes = [0; 3 + CODE_SIZE];
+        hex::decode_to_slice(digest, &mut bytes).ok()?;
+
+        let mut code = [0; CODE_SIZE];
+        code.copy_from_slice(&bytes[3..]);
+        Some(Self {
+            checksum: swap_nibbles(bytes[0]),
+            length: swap_nibbles(bytes[1]),
+            q1_ratio: bytes[2] >> 4,
+            q2_ratio: bytes[2] & 0x0f,
+            code,
+        })
+    }
+}
+
+/// Distance between two values on a circular range
+fn mod_diff(x: u8, y: u8, range: u32) -> u32 {
+    let (low, high) = (x.min(y) as u32, x.max(y) as u32);
+    (high - low).min(low + range - high)
+}
+
+/// Distance of a header field, 1 is tolerated and larger differences weigh heavily
+fn header_diff(diff: u32, tolerance: u32) -> u32 {
+    if diff <= tolerance {
+        diff
+    } else {
+        (diff - tolerance) * 12
+    }
+}
+
+/// Quartile differences of all buckets, opposite quartiles weighing double
+fn code_distance(a: &[u8; CODE_SIZE], b: &[u8; CODE_SIZE]) -> u32 {
+    a.iter()
+        .zip(b)
+        .flat_map(|(&x, &y)| (0..4).map(move |j| ((x >> (j * 2)) & 3).abs_diff((y >> (j * 2)) & 3)))
+        .map(|diff| if diff == 3 { 6 } else { diff as u32 })
+        .sum()
+}
+
+/// Distance between two TLSH digests, from 0 for identical (or nearly) inputs and
+/// growing as they differ, None if either is malformed.
+///
+/// This is the standard distance of the reference implementation, which includes
+/// the difference in input length.
+pub fn distance(first: &str, second: &str) -> Option<u32> {
+    let (first, second) = (Digest::parse(first)?, Digest::parse(second)?);
+
+    let length_diff = match mod_diff(first.length, second.length, 256) {
+        diff @ (0 | 1) => diff,
+        diff => diff * 12,
+    };
+
+    Some(
+        length_diff
+            + header_diff(mod_diff(first.q1_ratio, second.q1_ratio, 16), 1)
+            + header_diff(mod_diff(first.q2_ratio, second.q2_ratio, 16), 1)
+            + (first.checksum != second.checksum) as u32
+            + code_distance(&first.code, &second.code),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn xorshift(length: usize, seed: u32) -> Vec<u8> {
+        let mut x = seed;
+        (0..length)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                x as u8
+            })
+            .collect()
+    }
+
+    fn digest(data: &[u8]) -> String {
+        String::from_utf8(Tlsh.digest(ChunkedStream::Bytes(data.to_vec())).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn pearson_table_is_a_permutation() {
+        let mut sorted = V_TABLE;
+        sorted.sort_unstable();
+        assert!(sorted.iter().enumerate().all(|(i, &v)| i == v as usize));
+    }
+
+    #[test]
+    fn digest_format() {
+        let data = xorshift(10_000, 2463534242);
+        let hash = digest(&data);
+
+        assert_eq!(hash.len(), 72);
+        assert!(hash.starts_with("T1"));
+        assert!(hash[2..]
+            .bytes()
+            .all(|c| c.is_ascii_digit() || c.is_ascii_uppercase()));
+
+        // Streamed in uneven pieces
+        let mut state = Tlsh.incremental().unwrap();
+        for piece in data.chunks(999) {
+            state.update(piece);
+        }
+        assert_eq!(String::from_utf8(state.finalize()).unwrap(), hash);
+    }
+
+    #[test]
+    fn matches_reference() {
+        // Computed with a port of the reference implementation (tlsh_impl.cpp and
+        // tlsh_util.cpp), which takes the logarithm of the length in double precision
+        // and compares the codes with its bit pair table
+        let data = xorshift(10_000, 2463534242);
+        assert_eq!(
+            digest(&data),
+            "T1EC22BF98EF36A646D2D237CBCA12AE89E5C6351299CDEF97ED3010116410569728F06B"
+        );
+
+        let first = "T1E492E054ED30A342F1E2378FCF25AFC6A4C9395B50C2EF5B4C39652EA814A9023CB01A";
+        let second = "T1C592D014ED20A342F1E237FFCF25AF86A4C9395B54C2EF5B0C39612EA81499423CB01A";
+        let data = xorshift(20_000, 2463534242);
+        let mut changed = data.clone();
+        changed[10_000..10_100].fill(0);
+        assert_eq!(
+            (digest(&data).as_str(), digest(&changed).as_str()),
+            (first, second)
+        );
+        assert_eq!(distance(first, second), Some(17));
+    }
+
+    #[test]
+    fn rejects_short_and_uniform_inputs() {
+        assert!(Tlsh.digest(ChunkedStream::Bytes(xorshift(49, 1))).is_err());
+        assert!(Tlsh.digest(ChunkedStream::Bytes(vec![0; 10_000])).is_err());
+
+        let state = Tlsh.incremental().unwrap();
+        assert_eq!(state.finalize(), b"TNULL");
+    }
+
+    #[test]
+    fn captures_length() {
+        assert_eq!(length_capture(50), 9);
+        assert_eq!(length_capture(656), 15);
+        assert_eq!(length_capture(657), 16);
+        assert_eq!(length_capture(3200), 22);
+        assert_eq!(length_capture(1 << 20), 82);
+    }
+
+    #[test]
+    fn distances() {
+        let data = xorshift(20_000, 2463534242);
+        let mut changed = data.clone();
+        changed[10_000..10_100].fill(0);
+
+        let (first, second) = (digest(&data), digest(&changed));
+        let third = digest(&xorshift(20_000, 42));
+
+        assert_eq!(distance(&first, &first), Some(0));
+        assert_eq!(distance(&first, &second), distance(&second, &first));
+        let close = distance(&first, &second).unwrap();
+        let far = distance(&first, &third).unwrap();
+        assert!(close > 0 && close < far, "{} {}", close, far);
+
+        assert_eq!(distance(&first, "T1ABC"), None);
+        assert_eq!(distance(&first, "TNULL"), None);
+    }
+
+    #[test]
+    fn distance_of_fields() {
+        let zero = format!("T1{}", "0".repeat(70));
+        // Checksum 1, length 3, quartile ratios 2 and 1, the first bucket in the top quartile
+        let other = format!("T1103021{}03", "0".repeat(62));
+
+        assert_eq!(distance(&zero, &other), Some(1 + 3 * 12 + 12 + 1 + 6));
+        // Without the version prefix
+        assert_eq!(distance(&zero[2..], &other), distance(&zero, &other));
+    }
+}
@@ -0,0 +1,812 @@
+use super::{
+    helpers::{digest_incremental, BlockBuffer},
+    Algorithm, DigestResult, Incremental,
+};
+use crate::chunked_stream::ChunkedStream;
+
+const CHUNK_SIZE: usize = 1024;
+
+const PRIME32_1: u32 = 0x9E3779B1;
+const PRIME32_2: u32 = 0x85EBCA77;
+const PRIME32_3: u32 = 0xC2B2AE3D;
+const PRIME32_4: u32 = 0x27D4EB2F;
+const PRIME32_5: u32 = 0x165667B1;
+
+const PRIME64_1: u64 = 0x9E3779B185EBCA87;
+const PRIME64_2: u64 = 0xC2B2AE3D27D4EB4F;
+const PRIME64_3: u64 = 0x165667B19E3779F9;
+const PRIME64_4: u64 = 0x85EBCA77C2B2AE63;
+const PRIME64_5: u64 = 0x27D4EB2F165667C5;
+
+const PRIME_MX1: u64 = 0x165667919E3779F9;
+const PRIME_MX2: u64 = 0x9FB21C651E98DF25;
+
+const SECRET: [u8; 192] = [
+    0xb8, 0xfe, 0x6c, 0x39, 0x23, 0xa4, 0x4b, 0xbe, 0x7c, 0x01, 0x81, 0x2c, 0xf7, 0x21, 0xad, 0x1c,
+    0xde, 0xd4, 0x6d, 0xe9, 0x83, 0x90, 0x97, 0xdb, 0x72, 0x40, 0xa4, 0xa4, 0xb7, 0xb3, 0x67, 0x1f,
+    0xcb, 0x79, 0xe6, 0x4e, 0xcc, 0xc0, 0xe5, 0x78, 0x82, 0x5a, 0xd0, 0x7d, 0xcc, 0xff, 0x72, 0x21,
+    0xb8, 0x08, 0x46, 0x74, 0xf7, 0x43, 0x24, 0x8e, 0xe0, 0x35, 0x90, 0xe6, 0x81, 0x3a, 0x26, 0x4c,
+    0x3c, 0x28, 0x52, 0xbb, 0x91, 0xc3, 0x00, 0xcb, 0x88, 0xd0, 0x65, 0x8b, 0x1b, 0x53, 0x2e, 0xa3,
+    0x71, 0x64, 0x48, 0x97, 0xa2, 0x0d, 0xf9, 0x4e, 0x38, 0x19, 0xef, 0x46, 0xa9, 0xde, 0xac, 0xd8,
+    0xa8, 0xfa, 0x76, 0x3f, 0xe3, 0x9c, 0x34, 0x3f, 0xf9, 0xdc, 0xbb, 0xc7, 0xc7, 0x0b, 0x4f, 0x1d,
+    0x8a, 0x51, 0xe0, 0x4b, 0xcd, 0xb4, 0x59, 0x31, 0xc8, 0x9f, 0x7e, 0xc9, 0xd9, 0x78, 0x73, 0x64,
+    0xea, 0xc5, 0xac, 0x83, 0x34, 0xd3, 0xeb, 0xc3, 0xc5, 0x81, 0xa0, 0xff, 0xfa, 0x13, 0x63, 0xeb,
+    0x17, 0x0d, 0xdd, 0x51, 0xb7, 0xf0, 0xda, 0x49, 0xd3, 0x16, 0x55, 0x26, 0x29, 0xd4, 0x68, 0x9e,
+    0x2b, 0x16, 0xbe, 0x58, 0x7d, 0x47, 0xa1, 0xfc, 0x8f, 0xf8, 0xb8, 0xd1, 0x7a, 0xd0, 0x31, 0xce,
+    0x45, 0xcb, 0x3a, 0x8f, 0x95, 0x16, 0x04, 0x28, 0xaf, 0xd7, 0xfb, 0xca, 0xbb, 0x4b, 0x40, 0x7e,
+];
+
+/// Inputs up to this length are hashed without the accumulator loop
+const MID_SIZE_MAX: usize = 240;
+const STRIPE_LEN: usize = 64;
+const SECRET_CONSUME_RATE: usize = 8;
+const STRIPES_PER_BLOCK: usize = (SECRET.len() - STRIPE_LEN) / SECRET_CONSUME_RATE;
+const BLOCK_LEN: usize = STRIPE_LEN * STRIPES_PER_BLOCK;
+
+/// XXH32, 32-bit xxHash with a 32-bit seed
+///
+/// Reference: https://github.com/Cyan4973/xxHash/blob/dev/doc/xxhash_spec.md
+pub struct Xxh32 {
+    seed: u32,
+}
+
+/// XXH64, 64-bit xxHash with a 64-bit seed
+///
+/// Reference: https://github.com/Cyan4973/xxHash/blob/dev/doc/xxhash_spec.md
+pub struct Xxh64 {
+    seed: u64,
+}
+
+/// XXH3 with a 64-bit or 128-bit output and a 64-bit seed, using the default secret
+///
+/// Reference: https://github.com/Cyan4973/xxHash/blob/dev/doc/xxhash_spec.md
+pub struct Xxh3 {
+    variant: Xxh3Variant,
+    seed: u64,
+}
+
+#[derive(Clone, Copy)]
+pub enum Xxh3Variant {
+    _64,
+    _128,
+}
+
+impl Xxh32 {
+    pub fn new(seed: u32) -> Self {
+        Self { seed }
+    }
+}
+
+impl Xxh64 {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+}
+
+impl Xxh3 {
+    pub fn new(variant: Xxh3Variant, seed: u64) -> Self {
+        Self { variant, seed }
+    }
+}
+
+fn read32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+struct Xxh32State {
+    seed: u32,
+    acc: [u32; 4],
+    buffer: BlockBuffer<16>,
+}
+
+impl Xxh32State {
+    fn new(seed: u32) -> Self {
+        Self {
+            seed,
+            acc: [
+                seed.wrapping_add(PRIME32_1).wrapping_add(PRIME32_2),
+                seed.wrapping_add(PRIME32_2),
+                seed,
+                seed.wrapping_sub(PRIME32_1),
+            ],
+            buffer: BlockBuffer::new(),
+        }
+    }
+}
+
+fn xxh32_round(acc: u32, lane: u32) -> u32 {
+    acc.wrapping_add(lane.wrapping_mul(PRIME32_2))
+        .rotate_left(13)
+        .wrapping_mul(PRIME32_1)
+}
+
+impl Incremental for Xxh32State {
+    fn update(&mut self, data: &[u8]) {
+        let acc = &mut self.acc;
+        self.buffer.update(data, |stripe| {
+            for (i, lane) in acc.iter_mut().enumerate() {
+                *lane = xxh32_round(*lane, read32(stripe, i * 4));
+            }
+        });
+    }
+
+    fn finalize(&self) -> Vec<u8> {
+        let acc = &self.acc;
+        let length = self.buffer.length;
+
+        let mut h = if length >= 16 {
+            acc[0]
+                .rotate_left(1)
+                .wrapping_add(acc[1].rotate_left(7))
+                .wrapping_add(acc[2].rotate_left(12))
+                .wrapping_add(acc[3].rotate_left(18))
+        } else {
+            self.seed.wrapping_add(PRIME32_5)
+        };
+        h = h.wrapping_add(length as u32);
+
+        let words = self.buffer.remainder().chunks_exact(4);
+        let bytes = words.remainder();
+        for word in words {
+            h = h.wrapping_add(read32(word, 0).wrapping_mul(PRIME32_3));
+            h = h.rotate_left(17).wrapping_mul(PRIME32_4);
+        }
+        for &byte in bytes {
+            h = h.wrapping_add((byte as u32).wrapping_mul(PRIME32_5));
+            h = h.rotate_left(11).wrapping_mul(PRIME32_1);
+        }
+
+        h ^= h >> 15;
+        h = h.wrapping_mul(PRIME32_2);
+        h ^= h >> 13;
+        h = h.wrapping_mul(PRIME32_3);
+        h ^= h >> 16;
+
+        h.to_be_bytes().to_vec()
+    }
+}
+
+impl Algorithm for Xxh32 {
+    fn digest(&self, data: ChunkedStream) -> DigestResult {
+        digest_incremental(&mut Xxh32State::new(self.seed), data, CHUNK_SIZE)
+    }
+
+    fn incremental(&self) -> Option<Box<dyn Incremental>> {
+        Some(Box::new(Xxh32State::new(self.seed)))
+    }
+}
+
+fn xxh64_round(acc: u64, lane: u64) -> u64 {
+    acc.wrapping_add(lane.wrapping_mul(PRIME64_2))
+        .rotate_left(31)
+        .wrapping_mul(PRIME64_1)
+}
+
+fn xxh64_avalanche(mut h: u64) -> u64 {
+    h ^= h >> 33;
+    h = h.wrapping_mul(PRIME64_2);
+    h ^= h >> 29;
+    h = h.wrapping_mul(PRIME64_3);
+    h ^ (h >> 32)
+}
+
+struct Xxh64State {
+    seed: u64,
+    acc: [u64; 4],
+    buffer: BlockBuffer<32>,
+}
+
+impl Xxh64State {
+    fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            acc: [
+                seed.wrapping_add(PRIME64_1).wrapping_add(PRIME64_2),
+                seed.wrapping_add(PRIME64_2),
+                seed,
+                seed.wrapping_sub(PRIME64_1),
+            ],
+            buffer: BlockBuffer::new(),
+        }
+    }
+}
+
+impl Incremental for Xxh64State {
+    fn update(&mut self, data: &[u8]) {
+        let acc = &mut self.acc;
+        self.buffer.update(data, |stripe| {
+            for (i, lane) in acc.iter_mut().enumerate() {
+                *lane = xxh64_round(*lane, read64(stripe, i * 8));
+            }
+        });
+    }
+
+    fn finalize(&self) -> Vec<u8> {
+        let acc = &self.acc;
+        let length = self.buffer.length;
+
+        let mut h = if length >= 32 {
+            let h = acc[0]
+                .rotate_left(1)
+                .wrapping_add(acc[1].rotate_left(7))
+                .wrapping_add(acc[2].rotate_left(12))
+                .wrapping_add(acc[3].rotate_left(18));
+
+            acc.iter().fold(h, |h, &lane| {
+                (h ^ xxh64_round(0, lane))
+                    .wrapping_mul(PRIME64_1)
+                    .wrapping_add(PRIME64_4)
+            })
+        } else {
+            self.seed.wrapping_add(PRIME64_5)
+        };
+        h = h.wrapping_add(length);
+
+        let words = self.buffer.remainder().chunks_exact(8);
+        let mut bytes = words.remainder();
+        for word in words {
+            h ^= xxh64_round(0, read64(word, 0));
+            h = h
+                .rotate_left(27)
+                .wrapping_mul(PRIME64_1)
+                .wrapping_add(PRIME64_4);
+        }
+        if bytes.len() >= 4 {
+            h ^= (read32(bytes, 0) as u64).wrapping_mul(PRIME64_1);
+            h = h
+                .rotate_left(23)
+                .wrapping_mul(PRIME64_2)
+                .wrapping_add(PRIME64_3);
+            bytes = &bytes[4..];
+        }
+        for &byte in bytes {
+            h ^= (byte as u64).wrapping_mul(PRIME64_5);
+            h = h.rotate_left(11).wrapping_mul(PRIME64_1);
+        }
+
+        xxh64_avalanche(h).to_be_bytes().to_vec()
+    }
+}
+
+impl Algorithm for Xxh64 {
+    fn digest(&self, data: ChunkedStream) -> DigestResult {
+        digest_incremental(&mut Xxh64State::new(self.seed), data, CHUNK_SIZE)
+    }
+
+    fn incremental(&self) -> Option<Box<dyn Incremental>> {
+        Some(Box::new(Xxh64State::new(self.seed)))
+    }
+}
+
+/// State of XXH3 for inputs given in pieces. The last block is only processed once
+/// more input comes, since it is finalized differently, and the end of the block
+/// before it is kept as the last stripe of the input may span both.
+struct Xxh3State {
+    variant: Xxh3Variant,
+    seed: u64,
+    secret: [u8; 192],
+    acc: [u64; 8],
+    length: u64,
+    previous_stripe: [u8; STRIPE_LEN],
+    last_block: [u8; BLOCK_LEN],
+    last_len: usize,
+}
+
+impl Xxh3State {
+    fn new(variant: Xxh3Variant, seed: u64) -> Self {
+        Self {
+            variant,
+            seed,
+            secret: custom_secret(seed),
+            acc: INITIAL_ACC,
+            length: 0,
+            previous_stripe: [0; STRIPE_LEN],
+            last_block: [0; BLOCK_LEN],
+            last_len: 0,
+        }
+    }
+}
+
+impl Incremental for Xxh3State {
+    fn update(&mut self, mut data: &[u8]) {
+        self.length += data.len() as u64;
+
+        while !data.is_empty() {
+            if self.last_len == BLOCK_LEN {
+                let secret = &self.secret;
+                accumulate(&mut self.acc, &self.last_block, secret, STRIPES_PER_BLOCK);
+                scramble(&mut self.acc, &secret[secret.len() - STRIPE_LEN..]);
+                self.previous_stripe
+                    .copy_from_slice(&self.last_block[BLOCK_LEN - STRIPE_LEN..]);
+                self.last_len = 0;
+            }
+
+            let taken = (BLOCK_LEN - self.last_len).min(data.len());
+            self.last_block[self.last_len..self.last_len + taken].copy_from_slice(&data[..taken]);
+            self.last_len += taken;
+            data = &data[taken..];
+        }
+    }
+
+    fn finalize(&self) -> Vec<u8> {
+        let last_block = &self.last_block[..self.last_len];
+        if self.length <= MID_SIZE_MAX as u64 {
+            // Short inputs fit in a single block
+            return match self.variant {
+                Xxh3Variant::_64 => xxh3_64_short(last_block, self.seed).to_be_bytes().to_vec(),
+                Xxh3Variant::_128 => xxh3_128_short(last_block, self.seed).to_be_bytes().to_vec(),
+            };
+        }
+
+        let secret = &self.secret;
+        let mut acc = self.acc;
+        let stripes = (last_block.len() - 1) / STRIPE_LEN;
+        accumulate(&mut acc, last_block, secret, stripes);
+
+        let mut last_stripe = [0; STRIPE_LEN];
+        if last_block.len() >= STRIPE_LEN {
+            last_stripe.copy_from_slice(&last_block[last_block.len() - STRIPE_LEN..]);
+        } else {
+            let from_previous = STRIPE_LEN - last_block.len();
+            last_stripe[..from_previous]
+                .copy_from_slice(&self.previous_stripe[STRIPE_LEN - from_previous..]);
+            last_stripe[from_previous..].copy_from_slice(last_block);
+        }
+        accumulate_512(
+            &mut acc,
+            &last_stripe,
+            &secret[secret.len() - STRIPE_LEN - 7..],
+        );
+
+        let length = self.length;
+        let low = merge_accs(&acc, &secret[11..], length.wrapping_mul(PRIME64_1));
+        match self.variant {
+            Xxh3Variant::_64 => low.to_be_bytes().to_vec(),
+            Xxh3Variant::_128 => {
+                let high = merge_accs(
+                    &acc,
+                    &secret[secret.len() - STRIPE_LEN - 11..],
+                    !length.wrapping_mul(PRIME64_2),
+                );
+                ((high as u128) << 64 | low as u128).to_be_bytes().to_vec()
+            }
+        }
+    }
+}
+
+impl Algorithm for Xxh3 {
+    fn digest(&self, data: ChunkedStream) -> DigestResult {
+        let mut state = Xxh3State::new(self.variant, self.seed);
+        digest_incremental(&mut state, data, BLOCK_LEN)
+    }
+
+    fn incremental(&self) -> Option<Box<dyn Incremental>> {
+        Some(Box::new(Xxh3State::new(self.variant, self.seed)))
+    }
+}
+
+const INITIAL_ACC: [u64; 8] = [
+    PRIME32_3 as u64,
+    PRIME64_1,
+    PRIME64_2,
+    PRIME64_3,
+    PRIME64_4,
+    PRIME32_2 as u64,
+    PRIME64_5,
+    PRIME32_1 as u64,
+];
+
+/// Derives the secret used for long inputs from the seed
+fn custom_secret(seed: u64) -> [u8; 192] {
+    let mut secret = SECRET;
+    for (i, pair) in secret.chunks_exact_mut(16).enumerate() {
+        let low = read64(&SECRET, i * 16).wrapping_add(seed);
+        let high = read64(&SECRET, i * 16 + 8).wrapping_sub(seed);
+        pair[..8].copy_from_slice(&low.to_le_bytes());
+        pair[8..].copy_from_slice(&high.to_le_bytes());
+    }
+
+    secret
+}
+
+fn accumulate_512(acc: &mut [u64; 8], stripe: &[u8], secret: &[u8]) {
+    for i in 0..8 {
+        let data_value = read64(stripe, i * 8);
+        let data_key = data_value ^ read64(secret, i * 8);
+
+        acc[i ^ 1] = acc[i ^ 1].wrapping_add(data_value);
+        acc[i] = acc[i].wrapping_add((data_key & 0xFFFFFFFF).wrapping_mul(data_key >> 32));
+    }
+}
+
+fn accumulate(acc: &mut [u64; 8], block: &[u8], secret: &[u8], stripes: usize) {
+    for (i, stripe) in block.chunks_exact(STRIPE_LEN).take(stripes).enumerate() {
+        accumulate_512(acc, stripe, &secret[i * SECRET_CONSUME_RATE..]);
+    }
+}
+
+fn scramble(acc: &mut [u64; 8], secret: &[u8]) {
+    for (i, lane) in acc.iter_mut().enumerate() {
+        let value = (*lane ^ (*lane >> 47)) ^ read64(secret, i * 8);
+        *lane = value.wrapping_mul(PRIME32_1 as u64);
+    }
+}
+
+fn merge_accs(acc: &[u64; 8], secret: &[u8], start: u64) -> u64 {
+    let result = (0..4).fold(start, |result, i| {
+        result.wrapping_add(mul128_fold64(
+            acc[i * 2] ^ read64(secret, i * 16),
+            acc[i * 2 + 1] ^ read64(secret, i * 16 + 8),
+        ))
+    });
+
+    avalanche(result)
+}
+
+fn mul128_fold64(a: u64, b: u64) -> u64 {
+    let product = a as u128 * b as u128;
+    product as u64 ^ (product >> 64) as u64
+}
+
+fn avalanche(mut h: u64) -> u64 {
+    h ^= h >> 37;
+    h = h.wrapping_mul(PRIME_MX1);
+    h ^ (h >> 32)
+}
+
+fn rrmxmx(mut h: u64, length: u64) -> u64 {
+    h ^= h.rotate_left(49) ^ h.rotate_left(24);
+    h = h.wrapping_mul(PRIME_MX2);
+    h ^= (h >> 35).wrapping_add(length);
+    h = h.wrapping_mul(PRIME_MX2);
+    h ^ (h >> 28)
+}
+
+fn mix16(input: &[u8], secret: &[u8], seed: u64) -> u64 {
+    mul128_fold64(
+        read64(input, 0) ^ read64(secret, 0).wrapping_add(seed),
+        read64(input, 8) ^ read64(secret, 8).wrapping_sub(seed),
+    )
+}
+
+/// Mixes two 16-byte inputs into the low and high halves of a 128-bit hash
+fn mix32(acc: (u64, u64), input1: &[u8], input2: &[u8], secret: &[u8], seed: u64) -> (u64, u64) {
+    let mut low = acc.0.wrapping_add(mix16(input1, secret, seed));
+    low ^= read64(input2, 0).wrapping_add(read64(input2, 8));
+    let mut high = acc.1.wrapping_add(mix16(input2, &secret[16..], seed));
+    high ^= read64(input1, 0).wrapping_add(read64(input1, 8));
+
+    (low, high)
+}
+
+/// Combines the 128-bit accumulator of inputs from 17 to 240 bytes
+fn finish_128(acc: (u64, u64), length: u64, seed: u64) -> u128 {
+    let low = avalanche(acc.0.wrapping_add(acc.1));
+    let high = 0u64.wrapping_sub(avalanche(
+        acc.0
+            .wrapping_mul(PRIME64_1)
+            .wrapping_add(acc.1.wrapping_mul(PRIME64_4))
+            .wrapping_add(length.wrapping_sub(seed).wrapping_mul(PRIME64_2)),
+    ));
+
+    (high as u128) << 64 | low as u128
+}
+
+fn xxh3_64_short(input: &[u8], mut seed: u64) -> u64 {
+    let length = input.len();
+    let secret = &SECRET;
+
+    match length {
+        0 => xxh64_avalanche(seed ^ read64(secret, 56) ^ read64(secret, 64)),
+        1..=3 => {
+            let combined = (input[0] as u32) << 16
+                | (input[length >> 1] as u32) << 24
+                | input[length - 1] as u32
+                | (length as u32) << 8;
+            let flip = ((read32(secret, 0) ^ read32(secret, 4)) as u64).wrapping_add(seed);
+            xxh64_avalanche(combined as u64 ^ flip)
+        }
+        4..=8 => {
+            seed ^= ((seed as u32).swap_bytes() as u64) << 32;
+            let flip = (read64(secret, 8) ^ read64(secret, 16)).wrapping_sub(seed);
+            let input64 =
+                (read32(input, length - 4) as u64).wrapping_add((read32(input, 0) as u64) << 32);
+            rrmxmx(input64 ^ flip, length as u64)
+        }
+        9..=16 => {
+            let flip1 = (read64(secret, 24) ^ read64(secret, 32)).wrapping_add(seed);
+            let flip2 = (read64(secret, 40) ^ read64(secret, 48)).wrapping_sub(seed);
+            let low = read64(input, 0) ^ flip1;
+            let high = read64(input, length - 8) ^ flip2;
+
+            avalanche(
+                (length as u64)
+                    .wrapping_add(low.swap_bytes())
+                    .wrapping_add(high)
+                    .wrapping_add(mul128_fold64(low, high)),
+            )
+        }
+        17..=128 => {
+            // Pairs of 16-byte inputs from both ends, as many as the length allows
+            let pairs = (length - 1) / 32 + 1;
+            let acc = (0..pairs).fold((length as u64).wrapping_mul(PRIME64_1), |acc, i| {
+                acc.wrapping_add(mix16(&input[16 * i..], &secret[32 * i..], seed))
+                    .wrapping_add(mix16(
+                        &input[length - 16 * (i + 1)..],
+                        &secret[32 * i + 16..],
+                        seed,
+                    ))
+            });
+
+            avalanche(acc)
+        }
+        _ => {
+            let rounds = length / 16;
+            let mut acc = (0..8).fold((length as u64).wrapping_mul(PRIME64_1), |acc, i| {
+                acc.wrapping_add(mix16(&input[16 * i..], &secret[16 * i..], seed))
+            });
+            acc = avalanche(acc);
+
+            acc = (8..rounds).fold(acc, |acc, i| {
+                acc.wrapping_add(mix16(&input[16 * i..], &secret[16 * (i - 8) + 3..], seed))
+            });
+            acc = acc.wrapping_add(mix16(&input[length - 16..], &secret[119..], seed));
+
+            avalanche(acc)
+        }
+    }
+}
+
+fn xxh3_128_short(input: &[u8], mut seed: u64) -> u128 {
+    let length = input.len();
+    let secret = &SECRET;
+    let halves = |low: u64, high: u64| (high as u128) << 64 | low as u128;
+
+    match length {
+        0 => halves(
+            xxh64_avalanche(seed ^ read64(secret, 64) ^ read64(secret, 72)),
+            xxh64_avalanche(seed ^ read64(secret, 80) ^ read64(secret, 88)),
+        ),
+        1..=3 => {
+            let combined_low = (input[0] as u32) << 16
+                | (input[length >> 1] as u32) << 24
+                | input[length - 1] as u32
+                | (length as u32) << 8;
+            let combined_high = combined_low.swap_bytes().rotate_left(13);
+
+            let flip_low = ((read32(secret, 0) ^ read32(secret, 4)) as u64).wrapping_add(seed);
+            let flip_high = ((read32(secret, 8) ^ read32(secret, 12)) as u64).wrapping_sub(seed);
+
+            halves(
+                xxh64_avalanche(combined_low as u64 ^ flip_low),
+                xxh64_avalanche(combined_high as u64 ^ flip_high),
+            )
+        }
+        4..=8 => {
+            seed ^= ((seed as u32).swap_bytes() as u64) << 32;
+            let input64 =
+                (read32(input, 0) as u64).wrapping_add((read32(input, length - 4) as u64) << 32);
+            let flip = (read64(secret, 16) ^ read64(secret, 24)).wrapping_add(seed);
+
+            let product =
+                (input64 ^ flip) as u128 * PRIME64_1.wrapping_add((length as u64) << 2) as u128;
+            let (mut low, mut high) = (product as u64, (product >> 64) as u64);
+            high = high.wrapping_add(low << 1);
+            low ^= high >> 3;
+
+            low ^= low >> 35;
+            low = low.wrapping_mul(PRIME_MX2);
+            low ^= low >> 28;
+
+            halves(low, avalanche(high))
+        }
+        9..=16 => {
+            let flip_low = (read64(secret, 32) ^ read64(secret, 40)).wrapping_sub(seed);
+            let flip_high = (read64(secret, 48) ^ read64(secret, 56)).wrapping_add(seed);
+            let input_low = read64(input, 0);
+            let input_high = read64(input, length - 8) ^ flip_high;
+
+            let product =
+                (input_low ^ read64(input, length - 8) ^ flip_low) as u128 * PRIME64_1 as u128;
+            let mut low = (product as u64).wrapping_add((length as u64 - 1) << 54);
+            let high =
+                ((product >> 64) as u64)
+                    .wrapping_add(input_high.wrapping_add(
+                        (input_high & 0xFFFFFFFF).wrapping_mul(PRIME32_2 as u64 - 1),
+                    ));
+            low ^= high.swap_bytes();
+
+            let product = low as u128 * PRIME64_2 as u128;
+            let result_high = ((product >> 64) as u64).wrapping_add(high.wrapping_mul(PRIME64_2));
+
+            halves(avalanche(product as u64), avalanche(result_high))
+        }
+        17..=128 => {
+            let pairs = (length - 1) / 32 + 1;
+            let acc =
+                (0..pairs)
+                    .rev()
+                    .fold(((length as u64).wrapping_mul(PRIME64_1), 0), |acc, i| {
+                        mix32(
+                            acc,
+                            &input[16 * i..],
+                            &input[length - 16 * (i + 1)..],
+                            &secret[32 * i..],
+                            seed,
+                        )
+                    });
+
+            finish_128(acc, length as u64, seed)
+        }
+        _ => {
+            let rounds = length / 32;
+            let mut acc = (0..4).fold(((length as u64).wrapping_mul(PRIME64_1), 0), |acc, i| {
+                mix32(
+                    acc,
+                    &input[32 * i..],
+                    &input[32 * i + 16..],
+                    &secret[32 * i..],
+                    seed,
+                )
+            });
+            acc = (avalanche(acc.0), avalanche(acc.1));
+
+            acc = (4..rounds).fold(acc, |acc, i| {
+                mix32(
+                    acc,
+                    &input[32 * i..],
+                    &input[32 * i + 16..],
+                    &secret[32 * (i - 4) + 3..],
+                    seed,
+                )
+            });
+            acc = mix32(
+                acc,
+                &input[length - 16..],
+                &input[length - 32..],
+                &secret[103..],
+                0u64.wrapping_sub(seed),
+            );
+
+            finish_128(acc, length as u64, seed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::algorithms::helpers::test::assert_digest;
+
+    use super::*;
+
+    /// Deterministic non-repeating input of the given length
+    fn data(length: usize) -> ChunkedStream {
+        ChunkedStream::Bytes((0..length).map(|i| (i * 31 % 251) as u8).collect())
+    }
+
+    fn digest_hex(algorithm: &dyn Algorithm, length: usize) -> String {
+        hex::encode(algorithm.digest(data(length)).unwrap())
+    }
+
+    #[test]
+    fn xxh32_correct() {
+        for (input, expected) in [
+            ("", "02cc5d05"),
+            ("a", "550d7456"),
+            ("abc", "32d153ff"),
+            ("The quick brown fox jumps over the lazy dog", "e85ea4de"),
+        ] {
+            assert_digest(&Xxh32::new(0), input, expected);
+        }
+
+        assert_digest(&Xxh32::new(42), "abc", "0147abfe");
+        assert_eq!(digest_hex(&Xxh32::new(0), 5000), "776250b3");
+        assert_eq!(digest_hex(&Xxh32::new(42), 2048), "fb7721a6");
+    }
+
+    #[test]
+    fn xxh64_correct() {
+        for (input, expected) in [
+            ("", "ef46db3751d8e999"),
+            ("a", "d24ec4f1a98c6e5b"),
+            ("abc", "44bc2cf5ad770999"),
+            (
+                "The quick brown fox jumps over the lazy dog",
+                "0b242d361fda71bc",
+            ),
+        ] {
+            assert_digest(&Xxh64::new(0), input, expected);
+        }
+
+        assert_digest(&Xxh64::new(42), "abc", "13c1d910702770e6");
+        assert_eq!(digest_hex(&Xxh64::new(0), 5000), "e3851ef69b545e15");
+        assert_eq!(digest_hex(&Xxh64::new(42), 2048), "4c7b0ecc4eb507e1");
+    }
+
+    #[test]
+    fn xxh3_64_correct() {
+        // Covers every size class, and long inputs ending with a partial stripe
+        for (length, expected, expected_seeded) in [
+            (0, "2d06800538d394c2", "b029411ff43d84d2"),
+            (5, "3e2847637b2924d1", "bbdb503b9e12d216"),
+            (12, "1fdd6f00c4cdf526", "8a8d7a90b5651c15"),
+            (100, "1efbaa652af7dfb7", "4846b42298c689de"),
+            (200, "c06367d405bdc71d", "cfc47383eaf29851"),
+            (1024, "cbdff56b050f2c1c", "decec0e5317f2392"),
+            (2048, "eef46c3d948c544e", "433d1bb6a5aad3cc"),
+            (5000, "a092d73116291201", "9317f08a81b8d35d"),
+        ] {
+            assert_eq!(
+                digest_hex(&Xxh3::new(Xxh3Variant::_64, 0), length),
+                expected
+            );
+            assert_eq!(
+                digest_hex(&Xxh3::new(Xxh3Variant::_64, 42), length),
+                expected_seeded
+            );
+        }
+
+        assert_digest(&Xxh3::new(Xxh3Variant::_64, 0), "a", "e6c632b61e964e1f");
+        assert_digest(&Xxh3::new(Xxh3Variant::_64, 0), "abc", "78af5f94892f3950");
+    }
+
+    #[test]
+    fn xxh3_128_correct() {
+        for (length, expected, expected_seeded) in [
+            (
+                0,
+                "99aa06d3014798d86001c324468d497f",
+                "16c20acd33f7af2f3c1d09e9fe249164",
+            ),
+            (
+                5,
+                "8156cb136c7003e99f31da1ddba90baf",
+                "3b67ec8fe9e1d7a895bc62b0d6e35704",
+            ),
+            (
+                12,
+                "b1fea0dc49b72dec9fc089ace183eb69",
+                "5fdc1540d5f784fc7512462180c94531",
+            ),
+            (
+                100,
+                "2701f07d2c26abff94b1961520a11588",
+                "6332274b55ead791eb7c6b31fbfa2b06",
+            ),
+            (
+                200,
+                "cab4b6c1d7f66c432a1a7281f2883dff",
+                "a25a20f069f00aa4ba0a02d9d85cde80",
+            ),
+            (
+                1024,
+                "5f6ea7ec3ae785ebcbdff56b050f2c1c",
+                "b550e7d70efe8454decec0e5317f2392",
+            ),
+            (
+                5000,
+                "5f50ca3aad5b935da092d73116291201",
+                "5b5e942128615b5d9317f08a81b8d35d",
+            ),
+        ] {
+            assert_eq!(
+                digest_hex(&Xxh3::new(Xxh3Variant::_128, 0), length),
+                expected
+            );
+            assert_eq!(
+                digest_hex(&Xxh3::new(Xxh3Variant::_128, 42), length),
+                expected_seeded
+            );
+        }
+
+        assert_digest(
+            &Xxh3::new(Xxh3Variant::_128, 0),
+            "abc",
+            "06b05ab6733a618578af5f94892f3950",
+        );
+    }
+}
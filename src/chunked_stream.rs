@@ -1,9 +1,27 @@
 use std::fs::File;
-use std::io::{BufRead, BufReader, Read, Result, Stdin};
+use std::io::{self, BufRead, BufReader, Read, Result, Seek, Stdin};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use memmap2::Mmap;
+
+/// Number of bytes read at once by `for_each_chunk` from buffered streams
+const READ_SIZE: usize = 65536;
 
 pub enum ChunkedStream {
     Bytes(Vec<u8>),
-    File(Box<dyn BufRead>),
+    File(Box<dyn BufRead + Send>),
+    /// Regular file mapped into memory, of which the bytes from `start` to `end` are read
+    Mapped {
+        map: Mmap,
+        start: usize,
+        end: usize,
+    },
+    /// Stream which adds the number of bytes read from it to `counter` as it is read
+    Counted {
+        inner: Box<ChunkedStream>,
+        counter: Arc<AtomicU64>,
+    },
 }
 
 pub struct ChunkedIter {
@@ -18,6 +36,25 @@ impl From<String> for ChunkedStream {
     }
 }
 
+/// Maps a regular file into memory from its current position, None for empty files,
+/// pipes, special files and anything else which cannot be mapped
+fn map_file(file: &mut File) -> Option<ChunkedStream> {
+    let metadata = file.metadata().ok()?;
+    if !metadata.is_file() || metadata.len() == 0 {
+        return None;
+    }
+
+    let start = file.stream_position().ok()?;
+    // SAFETY: reading a page of the file which was truncated since it was mapped
+    // raises SIGBUS, which aborts the process. Callers of `mapped` make sure the
+    // file is not truncated while it is read.
+    let map = unsafe { Mmap::map(&*file) }.ok()?;
+    let start = usize::try_from(start).ok()?.min(map.len());
+    let end = map.len();
+
+    Some(ChunkedStream::Mapped { map, start, end })
+}
+
 impl From<File> for ChunkedStream {
     fn from(file: File) -> Self {
         ChunkedStream::File(Box::new(BufReader::new(file)))
@@ -31,38 +68,199 @@ impl From<Stdin> for ChunkedStream {
 }
 
 impl ChunkedStream {
+    /// Maps a regular file into memory from its current position, which saves copying
+    /// its bytes into a buffer. Other files are read through a buffer.
+    ///
+    /// The process is killed by SIGBUS if the file is truncated while it is read, so
+    /// only map files which do not change until they are hashed.
+    pub fn mapped(mut file: File) -> Self {
+        map_file(&mut file).unwrap_or_else(|| Self::from(file))
+    }
+
+    /// Counts the bytes read from the stream in `counter`, which can be read from
+    /// other threads to report progress. Skipped bytes are not counted.
+    pub fn counted(self, counter: Arc<AtomicU64>) -> Self {
+        ChunkedStream::Counted {
+            inner: Box::new(self),
+            counter,
+        }
+    }
+
     pub fn into_iter(self, chunk_size: usize) -> ChunkedIter {
         ChunkedIter {
             data: self,
             chunk_size,
         }
     }
+
+    /// Skips the first `count` bytes by reading through them, failing with
+    /// `UnexpectedEof` if the stream is shorter than that.
+    pub fn skip(self, count: u64) -> Result<Self> {
+        match self {
+            ChunkedStream::Bytes(mut bytes) => {
+                if count > bytes.len() as u64 {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+
+                bytes.drain(..count as usize);
+                Ok(ChunkedStream::Bytes(bytes))
+            }
+            ChunkedStream::File(mut reader) => {
+                if io::copy(&mut reader.by_ref().take(count), &mut io::sink())? < count {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+
+                Ok(ChunkedStream::File(reader))
+            }
+            ChunkedStream::Mapped { map, start, end } => {
+                if count > (end - start) as u64 {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+
+                let start = start + count as usize;
+                Ok(ChunkedStream::Mapped { map, start, end })
+            }
+            ChunkedStream::Counted { inner, counter } => Ok(inner.skip(count)?.counted(counter)),
+        }
+    }
+
+    /// Limits the stream to its first `length` bytes
+    pub fn take(self, length: u64) -> Self {
+        match self {
+            ChunkedStream::Bytes(mut bytes) => {
+                bytes.truncate(length.min(bytes.len() as u64) as usize);
+                ChunkedStream::Bytes(bytes)
+            }
+            ChunkedStream::File(reader) => ChunkedStream::File(Box::new(reader.take(length))),
+            ChunkedStream::Mapped { map, start, end } => {
+                let end = (start as u64).saturating_add(length).min(end as u64) as usize;
+                ChunkedStream::Mapped { map, start, end }
+            }
+            ChunkedStream::Counted { inner, counter } => inner.take(length).counted(counter),
+        }
+    }
+
+    /// Calls `process` with every chunk of the stream in order, without allocating
+    /// per chunk. Chunks borrow from the stream (or from a single read buffer), and
+    /// all of them are `chunk_size` bytes long except for the last one, which is
+    /// shorter if the length of the stream is not a multiple of the chunk size.
+    ///
+    /// An empty stream has no chunks.
+    pub fn for_each_chunk(self, chunk_size: usize, mut process: impl FnMut(&[u8])) -> Result<()> {
+        self.try_for_each_chunk(chunk_size, |chunk| {
+            process(chunk);
+            Ok(())
+        })
+    }
+
+    /// Same as `for_each_chunk`, but stops reading at the first error returned by
+    /// `process`, which is returned
+    pub fn try_for_each_chunk(
+        self,
+        chunk_size: usize,
+        mut process: impl FnMut(&[u8]) -> Result<()>,
+    ) -> Result<()> {
+        match self {
+            ChunkedStream::Bytes(bytes) => bytes.chunks(chunk_size).try_for_each(process),
+            ChunkedStream::Mapped { map, start, end } => {
+                map[start..end].chunks(chunk_size).try_for_each(process)
+            }
+            ChunkedStream::File(mut reader) => {
+                // Whole chunks are read at once into a single buffer
+                let mut buffer = vec![0; chunk_size * (READ_SIZE / chunk_size).max(1)];
+                loop {
+                    // Fill the buffer, reads can return fewer bytes before the end
+                    let mut filled = 0;
+                    while filled < buffer.len() {
+                        match reader.read(&mut buffer[filled..]) {
+                            Ok(0) => break,
+                            Ok(read) => filled += read,
+                            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                            Err(err) => return Err(err),
+                        }
+                    }
+
+                    buffer[..filled]
+                        .chunks(chunk_size)
+                        .try_for_each(&mut process)?;
+                    if filled < buffer.len() {
+                        return Ok(());
+                    }
+                }
+            }
+            ChunkedStream::Counted { inner, counter } => {
+                // Bytes are counted every READ_SIZE bytes or so rather than every chunk
+                let segment_size = chunk_size * (READ_SIZE / chunk_size).max(1);
+                let mut segment = 0;
+                let mut count_chunk = |chunk: &[u8]| {
+                    segment += chunk.len();
+                    if segment >= segment_size {
+                        counter.fetch_add(segment as u64, Ordering::Relaxed);
+                        segment = 0;
+                    }
+                    process(chunk)
+                };
+                // Not generic over the closure, which would recurse for nested streams
+                let result = inner.try_for_each_chunk(
+                    chunk_size,
+                    &mut count_chunk as &mut dyn FnMut(&[u8]) -> Result<()>,
+                );
+                counter.fetch_add(segment as u64, Ordering::Relaxed);
+                result
+            }
+        }
+    }
 }
 
 impl Iterator for ChunkedIter {
     type Item = Result<Vec<u8>>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match &mut self.data {
-            ChunkedStream::Bytes(bytes) => {
-                if bytes.is_empty() {
-                    None
-                } else if bytes.len() < self.chunk_size {
-                    Some(Ok(bytes.drain(..).collect()))
-                } else {
-                    Some(Ok(bytes.drain(..self.chunk_size).collect()))
-                }
+        next_chunk(&mut self.data, self.chunk_size)
+    }
+}
+
+/// Next chunk of the stream, read or copied into a new vector. Prefer
+/// `for_each_chunk`, which does not allocate per chunk.
+fn next_chunk(data: &mut ChunkedStream, chunk_size: usize) -> Option<Result<Vec<u8>>> {
+    match data {
+        ChunkedStream::Bytes(bytes) => {
+            if bytes.is_empty() {
+                None
+            } else if bytes.len() < chunk_size {
+                Some(Ok(std::mem::take(bytes)))
+            } else {
+                Some(Ok(bytes.drain(..chunk_size).collect()))
             }
+        }
 
-            ChunkedStream::File(reader) => {
-                let mut chunk = Vec::with_capacity(self.chunk_size);
+        ChunkedStream::File(reader) => {
+            let mut chunk = Vec::with_capacity(chunk_size);
 
-                match reader.take(self.chunk_size as u64).read_to_end(&mut chunk) {
-                    Ok(0) => None,
-                    Ok(_) => Some(Ok(chunk)),
-                    Err(err) => Some(Err(err)),
-                }
+            match reader.take(chunk_size as u64).read_to_end(&mut chunk) {
+                Ok(0) => None,
+                Ok(_) => Some(Ok(chunk)),
+                Err(err) => Some(Err(err)),
+            }
+        }
+
+        ChunkedStream::Mapped { map, start, end } => {
+            if *start == *end {
+                return None;
             }
+
+            let chunk_end = (*end).min(*start + chunk_size);
+            let chunk = map[*start..chunk_end].to_vec();
+            *start = chunk_end;
+            Some(Ok(chunk))
+        }
+
+        ChunkedStream::Counted { inner, counter } => {
+            let chunk = next_chunk(inner, chunk_size);
+            if let Some(Ok(chunk)) = &chunk {
+                counter.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+            }
+            chunk
         }
     }
 }
@@ -74,6 +272,7 @@ mod test {
     use super::ChunkedStream;
 
     #[test]
+    #[allow(clippy::useless_vec, clippy::explicit_counter_loop)]
     fn chunk_iterate_bytes() {
         let data = ChunkedStream::Bytes("Example message".as_bytes().into());
         let expected = vec![
@@ -93,6 +292,131 @@ mod test {
     }
 
     #[test]
+    fn skip_and_take() {
+        let collect = |data: ChunkedStream| -> Vec<u8> {
+            data.into_iter(4).flat_map(|chunk| chunk.unwrap()).collect()
+        };
+
+        let bytes = ChunkedStream::Bytes(b"Example message".to_vec());
+        assert_eq!(collect(bytes.skip(8).unwrap().take(4)), b"mess");
+
+        let reader = ChunkedStream::File(Box::new(&b"Example message"[..]));
+        assert_eq!(collect(reader.skip(8).unwrap().take(100)), b"message");
+
+        for data in [
+            ChunkedStream::Bytes(b"short".to_vec()),
+            ChunkedStream::File(Box::new(&b"short"[..])),
+        ] {
+            assert_eq!(
+                data.skip(6).err().unwrap().kind(),
+                std::io::ErrorKind::UnexpectedEof
+            );
+        }
+    }
+
+    #[test]
+    fn counts_bytes_read() {
+        use std::io::Cursor;
+        use std::sync::{atomic::AtomicU64, Arc};
+
+        let data = vec![7; 200_000];
+        for (stream, iterate) in [
+            (ChunkedStream::Bytes(data.clone()), false),
+            (
+                ChunkedStream::File(Box::new(Cursor::new(data.clone()))),
+                false,
+            ),
+            (ChunkedStream::Bytes(data.clone()), true),
+        ] {
+            let counter = Arc::new(AtomicU64::new(0));
+            let stream = stream.counted(counter.clone()).skip(10).unwrap();
+            let stream = stream.take(150_000);
+
+            let mut length = 0;
+            match iterate {
+                true => stream
+                    .into_iter(64)
+                    .for_each(|chunk| length += chunk.unwrap().len()),
+                false => stream
+                    .for_each_chunk(64, |chunk| length += chunk.len())
+                    .unwrap(),
+            }
+            assert_eq!(length, 150_000);
+            assert_eq!(counter.load(std::sync::atomic::Ordering::Relaxed), 150_000);
+        }
+    }
+
+    #[test]
+    fn maps_regular_files() {
+        use std::io::{Seek, SeekFrom, Write};
+
+        let mut tmpfile = tempfile::tempfile().unwrap();
+        write!(tmpfile, "Example message in file").unwrap();
+        tmpfile.seek(SeekFrom::Start(8)).unwrap();
+
+        let data = ChunkedStream::mapped(tmpfile.try_clone().unwrap());
+        assert!(matches!(data, ChunkedStream::Mapped { .. }));
+        assert!(matches!(
+            ChunkedStream::from(tmpfile),
+            ChunkedStream::File(_)
+        ));
+
+        let data = data.skip(3).unwrap().take(9);
+        let mut chunks = vec![];
+        data.for_each_chunk(4, |chunk| chunks.push(chunk.to_vec()))
+            .unwrap();
+        assert_eq!(chunks, [b"sage".to_vec(), b" in ".to_vec(), b"f".to_vec()]);
+
+        // Empty files cannot be mapped
+        let data = ChunkedStream::mapped(tempfile::tempfile().unwrap());
+        assert!(matches!(data, ChunkedStream::File(_)));
+        assert_eq!(data.into_iter(4).count(), 0);
+    }
+
+    #[test]
+    fn chunks_are_whole_for_every_source() {
+        /// Reader which returns at most 3 bytes per read
+        struct Trickle<'a>(&'a [u8]);
+
+        impl std::io::Read for Trickle<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let length = buf.len().min(3).min(self.0.len());
+                buf[..length].copy_from_slice(&self.0[..length]);
+                self.0 = &self.0[length..];
+                Ok(length)
+            }
+        }
+
+        let message = b"Example message in file";
+        let reader = BufReader::with_capacity(2, Trickle(message));
+        let mut chunks = vec![];
+        ChunkedStream::File(Box::new(reader))
+            .for_each_chunk(8, |chunk| chunks.push(chunk.to_vec()))
+            .unwrap();
+        assert_eq!(chunks, message.chunks(8).collect::<Vec<_>>());
+
+        let mut count = 0;
+        ChunkedStream::Bytes(vec![])
+            .for_each_chunk(8, |_| count += 1)
+            .unwrap();
+        assert_eq!(count, 0);
+
+        // Stops at the first error
+        let mut count = 0;
+        let reader = BufReader::new(Trickle(message));
+        let result = ChunkedStream::File(Box::new(reader)).try_for_each_chunk(4, |_| {
+            count += 1;
+            match count {
+                2 => Err(std::io::ErrorKind::Interrupted.into()),
+                _ => Ok(()),
+            }
+        });
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::Interrupted);
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    #[allow(clippy::useless_vec, clippy::explicit_counter_loop)]
     fn chunk_iterate_file() {
         use std::io::{Seek, SeekFrom, Write};
 
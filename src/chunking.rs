@@ -0,0 +1,269 @@
+//! Content-defined chunking, which splits a stream where its content says so
+//! rather than at fixed offsets, so that inserting or removing bytes only
+//! changes the chunks around the edit.
+//!
+//! Reference: Xia et al., "The Design of Fast Content-Defined Chunking for
+//! Data Deduplication Based Storage Systems" (FastCDC, 2020)
+
+use std::io;
+
+use crate::chunked_stream::ChunkedStream;
+
+/// Size of the reads from the underlying stream
+const READ_SIZE: usize = 65536;
+
+/// SplitMix64 step, used to fill the gear table at compile time
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    (state, z ^ (z >> 31))
+}
+
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0; 256];
+    let mut state = 0;
+    let mut i = 0;
+    while i < 256 {
+        let (next, value) = splitmix64(state);
+        table[i] = value;
+        state = next;
+        i += 1;
+    }
+    table
+}
+
+/// Random value for every byte, generated with SplitMix64 from a zero seed.
+///
+/// Boundaries therefore differ from other FastCDC implementations, which each
+/// ship their own table.
+pub const GEAR: [u64; 256] = gear_table();
+
+/// Gear rolling hash, where every byte shifts the previous ones one bit further
+/// out so that the hash only depends on the last 64 bytes.
+#[derive(Clone, Copy, Default)]
+pub struct Gear {
+    hash: u64,
+}
+
+impl Gear {
+    pub fn roll(&mut self, byte: u8) -> u64 {
+        self.hash = (self.hash << 1).wrapping_add(GEAR[byte as usize]);
+        self.hash
+    }
+}
+
+/// Minimum, average (targeted) and maximum size of chunks in bytes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChunkSizes {
+    pub min: usize,
+    pub avg: usize,
+    pub max: usize,
+}
+
+impl ChunkSizes {
+    /// Smallest average size accepted, below which chunk metadata outweighs the chunks
+    pub const MIN_AVERAGE: usize = 64;
+
+    /// None unless `min <= avg <= max` and `avg` is at least `MIN_AVERAGE`
+    pub fn new(min: usize, avg: usize, max: usize) -> Option<Self> {
+        (min > 0 && min <= avg && avg <= max && avg >= Self::MIN_AVERAGE).then_some(Self {
+            min,
+            avg,
+            max,
+        })
+    }
+
+    /// A quarter of the average for the minimum and four times as much for the maximum,
+    /// as suggested for FastCDC
+    pub fn from_average(avg: usize) -> Option<Self> {
+        Self::new(avg / 4, avg, avg.checked_mul(4)?)
+    }
+}
+
+/// Mask of the `bits` highest bits, which depend on the most bytes of a Gear hash
+fn top_bits(bits: u32) -> u64 {
+    !0 << (64 - bits.clamp(1, 63))
+}
+
+/// FastCDC chunker, splitting streams into chunks between the minimum and the
+/// maximum size (other than the last one, which may be shorter).
+///
+/// Cut points are never searched in the first `min` bytes of a chunk, and a
+/// stricter mask is used before the average size than after, which keeps chunk
+/// sizes close to the average (normalized chunking, level 1).
+pub struct FastCdc {
+    sizes: ChunkSizes,
+    mask_small: u64,
+    mask_large: u64,
+}
+
+impl FastCdc {
+    pub fn new(sizes: ChunkSizes) -> Self {
+        let bits = sizes.avg.ilog2();
+        Self {
+            sizes,
+            mask_small: top_bits(bits + 1),
+            mask_large: top_bits(bits - 1),
+        }
+    }
+
+    /// Length of the next chunk at the start of `data`, which holds either the
+    /// rest of the stream or at least the maximum size
+    fn cut(&self, data: &[u8]) -> usize {
+        let length = data.len();
+        if length <= self.sizes.min {
+            return length;
+        }
+
+        let end = length.min(self.sizes.max);
+        let normal = self.sizes.avg.min(end);
+
+        let mut gear = Gear::default();
+        for (i, &byte) in data[self.sizes.min..end].iter().enumerate() {
+            let position = self.sizes.min + i;
+            let mask = match position < normal {
+                true => self.mask_small,
+                false => self.mask_large,
+            };
+            if gear.roll(byte) & mask == 0 {
+                return position + 1;
+            }
+        }
+
+        end
+    }
+
+    /// Calls `process` with every chunk of the stream in order, borrowed from a
+    /// single buffer, and stops at the first error it returns
+    pub fn try_for_each_chunk(
+        &self,
+        data: ChunkedStream,
+        mut process: impl FnMut(&[u8]) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let mut buffer = Vec::with_capacity(self.sizes.max + READ_SIZE);
+        data.try_for_each_chunk(READ_SIZE, |read| {
+            buffer.extend_from_slice(read);
+
+            // A cut point can be anywhere up to the maximum size
+            let mut start = 0;
+            while buffer.len() - start >= self.sizes.max {
+                let length = self.cut(&buffer[start..]);
+                process(&buffer[start..start + length])?;
+                start += length;
+            }
+
+            buffer.drain(..start);
+            Ok(())
+        })?;
+
+        let mut start = 0;
+        while start < buffer.len() {
+            let length = self.cut(&buffer[start..]);
+            process(&buffer[start..start + length])?;
+            start += length;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn xorshift(length: usize, seed: u32) -> Vec<u8> {
+        let mut x = seed;
+        (0..length)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                x as u8
+            })
+            .collect()
+    }
+
+    fn chunks(data: ChunkedStream, sizes: ChunkSizes) -> Vec<Vec<u8>> {
+        let mut chunks = vec![];
+        FastCdc::new(sizes)
+            .try_for_each_chunk(data, |chunk| {
+                chunks.push(chunk.to_vec());
+                Ok(())
+            })
+            .unwrap();
+        chunks
+    }
+
+    #[test]
+    fn validates_sizes() {
+        assert_eq!(
+            ChunkSizes::from_average(8192),
+            Some(ChunkSizes {
+                min: 2048,
+                avg: 8192,
+                max: 32768
+            })
+        );
+        assert_eq!(ChunkSizes::new(4096, 2048, 8192), None);
+        assert_eq!(ChunkSizes::new(0, 2048, 8192), None);
+        assert_eq!(ChunkSizes::new(16, 32, 64), None);
+    }
+
+    #[test]
+    fn chunk_sizes_are_bounded() {
+        let sizes = ChunkSizes::from_average(1024).unwrap();
+        let data = xorshift(200_000, 2463534242);
+        let chunks = chunks(ChunkedStream::Bytes(data.clone()), sizes);
+
+        assert_eq!(chunks.concat(), data);
+        let (last, rest) = chunks.split_last().unwrap();
+        assert!(last.len() <= sizes.max);
+        assert!(rest
+            .iter()
+            .all(|chunk| chunk.len() >= sizes.min && chunk.len() <= sizes.max));
+
+        // Close to the average, if not exactly
+        let average = data.len() / chunks.len();
+        assert!(average > 512 && average < 2048, "{}", average);
+
+        // Uniform input has no cut points
+        let zeros = self::chunks(ChunkedStream::Bytes(vec![0; 10_000]), sizes);
+        assert!(zeros[..zeros.len() - 1]
+            .iter()
+            .all(|chunk| chunk.len() == sizes.max));
+    }
+
+    #[test]
+    fn boundaries_follow_content() {
+        let sizes = ChunkSizes::from_average(256).unwrap();
+        let data = xorshift(200_000, 2463534242);
+
+        // Read in pieces which do not line up with the chunks
+        let reader = ChunkedStream::File(Box::new(io::Cursor::new(data.clone())));
+        let original = chunks(reader, sizes);
+        assert_eq!(original, chunks(ChunkedStream::Bytes(data.clone()), sizes));
+
+        // Inserting bytes at the start only changes the first chunks
+        let mut edited = b"inserted".to_vec();
+        edited.extend(&data);
+        let edited = chunks(ChunkedStream::Bytes(edited), sizes);
+        let shared = edited
+            .iter()
+            .filter(|chunk| original.contains(chunk))
+            .count();
+        assert!(
+            shared >= original.len() - 3,
+            "{} of {}",
+            shared,
+            original.len()
+        );
+    }
+
+    #[test]
+    fn empty_input() {
+        let sizes = ChunkSizes::from_average(256).unwrap();
+        assert!(chunks(ChunkedStream::Bytes(vec![]), sizes).is_empty());
+    }
+}
@@ -1,41 +1,866 @@
+use std::fmt::Display;
+
 use lazy_static::lazy_static;
+use serde::Serialize;
+
+use hashy::algorithms::{
+    adler32::Adler32,
+    crc::{Crc, CrcParams, CRC_16_CCITT, CRC_32_ISCSI, CRC_32_ISO_HDLC, CRC_64_XZ},
+    fletcher::Fletcher,
+    fnv::{Fnv, FnvVariant, FnvWidth},
+    md2::Md2,
+    md4::Md4,
+    md5::Md5,
+    md6::Md6,
+    murmur3::{Murmur3, Murmur3Variant},
+    sha1::Sha1,
+    sha2::{Sha2, Sha2Variant},
+    sha3::{Sha3, Sha3Variant, Shake, ShakeVariant},
+    siphash::SipHash,
+    ssdeep::Ssdeep,
+    tlsh::Tlsh,
+    xxhash::{Xxh3, Xxh32, Xxh3Variant, Xxh64},
+    Algorithm,
+};
 
-pub enum Specification {
-    Single(&'static str),
-    Family {
-        name: &'static str,
-        members: Vec<&'static str>,
-    },
+/// Numeric parameter appended to an algorithm name with a dash (e.g. `md6-256`)
+#[derive(Debug, Serialize)]
+pub struct Parameter {
+    /// Placeholder shown in algorithm listings
+    pub placeholder: &'static str,
+    /// Human readable name used in error messages
+    pub description: &'static str,
+    pub min: usize,
+    pub max: usize,
+    pub multiple_of: usize,
 }
 
-lazy_static! {
-    pub static ref ALGORITHMS: Vec<Specification> = vec![
-        Specification::Family {
-            name: "MD",
-            members: vec!["md2", "md4", "md5", "md6-n"]
-        },
-        Specification::Single("sha1"),
-        Specification::Family {
-            name: "SHA2",
-            members: vec![
-                "sha-224 (sha2-224)",
-                "sha-256 (sha2, sha2-256)",
-                "sha-384 (sha2-384)",
-                "sha-512 (sha2-512)",
-                "sha-512-224 (sha2-512-224)",
-                "sha-512-256 (sha2-512-256)"
-            ]
-        },
-        Specification::Family {
-            name: "SHA3",
-            members: vec![
-                "sha3-224",
-                "sha3-256",
-                "sha3-384",
-                "sha3-512",
-                "shake128-n",
-                "shake256-n"
-            ]
+/// Seed or key appended to an algorithm name with a colon (e.g. `xxh64:42`)
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase", tag = "kind")]
+pub enum SeedParameter {
+    /// Integer in decimal or `0x`-prefixed hexadecimal, optional
+    Integer { bits: u32 },
+    /// Key in hexadecimal, required
+    Key { bytes: usize },
+}
+
+/// Seed or key given along with the algorithm name
+#[derive(Debug, PartialEq)]
+pub enum Seed {
+    Integer(u64),
+    Key(Vec<u8>),
+}
+
+/// Security status of an algorithm, used by tooling to enforce policies
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SecurityStatus {
+    /// Practical collision (or worse) attacks are known
+    Broken,
+    /// No practical attacks, but not standardized or superseded
+    Legacy,
+    Recommended,
+    /// Checksums meant to detect accidental corruption only
+    NonCryptographic,
+}
+
+/// Registry entry describing how an algorithm is named and constructed.
+///
+/// Names and aliases are matched in full, with the parameter (if any)
+/// following the name after a dash.
+#[derive(Serialize)]
+pub struct AlgorithmEntry {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    /// Name used in BSD-style tagged output, e.g. `SHA256 (file) = ...`
+    pub tag: &'static str,
+    pub family: Option<&'static str>,
+    /// Digest size in bits, None if given by the parameter
+    pub digest_bits: Option<usize>,
+    /// Block size (or rate for sponge constructions) in bits
+    pub block_bits: usize,
+    /// Extendable-output function
+    pub xof: bool,
+    pub keyed: bool,
+    /// Digest is a printable signature (such as ssdeep), printed as-is instead of encoded
+    pub textual: bool,
+    pub security: SecurityStatus,
+    pub parameter: Option<Parameter>,
+    pub seed: Option<SeedParameter>,
+    #[serde(skip)]
+    pub constructor: fn(Option<usize>, Option<&Seed>) -> Box<dyn Algorithm>,
+}
+
+impl AlgorithmEntry {
+    /// Canonical name followed by all aliases
+    pub fn names(&self) -> impl Iterator<Item = &'static str> {
+        std::iter::once(self.name).chain(self.aliases.iter().copied())
+    }
+
+    /// Formats a name of this entry along with its parameter and seed placeholders, if any
+    pub fn display_name(&self, name: &str) -> String {
+        let name = match &self.parameter {
+            Some(parameter) => format!("{}-{}", name, parameter.placeholder),
+            None => name.to_string(),
+        };
+
+        match &self.seed {
+            Some(seed @ SeedParameter::Integer { .. }) => {
+                format!("{}[:{}]", name, seed.placeholder())
+            }
+            Some(seed @ SeedParameter::Key { .. }) => format!("{}:{}", name, seed.placeholder()),
+            None => name,
+        }
+    }
+}
+
+impl SeedParameter {
+    /// Placeholder shown in algorithm listings and error messages
+    pub fn placeholder(&self) -> &'static str {
+        match self {
+            SeedParameter::Integer { .. } => "seed",
+            SeedParameter::Key { .. } => "key",
+        }
+    }
+
+    /// Parses the seed, None if it is malformed or out of range
+    pub fn parse(&self, input: &str) -> Option<Seed> {
+        match self {
+            SeedParameter::Integer { bits } => {
+                let value = match input.strip_prefix("0x") {
+                    Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+                    None => input.parse().ok()?,
+                };
+
+                (*bits >= 64 || value >> bits == 0).then_some(Seed::Integer(value))
+            }
+            SeedParameter::Key { bytes } => hex::decode(input)
+                .ok()
+                .filter(|key| key.len() == *bytes)
+                .map(Seed::Key),
+        }
+    }
+}
+
+impl Display for SeedParameter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SeedParameter::Integer { bits } => write!(f, "an integer of at most {} bits", bits),
+            SeedParameter::Key { bytes } => write!(f, "{} bytes in hexadecimal", bytes),
+        }
+    }
+}
+
+impl Display for Seed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Seed::Integer(value) => write!(f, "{}", value),
+            Seed::Key(key) => write!(f, "{}", hex::encode(key)),
         }
+    }
+}
+
+impl Parameter {
+    pub fn accepts(&self, value: usize) -> bool {
+        (self.min..=self.max).contains(&value) && value.is_multiple_of(self.multiple_of)
+    }
+}
+
+const OUTPUT_LENGTH_MD6: Parameter = Parameter {
+    placeholder: "n",
+    description: "output length in bits",
+    min: 8,
+    max: 512,
+    multiple_of: 8,
+};
+
+const OUTPUT_LENGTH_SHAKE: Parameter = Parameter {
+    placeholder: "n",
+    description: "output length in bits",
+    min: 8,
+    max: 65536,
+    multiple_of: 8,
+};
+
+fn sha2(variant: Sha2Variant) -> Box<dyn Algorithm> {
+    Box::new(Sha2::new(variant))
+}
+
+fn crc(params: CrcParams) -> Box<dyn Algorithm> {
+    Box::new(Crc::new(params))
+}
+
+/// Integer seed, 0 if none was given
+fn integer_seed(seed: Option<&Seed>) -> u64 {
+    match seed {
+        Some(Seed::Integer(value)) => *value,
+        _ => 0,
+    }
+}
+
+/// FNV with the seed, if any, as the offset basis
+fn fnv(variant: FnvVariant, width: FnvWidth, seed: Option<&Seed>) -> Box<dyn Algorithm> {
+    let offset_basis = match seed {
+        Some(Seed::Integer(value)) => Some(*value),
+        _ => None,
+    };
+    Box::new(Fnv::new(variant, width, offset_basis))
+}
+
+fn siphash(seed: Option<&Seed>) -> Box<dyn Algorithm> {
+    let key = match seed {
+        Some(Seed::Key(key)) => key.as_slice().try_into().ok(),
+        _ => None,
+    };
+    Box::new(SipHash::new(key.expect("key is validated by the registry")))
+}
+
+fn sha3(variant: Sha3Variant) -> Box<dyn Algorithm> {
+    Box::new(Sha3::new(variant))
+}
+
+fn shake(variant: ShakeVariant, output_length: Option<usize>) -> Box<dyn Algorithm> {
+    let output_length = output_length.unwrap_or_default();
+    Box::new(
+        Shake::new(variant, output_length).expect("output length is validated by the registry"),
+    )
+}
+
+lazy_static! {
+    pub static ref ALGORITHMS: Vec<AlgorithmEntry> = vec![
+        AlgorithmEntry {
+            name: "md2",
+            aliases: &[],
+            tag: "MD2",
+            family: Some("MD"),
+            digest_bits: Some(128),
+            block_bits: 128,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::Broken,
+            parameter: None,
+            seed: None,
+            constructor: |_, _| Box::new(Md2),
+        },
+        AlgorithmEntry {
+            name: "md4",
+            aliases: &[],
+            tag: "MD4",
+            family: Some("MD"),
+            digest_bits: Some(128),
+            block_bits: 512,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::Broken,
+            parameter: None,
+            seed: None,
+            constructor: |_, _| Box::new(Md4),
+        },
+        AlgorithmEntry {
+            name: "md5",
+            aliases: &[],
+            tag: "MD5",
+            family: Some("MD"),
+            digest_bits: Some(128),
+            block_bits: 512,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::Broken,
+            parameter: None,
+            seed: None,
+            constructor: |_, _| Box::new(Md5),
+        },
+        AlgorithmEntry {
+            name: "md6",
+            aliases: &[],
+            tag: "MD6",
+            family: Some("MD"),
+            digest_bits: None,
+            block_bits: 4096,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::Legacy,
+            parameter: Some(OUTPUT_LENGTH_MD6),
+            seed: None,
+            constructor: |output_length, _| {
+                Box::new(Md6::new(output_length.unwrap_or_default(), None))
+            },
+        },
+        AlgorithmEntry {
+            name: "sha1",
+            aliases: &["sha-1"],
+            tag: "SHA1",
+            family: None,
+            digest_bits: Some(160),
+            block_bits: 512,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::Broken,
+            parameter: None,
+            seed: None,
+            constructor: |_, _| Box::new(Sha1),
+        },
+        AlgorithmEntry {
+            name: "sha-224",
+            aliases: &["sha2-224", "sha224"],
+            tag: "SHA224",
+            family: Some("SHA2"),
+            digest_bits: Some(224),
+            block_bits: 512,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::Recommended,
+            parameter: None,
+            seed: None,
+            constructor: |_, _| sha2(Sha2Variant::_224),
+        },
+        AlgorithmEntry {
+            name: "sha-256",
+            aliases: &["sha2", "sha2-256", "sha256"],
+            tag: "SHA256",
+            family: Some("SHA2"),
+            digest_bits: Some(256),
+            block_bits: 512,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::Recommended,
+            parameter: None,
+            seed: None,
+            constructor: |_, _| sha2(Sha2Variant::_256),
+        },
+        AlgorithmEntry {
+            name: "sha-384",
+            aliases: &["sha2-384", "sha384"],
+            tag: "SHA384",
+            family: Some("SHA2"),
+            digest_bits: Some(384),
+            block_bits: 1024,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::Recommended,
+            parameter: None,
+            seed: None,
+            constructor: |_, _| sha2(Sha2Variant::_384),
+        },
+        AlgorithmEntry {
+            name: "sha-512",
+            aliases: &["sha2-512", "sha512"],
+            tag: "SHA512",
+            family: Some("SHA2"),
+            digest_bits: Some(512),
+            block_bits: 1024,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::Recommended,
+            parameter: None,
+            seed: None,
+            constructor: |_, _| sha2(Sha2Variant::_512),
+        },
+        AlgorithmEntry {
+            name: "sha-512-224",
+            aliases: &["sha2-512-224", "sha512-224"],
+            tag: "SHA512/224",
+            family: Some("SHA2"),
+            digest_bits: Some(224),
+            block_bits: 1024,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::Recommended,
+            parameter: None,
+            seed: None,
+            constructor: |_, _| sha2(Sha2Variant::_512_224),
+        },
+        AlgorithmEntry {
+            name: "sha-512-256",
+            aliases: &["sha2-512-256", "sha512-256"],
+            tag: "SHA512/256",
+            family: Some("SHA2"),
+            digest_bits: Some(256),
+            block_bits: 1024,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::Recommended,
+            parameter: None,
+            seed: None,
+            constructor: |_, _| sha2(Sha2Variant::_512_256),
+        },
+        AlgorithmEntry {
+            name: "sha3-224",
+            aliases: &[],
+            tag: "SHA3-224",
+            family: Some("SHA3"),
+            digest_bits: Some(224),
+            block_bits: 1152,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::Recommended,
+            parameter: None,
+            seed: None,
+            constructor: |_, _| sha3(Sha3Variant::_224),
+        },
+        AlgorithmEntry {
+            name: "sha3-256",
+            aliases: &[],
+            tag: "SHA3-256",
+            family: Some("SHA3"),
+            digest_bits: Some(256),
+            block_bits: 1088,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::Recommended,
+            parameter: None,
+            seed: None,
+            constructor: |_, _| sha3(Sha3Variant::_256),
+        },
+        AlgorithmEntry {
+            name: "sha3-384",
+            aliases: &[],
+            tag: "SHA3-384",
+            family: Some("SHA3"),
+            digest_bits: Some(384),
+            block_bits: 832,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::Recommended,
+            parameter: None,
+            seed: None,
+            constructor: |_, _| sha3(Sha3Variant::_384),
+        },
+        AlgorithmEntry {
+            name: "sha3-512",
+            aliases: &[],
+            tag: "SHA3-512",
+            family: Some("SHA3"),
+            digest_bits: Some(512),
+            block_bits: 576,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::Recommended,
+            parameter: None,
+            seed: None,
+            constructor: |_, _| sha3(Sha3Variant::_512),
+        },
+        AlgorithmEntry {
+            name: "shake128",
+            aliases: &[],
+            tag: "SHAKE128",
+            family: Some("SHA3"),
+            digest_bits: None,
+            block_bits: 1344,
+            xof: true,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::Recommended,
+            parameter: Some(OUTPUT_LENGTH_SHAKE),
+            seed: None,
+            constructor: |output_length, _| shake(ShakeVariant::_128, output_length),
+        },
+        AlgorithmEntry {
+            name: "shake256",
+            aliases: &[],
+            tag: "SHAKE256",
+            family: Some("SHA3"),
+            digest_bits: None,
+            block_bits: 1088,
+            xof: true,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::Recommended,
+            parameter: Some(OUTPUT_LENGTH_SHAKE),
+            seed: None,
+            constructor: |output_length, _| shake(ShakeVariant::_256, output_length),
+        },
+        AlgorithmEntry {
+            name: "crc16-ccitt",
+            aliases: &["crc-16-ccitt", "crc16-kermit"],
+            tag: "CRC16-CCITT",
+            family: Some("CRC"),
+            digest_bits: Some(16),
+            block_bits: 8,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::NonCryptographic,
+            parameter: None,
+            seed: None,
+            constructor: |_, _| crc(CRC_16_CCITT),
+        },
+        AlgorithmEntry {
+            name: "crc32",
+            aliases: &["crc-32", "crc32-iso-hdlc"],
+            tag: "CRC32",
+            family: Some("CRC"),
+            digest_bits: Some(32),
+            block_bits: 8,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::NonCryptographic,
+            parameter: None,
+            seed: None,
+            constructor: |_, _| crc(CRC_32_ISO_HDLC),
+        },
+        AlgorithmEntry {
+            name: "crc32c",
+            aliases: &["crc-32c", "crc32-iscsi"],
+            tag: "CRC32C",
+            family: Some("CRC"),
+            digest_bits: Some(32),
+            block_bits: 8,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::NonCryptographic,
+            parameter: None,
+            seed: None,
+            constructor: |_, _| crc(CRC_32_ISCSI),
+        },
+        AlgorithmEntry {
+            name: "crc64-xz",
+            aliases: &["crc-64-xz", "crc64"],
+            tag: "CRC64-XZ",
+            family: Some("CRC"),
+            digest_bits: Some(64),
+            block_bits: 8,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::NonCryptographic,
+            parameter: None,
+            seed: None,
+            constructor: |_, _| crc(CRC_64_XZ),
+        },
+        AlgorithmEntry {
+            name: "adler32",
+            aliases: &["adler-32"],
+            tag: "ADLER32",
+            family: Some("Checksum"),
+            digest_bits: Some(32),
+            block_bits: 8,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::NonCryptographic,
+            parameter: None,
+            seed: None,
+            constructor: |_, _| Box::new(Adler32),
+        },
+        AlgorithmEntry {
+            name: "fletcher16",
+            aliases: &["fletcher-16"],
+            tag: "FLETCHER16",
+            family: Some("Checksum"),
+            digest_bits: Some(16),
+            block_bits: 8,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::NonCryptographic,
+            parameter: None,
+            seed: None,
+            constructor: |_, _| Box::new(Fletcher::_16),
+        },
+        AlgorithmEntry {
+            name: "fletcher32",
+            aliases: &["fletcher-32"],
+            tag: "FLETCHER32",
+            family: Some("Checksum"),
+            digest_bits: Some(32),
+            block_bits: 16,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::NonCryptographic,
+            parameter: None,
+            seed: None,
+            constructor: |_, _| Box::new(Fletcher::_32),
+        },
+        AlgorithmEntry {
+            name: "xxh32",
+            aliases: &[],
+            tag: "XXH32",
+            family: Some("xxHash"),
+            digest_bits: Some(32),
+            block_bits: 128,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::NonCryptographic,
+            parameter: None,
+            seed: Some(SeedParameter::Integer { bits: 32 }),
+            constructor: |_, seed| Box::new(Xxh32::new(integer_seed(seed) as u32)),
+        },
+        AlgorithmEntry {
+            name: "xxh64",
+            aliases: &[],
+            tag: "XXH64",
+            family: Some("xxHash"),
+            digest_bits: Some(64),
+            block_bits: 256,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::NonCryptographic,
+            parameter: None,
+            seed: Some(SeedParameter::Integer { bits: 64 }),
+            constructor: |_, seed| Box::new(Xxh64::new(integer_seed(seed))),
+        },
+        AlgorithmEntry {
+            name: "xxh3-64",
+            aliases: &["xxh3"],
+            tag: "XXH3",
+            family: Some("xxHash"),
+            digest_bits: Some(64),
+            block_bits: 512,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::NonCryptographic,
+            parameter: None,
+            seed: Some(SeedParameter::Integer { bits: 64 }),
+            constructor: |_, seed| Box::new(Xxh3::new(Xxh3Variant::_64, integer_seed(seed))),
+        },
+        AlgorithmEntry {
+            name: "xxh3-128",
+            aliases: &["xxh128"],
+            tag: "XXH128",
+            family: Some("xxHash"),
+            digest_bits: Some(128),
+            block_bits: 512,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::NonCryptographic,
+            parameter: None,
+            seed: Some(SeedParameter::Integer { bits: 64 }),
+            constructor: |_, seed| Box::new(Xxh3::new(Xxh3Variant::_128, integer_seed(seed))),
+        },
+        AlgorithmEntry {
+            name: "murmur3-x86-32",
+            aliases: &["murmur3", "murmur3-32"],
+            tag: "MURMUR3-32",
+            family: Some("MurmurHash"),
+            digest_bits: Some(32),
+            block_bits: 32,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::NonCryptographic,
+            parameter: None,
+            seed: Some(SeedParameter::Integer { bits: 32 }),
+            constructor: |_, seed| Box::new(Murmur3::new(
+                Murmur3Variant::X86_32,
+                integer_seed(seed) as u32
+            )),
+        },
+        AlgorithmEntry {
+            name: "murmur3-x64-128",
+            aliases: &["murmur3-128"],
+            tag: "MURMUR3-128",
+            family: Some("MurmurHash"),
+            digest_bits: Some(128),
+            block_bits: 128,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::NonCryptographic,
+            parameter: None,
+            seed: Some(SeedParameter::Integer { bits: 32 }),
+            constructor: |_, seed| Box::new(Murmur3::new(
+                Murmur3Variant::X64_128,
+                integer_seed(seed) as u32
+            )),
+        },
+        AlgorithmEntry {
+            name: "fnv1-32",
+            aliases: &[],
+            tag: "FNV1-32",
+            family: Some("FNV"),
+            digest_bits: Some(32),
+            block_bits: 8,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::NonCryptographic,
+            parameter: None,
+            seed: Some(SeedParameter::Integer { bits: 32 }),
+            constructor: |_, seed| fnv(FnvVariant::Fnv1, FnvWidth::_32, seed),
+        },
+        AlgorithmEntry {
+            name: "fnv1a-32",
+            aliases: &["fnv1a"],
+            tag: "FNV1A-32",
+            family: Some("FNV"),
+            digest_bits: Some(32),
+            block_bits: 8,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::NonCryptographic,
+            parameter: None,
+            seed: Some(SeedParameter::Integer { bits: 32 }),
+            constructor: |_, seed| fnv(FnvVariant::Fnv1a, FnvWidth::_32, seed),
+        },
+        AlgorithmEntry {
+            name: "fnv1-64",
+            aliases: &[],
+            tag: "FNV1-64",
+            family: Some("FNV"),
+            digest_bits: Some(64),
+            block_bits: 8,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::NonCryptographic,
+            parameter: None,
+            seed: Some(SeedParameter::Integer { bits: 64 }),
+            constructor: |_, seed| fnv(FnvVariant::Fnv1, FnvWidth::_64, seed),
+        },
+        AlgorithmEntry {
+            name: "fnv1a-64",
+            aliases: &[],
+            tag: "FNV1A-64",
+            family: Some("FNV"),
+            digest_bits: Some(64),
+            block_bits: 8,
+            xof: false,
+            keyed: false,
+            textual: false,
+            security: SecurityStatus::NonCryptographic,
+            parameter: None,
+            seed: Some(SeedParameter::Integer { bits: 64 }),
+            constructor: |_, seed| fnv(FnvVariant::Fnv1a, FnvWidth::_64, seed),
+        },
+        AlgorithmEntry {
+            name: "siphash-2-4",
+            aliases: &["siphash"],
+            tag: "SIPHASH-2-4",
+            family: Some("SipHash"),
+            digest_bits: Some(64),
+            block_bits: 64,
+            xof: false,
+            keyed: true,
+            textual: false,
+            security: SecurityStatus::Recommended,
+            parameter: None,
+            seed: Some(SeedParameter::Key { bytes: 16 }),
+            constructor: |_, seed| siphash(seed),
+        },
+        AlgorithmEntry {
+            name: "ssdeep",
+            aliases: &["ctph"],
+            tag: "SSDEEP",
+            family: Some("Fuzzy"),
+            digest_bits: None,
+            block_bits: 8,
+            xof: false,
+            keyed: false,
+            textual: true,
+            security: SecurityStatus::NonCryptographic,
+            parameter: None,
+            seed: None,
+            constructor: |_, _| Box::new(Ssdeep),
+        },
+        AlgorithmEntry {
+            name: "tlsh",
+            aliases: &[],
+            tag: "TLSH",
+            family: Some("Fuzzy"),
+            digest_bits: None,
+            block_bits: 8,
+            xof: false,
+            keyed: false,
+            textual: true,
+            security: SecurityStatus::NonCryptographic,
+            parameter: None,
+            seed: None,
+            constructor: |_, _| Box::new(Tlsh),
+        },
     ];
 }
+
+#[cfg(test)]
+mod test {
+    use hashy::chunked_stream::ChunkedStream;
+
+    use super::*;
+
+    #[test]
+    fn digest_sizes_match_metadata() {
+        for entry in ALGORITHMS.iter() {
+            let (parameter, expected_bits) = match (&entry.parameter, entry.digest_bits) {
+                (Some(parameter), None) => (Some(parameter.max), parameter.max),
+                (None, Some(digest_bits)) => (None, digest_bits),
+                // Signatures vary in length
+                (None, None) if entry.textual => continue,
+                _ => panic!("{} needs either a digest size or a parameter", entry.name),
+            };
+
+            // Keys are required, seeds are not
+            let seed = match entry.seed {
+                Some(SeedParameter::Key { bytes }) => Some(Seed::Key(vec![0; bytes])),
+                _ => None,
+            };
+
+            let digest = (entry.constructor)(parameter, seed.as_ref())
+                .digest(ChunkedStream::Bytes(vec![]))
+                .unwrap();
+            assert_eq!(
+                digest.len() * 8,
+                expected_bits,
+                "digest size of {}",
+                entry.name
+            );
+        }
+    }
+
+    #[test]
+    fn parses_seeds() {
+        let integer = SeedParameter::Integer { bits: 32 };
+        assert_eq!(integer.parse("42"), Some(Seed::Integer(42)));
+        assert_eq!(integer.parse("0xffffffff"), Some(Seed::Integer(0xffffffff)));
+        assert_eq!(integer.parse("0x100000000"), None);
+        assert_eq!(integer.parse("-1"), None);
+
+        let key = SeedParameter::Key { bytes: 2 };
+        assert_eq!(key.parse("00ff"), Some(Seed::Key(vec![0, 255])));
+        assert_eq!(key.parse("00"), None);
+        assert_eq!(key.parse("zz00"), None);
+    }
+
+    #[test]
+    fn names_are_unique() {
+        let mut names: Vec<&str> = ALGORITHMS.iter().flat_map(|entry| entry.names()).collect();
+        let count = names.len();
+        names.sort();
+        names.dedup();
+
+        assert_eq!(names.len(), count);
+    }
+
+    #[test]
+    fn tags_are_unique() {
+        let mut tags: Vec<&str> = ALGORITHMS.iter().map(|entry| entry.tag).collect();
+        let count = tags.len();
+        tags.sort();
+        tags.dedup();
+
+        assert_eq!(tags.len(), count);
+    }
+}
@@ -0,0 +1,363 @@
+use std::{
+    collections::HashMap,
+    fs,
+    hint::black_box,
+    io::{self, Write},
+    path::PathBuf,
+    time::Instant,
+};
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+use hashy::{algorithms::Algorithm, chunked_stream::ChunkedStream};
+
+use super::{formats::OutputFormat, parsers::AlgorithmSpec};
+
+const BASELINE_VERSION: u32 = 1;
+
+/// Minimum number of bytes digested per sample, so that small buffers are
+/// digested many times over instead of timing a single call
+const SAMPLE_BYTES: usize = 1 << 20;
+
+/// Throughput drop (in percent) below which a benchmark is never reported as
+/// a regression, however stable it is
+const REGRESSION_PERCENT: f64 = 10.0;
+
+pub struct BenchOptions {
+    /// Sizes of the buffers digested, in bytes
+    pub sizes: Vec<usize>,
+    /// Timed samples of every algorithm and size, after a warm-up sample
+    pub repetitions: usize,
+    pub output_format: OutputFormat,
+    /// Baseline results to compare against
+    pub baseline: Option<PathBuf>,
+    pub save_baseline: Option<PathBuf>,
+}
+
+/// Throughput of an algorithm digesting buffers of one size
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchResult {
+    pub algorithm: String,
+    pub size: usize,
+    pub repetitions: usize,
+    /// Mean throughput in MB/s (10^6 bytes per second)
+    pub megabytes_per_second: f64,
+    /// Standard deviation of the throughput across samples, in MB/s
+    pub stddev: f64,
+    /// Estimated from the timestamp counter, None where there is none
+    pub cycles_per_byte: Option<f64>,
+    /// Change of the throughput against the baseline, in percent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub change_percent: Option<f64>,
+}
+
+impl BenchResult {
+    /// Standard deviation relative to the mean, in percent
+    fn variation_percent(&self) -> f64 {
+        self.stddev / self.megabytes_per_second * 100.0
+    }
+
+    /// Whether the throughput dropped by more than the noise of both runs
+    fn regressed(&self, baseline: &BenchResult) -> bool {
+        let noise = 2.0 * (self.variation_percent() + baseline.variation_percent());
+        self.change_percent
+            .is_some_and(|change| -change > REGRESSION_PERCENT.max(noise))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Baseline {
+    version: u32,
+    results: Vec<BenchResult>,
+}
+
+/// Cycles of the timestamp counter, which ticks at a constant rate close to the
+/// nominal clock rate of the CPU
+#[cfg(target_arch = "x86_64")]
+fn cycles() -> Option<u64> {
+    // SAFETY: rdtsc is available on every x86_64 CPU
+    Some(unsafe { core::arch::x86_64::_rdtsc() })
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn cycles() -> Option<u64> {
+    None
+}
+
+/// Pseudo-random bytes (xorshift64), the same for every run
+fn test_data(size: usize) -> Vec<u8> {
+    let mut state = 0x9e3779b97f4a7c15u64;
+    (0..size)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+fn mean_and_stddev(samples: &[f64]) -> (f64, f64) {
+    let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+    let variance = match samples.len() {
+        0 | 1 => 0.0,
+        count => {
+            samples
+                .iter()
+                .map(|sample| (sample - mean).powi(2))
+                .sum::<f64>()
+                / (count - 1) as f64
+        }
+    };
+
+    (mean, variance.sqrt())
+}
+
+/// Digests a buffer of `size` bytes repeatedly, with one untimed warm-up sample
+fn bench_algorithm(
+    algorithm: &dyn Algorithm,
+    name: &str,
+    size: usize,
+    repetitions: usize,
+) -> io::Result<BenchResult> {
+    let data = test_data(size);
+    let iterations = (SAMPLE_BYTES / size.max(1)).max(1);
+    let sample_bytes = (iterations * size) as f64;
+
+    let mut throughputs = vec![];
+    let mut total_cycles = Some(0u64);
+    for sample in 0..=repetitions {
+        // Streams are created beforehand so that copying the data is not timed
+        let streams: Vec<_> = (0..iterations)
+            .map(|_| ChunkedStream::Bytes(data.clone()))
+            .collect();
+
+        let start_cycles = cycles();
+        let start_time = Instant::now();
+        for stream in streams {
+            black_box(algorithm.digest(black_box(stream))?);
+        }
+        let elapsed = start_time.elapsed().as_secs_f64();
+        let end_cycles = cycles();
+
+        if sample > 0 {
+            throughputs.push(sample_bytes / elapsed / 1e6);
+            total_cycles = match (total_cycles, start_cycles, end_cycles) {
+                (Some(total), Some(start), Some(end)) => Some(total + end.wrapping_sub(start)),
+                _ => None,
+            };
+        }
+    }
+
+    let (megabytes_per_second, stddev) = mean_and_stddev(&throughputs);
+    Ok(BenchResult {
+        algorithm: name.to_string(),
+        size,
+        repetitions,
+        megabytes_per_second,
+        stddev,
+        cycles_per_byte: total_cycles
+            .map(|total| total as f64 / (sample_bytes * repetitions as f64)),
+        change_percent: None,
+    })
+}
+
+/// Formats a buffer size in bytes, or in K or M if it is a multiple of them
+fn format_size(size: usize) -> String {
+    match size {
+        0 => "0".to_string(),
+        size if size % (1 << 20) == 0 => format!("{}M", size >> 20),
+        size if size % (1 << 10) == 0 => format!("{}K", size >> 10),
+        size => size.to_string(),
+    }
+}
+
+fn write_header(out: &mut impl Write, compare: bool) -> io::Result<()> {
+    write!(
+        out,
+        "{:<16} {:>6} {:>10} {:>7} {:>9}",
+        "algorithm", "size", "MB/s", "+/-", "cycles/B"
+    )?;
+    match compare {
+        true => writeln!(out, " {:>8}", "change"),
+        false => writeln!(out),
+    }
+}
+
+fn write_row(out: &mut impl Write, result: &BenchResult, regressed: bool) -> io::Result<()> {
+    write!(
+        out,
+        "{:<16} {:>6} {:>10.1} {:>6.1}% {:>9}",
+        result.algorithm,
+        format_size(result.size),
+        result.megabytes_per_second,
+        result.variation_percent(),
+        result
+            .cycles_per_byte
+            .map_or("-".to_string(), |cycles| format!("{:.2}", cycles))
+    )?;
+    match (result.change_percent, regressed) {
+        (Some(change), true) => writeln!(out, " {:>+7.1}% REGRESSED", change),
+        (Some(change), false) => writeln!(out, " {:>+7.1}%", change),
+        (None, _) => writeln!(out),
+    }?;
+    out.flush()
+}
+
+fn read_baseline(path: &PathBuf) -> anyhow::Result<HashMap<(String, usize), BenchResult>> {
+    let content = fs::read_to_string(path)
+        .map_err(|err| anyhow!("Invalid path! {}: {}", path.display(), err))?;
+    let baseline: Baseline = serde_json::from_str(&content)
+        .map_err(|err| anyhow!("Invalid baseline {}: {}", path.display(), err))?;
+    if baseline.version != BASELINE_VERSION {
+        return Err(anyhow!(
+            "Unsupported baseline version {} in {}",
+            baseline.version,
+            path.display()
+        ));
+    }
+
+    Ok(baseline
+        .results
+        .into_iter()
+        .map(|result| ((result.algorithm.clone(), result.size), result))
+        .collect())
+}
+
+/// Benchmarks every algorithm over buffers of every size, one after the other so
+/// that they do not compete for the CPU. Results are compared to the baseline if
+/// any, failing if any throughput dropped by more than the noise of the runs.
+pub fn bench(algorithms: &[AlgorithmSpec], options: &BenchOptions) -> anyhow::Result<()> {
+    let baseline = options.baseline.as_ref().map(read_baseline).transpose()?;
+
+    let mut out = io::stdout().lock();
+    if options.output_format == OutputFormat::Text {
+        write_header(&mut out, baseline.is_some())?;
+    }
+
+    let mut results = vec![];
+    let (mut failed, mut regressed) = (0, 0);
+    for spec in algorithms {
+        let (name, algorithm) = (spec.name(), spec.build());
+        for &size in &options.sizes {
+            let mut result =
+                match bench_algorithm(algorithm.as_ref(), &name, size, options.repetitions) {
+                    Ok(result) => result,
+                    Err(err) => {
+                        eprintln!("{} ({}): {}", name, format_size(size), err);
+                        failed += 1;
+                        continue;
+                    }
+                };
+
+            let previous = baseline
+                .as_ref()
+                .and_then(|baseline| baseline.get(&(name.clone(), size)));
+            result.change_percent = previous.map(|previous| {
+                (result.megabytes_per_second / previous.megabytes_per_second - 1.0) * 100.0
+            });
+            let is_regression = previous.is_some_and(|previous| result.regressed(previous));
+            regressed += is_regression as usize;
+
+            match options.output_format {
+                OutputFormat::Text => write_row(&mut out, &result, is_regression)?,
+                OutputFormat::Ndjson => writeln!(out, "{}", serde_json::to_string(&result)?)?,
+                _ => {}
+            }
+            results.push(result);
+        }
+    }
+
+    if options.output_format == OutputFormat::Json {
+        writeln!(out, "{}", serde_json::to_string_pretty(&results)?)?;
+    }
+
+    if let Some(path) = &options.save_baseline {
+        let baseline = Baseline {
+            version: BASELINE_VERSION,
+            results: results
+                .into_iter()
+                .map(|result| BenchResult {
+                    change_percent: None,
+                    ..result
+                })
+                .collect(),
+        };
+        fs::write(path, serde_json::to_string_pretty(&baseline)? + "\n")
+            .map_err(|err| anyhow!("Failed to save the baseline {}: {}", path.display(), err))?;
+    }
+
+    if failed > 0 {
+        eprintln!("WARNING: {} benchmark(s) could not run", failed);
+        return Err(anyhow!("Failed to run {} benchmark(s)", failed));
+    }
+    if regressed > 0 {
+        eprintln!("WARNING: {} benchmark(s) regressed", regressed);
+        return Err(anyhow!("Performance regressed against the baseline"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn result(megabytes_per_second: f64, stddev: f64) -> BenchResult {
+        BenchResult {
+            algorithm: "md5".to_string(),
+            size: 1024,
+            repetitions: 10,
+            megabytes_per_second,
+            stddev,
+            cycles_per_byte: None,
+            change_percent: None,
+        }
+    }
+
+    #[test]
+    fn computes_statistics() {
+        let (mean, stddev) = mean_and_stddev(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]);
+        assert_eq!(mean, 5.0);
+        assert!((stddev - 2.138).abs() < 0.001);
+        assert_eq!(mean_and_stddev(&[3.0]), (3.0, 0.0));
+
+        assert_eq!(format_size(64), "64");
+        assert_eq!(format_size(4096), "4K");
+        assert_eq!(format_size(3 << 20), "3M");
+        assert_eq!(format_size(1000), "1000");
+    }
+
+    #[test]
+    fn detects_regressions() {
+        let baseline = result(100.0, 1.0);
+
+        let mut slower = result(85.0, 1.0);
+        slower.change_percent = Some(-15.0);
+        assert!(slower.regressed(&baseline));
+
+        // Within the regression threshold
+        let mut similar = result(95.0, 1.0);
+        similar.change_percent = Some(-5.0);
+        assert!(!similar.regressed(&baseline));
+
+        // Within the noise of the runs
+        let mut noisy = result(85.0, 8.5);
+        noisy.change_percent = Some(-15.0);
+        assert!(!noisy.regressed(&baseline));
+    }
+
+    #[test]
+    fn benchmarks_algorithms() {
+        use hashy::algorithms::md5::Md5;
+
+        let result = bench_algorithm(&Md5, "md5", 4096, 2).unwrap();
+        assert_eq!(result.size, 4096);
+        assert!(result.megabytes_per_second > 0.0);
+        assert_eq!(
+            result.cycles_per_byte.is_some(),
+            cfg!(target_arch = "x86_64")
+        );
+    }
+}
@@ -0,0 +1,248 @@
+// Reference: https://www.bittorrent.org/beps/bep_0003.html#bencoding
+
+use std::{collections::BTreeMap, fmt::Display};
+
+use nom::{
+    branch::alt,
+    character::complete::{char, digit1},
+    combinator::{all_consuming, consumed, map, map_res, opt, recognize},
+    multi::{length_data, many0},
+    sequence::{delimited, pair, terminated},
+    IResult, Parser,
+};
+
+/// Deepest nesting of lists and dicts accepted, far more than torrents need
+const MAX_DEPTH: usize = 64;
+
+/// Bencoded value, borrowing its strings from the input
+#[derive(Debug, PartialEq)]
+pub enum Value<'a> {
+    Integer(i64),
+    Bytes(&'a [u8]),
+    List(Vec<Value<'a>>),
+    Dict {
+        entries: BTreeMap<&'a [u8], Value<'a>>,
+        /// Encoded dict as it appears in the input, which infohashes are computed over
+        raw: &'a [u8],
+    },
+}
+
+#[derive(Debug, PartialEq)]
+pub struct InvalidBencode;
+
+impl Display for InvalidBencode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid bencode")
+    }
+}
+
+impl<'a> Value<'a> {
+    /// Looks up a key if the value is a dict
+    pub fn get(&self, key: &str) -> Option<&Value<'a>> {
+        match self {
+            Value::Dict { entries, .. } => entries.get(key.as_bytes()),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            Value::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match self {
+            Value::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&'a str> {
+        self.as_bytes()
+            .and_then(|bytes| std::str::from_utf8(bytes).ok())
+    }
+
+    pub fn as_list(&self) -> Option<&[Value<'a>]> {
+        match self {
+            Value::List(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_dict(&self) -> Option<&BTreeMap<&'a [u8], Value<'a>>> {
+        match self {
+            Value::Dict { entries, .. } => Some(entries),
+            _ => None,
+        }
+    }
+}
+
+/// Decimal number without leading zeros (other than 0 itself)
+fn canonical_digits(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    let (rest, digits) = digit1(input)?;
+    if digits.len() > 1 && digits[0] == b'0' {
+        return Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Digit,
+        )));
+    }
+
+    Ok((rest, digits))
+}
+
+/// `i<number>e`, where -0 is not allowed
+fn integer(input: &[u8]) -> IResult<&[u8], i64> {
+    map_res(
+        delimited(
+            char('i'),
+            recognize(pair(opt(char('-')), canonical_digits)),
+            char('e'),
+        ),
+        |number: &[u8]| match number {
+            b"-0" => Err(()),
+            _ => std::str::from_utf8(number)
+                .map_err(|_| ())?
+                .parse()
+                .map_err(|_| ()),
+        },
+    )
+    .parse(input)
+}
+
+/// `<length>:<bytes>`
+fn bytes(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    let length = map_res(canonical_digits, |digits: &[u8]| {
+        std::str::from_utf8(digits).unwrap().parse::<usize>()
+    });
+
+    length_data(terminated(length, char(':'))).parse(input)
+}
+
+/// Fails without trying other parsers once lists and dicts are nested deeper than
+/// `MAX_DEPTH`, which would otherwise overflow the stack
+fn check_depth(input: &[u8], depth: usize) -> IResult<&[u8], ()> {
+    match depth > MAX_DEPTH {
+        true => Err(nom::Err::Failure(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::TooLarge,
+        ))),
+        false => Ok((input, ())),
+    }
+}
+
+/// `l<value>...e`, `depth` being the nesting level of the list
+fn list(input: &[u8], depth: usize) -> IResult<&[u8], Vec<Value<'_>>> {
+    let (input, _) = char('l').parse(input)?;
+    let (input, _) = check_depth(input, depth)?;
+    terminated(many0(|input| value(input, depth)), char('e')).parse(input)
+}
+
+/// `d<key><value>...e`, keys are not required to be sorted since the raw dict is kept
+fn dict(input: &[u8], depth: usize) -> IResult<&[u8], Value<'_>> {
+    let entries = |input| {
+        let (input, _) = char('d').parse(input)?;
+        let (input, _) = check_depth(input, depth)?;
+        terminated(many0(pair(bytes, |input| value(input, depth))), char('e')).parse(input)
+    };
+
+    map(consumed(entries), |(raw, entries)| Value::Dict {
+        entries: entries.into_iter().collect(),
+        raw,
+    })
+    .parse(input)
+}
+
+/// Any value inside `depth` lists and dicts
+fn value(input: &[u8], depth: usize) -> IResult<&[u8], Value<'_>> {
+    alt((
+        map(integer, Value::Integer),
+        map(bytes, Value::Bytes),
+        map(|input| list(input, depth + 1), Value::List),
+        |input| dict(input, depth + 1),
+    ))
+    .parse(input)
+}
+
+/// Parses a single bencoded value, which has to span the whole input
+pub fn parse(input: &[u8]) -> Result<Value<'_>, InvalidBencode> {
+    all_consuming(|input| value(input, 0))
+        .parse(input)
+        .map(|(_, value)| value)
+        .map_err(|_| InvalidBencode)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_values() {
+        assert_eq!(parse(b"i42e"), Ok(Value::Integer(42)));
+        assert_eq!(parse(b"i-3e"), Ok(Value::Integer(-3)));
+        assert_eq!(parse(b"i0e"), Ok(Value::Integer(0)));
+        assert_eq!(parse(b"4:spam"), Ok(Value::Bytes(b"spam")));
+        assert_eq!(parse(b"0:"), Ok(Value::Bytes(b"")));
+        assert_eq!(
+            parse(b"l4:spami7ee"),
+            Ok(Value::List(vec![Value::Bytes(b"spam"), Value::Integer(7)]))
+        );
+
+        let dict = parse(b"d3:cow3:moo4:spaml1:a1:bee").unwrap();
+        assert_eq!(dict.get("cow").and_then(Value::as_str), Some("moo"));
+        assert_eq!(
+            dict.get("spam")
+                .and_then(Value::as_list)
+                .map(|list| list.len()),
+            Some(2)
+        );
+        assert_eq!(dict.get("missing"), None);
+    }
+
+    #[test]
+    fn keeps_raw_dicts() {
+        let input = b"d4:infod6:lengthi5ee4:name1:xe";
+        let torrent = parse(input).unwrap();
+
+        match torrent.get("info") {
+            Some(Value::Dict { raw, .. }) => assert_eq!(*raw, b"d6:lengthi5ee"),
+            other => panic!("unexpected info {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        for input in [
+            &b""[..],
+            b"i-0e",
+            b"i03e",
+            b"ie",
+            b"i12",
+            b"5:spam",
+            b"03:abc",
+            b"l4:spam",
+            b"d3:cowe",
+            b"di1e3:mooe",
+            b"i1ei2e",
+            b"x",
+        ] {
+            assert_eq!(parse(input), Err(InvalidBencode), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn limits_nesting() {
+        let lists = |depth: usize| ["l".repeat(depth), "e".repeat(depth)].concat();
+        let dicts =
+            |depth: usize| ["d1:a".repeat(depth), "i0e".to_string(), "e".repeat(depth)].concat();
+
+        assert!(parse(lists(MAX_DEPTH).as_bytes()).is_ok());
+        assert!(parse(dicts(MAX_DEPTH).as_bytes()).is_ok());
+        assert_eq!(parse(lists(MAX_DEPTH + 1).as_bytes()), Err(InvalidBencode));
+        assert_eq!(parse(dicts(MAX_DEPTH + 1).as_bytes()), Err(InvalidBencode));
+
+        // Deep enough to overflow the stack without a limit
+        assert_eq!(parse(lists(200_000).as_bytes()), Err(InvalidBencode));
+    }
+}
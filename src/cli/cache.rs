@@ -0,0 +1,519 @@
+use std::{
+    collections::HashMap,
+    fs::{self, Metadata},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use hashy::{algorithms::Algorithm, chunked_stream::ChunkedStream};
+
+const CACHE_VERSION: u32 = 1;
+
+/// Where cached digests are kept
+#[derive(Debug, Clone, PartialEq)]
+pub enum CacheStore {
+    /// JSON file shared by all files and algorithms
+    File(PathBuf),
+    /// `user.hashy.<algorithm>` extended attribute of every file
+    Xattr,
+}
+
+/// State of a file when its digest was computed, the digest is reused while it matches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FileState {
+    pub device: u64,
+    pub inode: u64,
+    pub size: u64,
+    pub mtime_seconds: i64,
+    pub mtime_nanoseconds: i64,
+}
+
+impl FileState {
+    #[cfg(unix)]
+    fn of(metadata: &Metadata) -> Option<Self> {
+        use std::os::unix::fs::MetadataExt;
+
+        Some(Self {
+            device: metadata.dev(),
+            inode: metadata.ino(),
+            size: metadata.size(),
+            mtime_seconds: metadata.mtime(),
+            mtime_nanoseconds: metadata.mtime_nsec(),
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn of(_: &Metadata) -> Option<Self> {
+        None
+    }
+
+    /// `device:inode:size:seconds.nanoseconds`, as stored in extended attributes
+    fn encode(&self) -> String {
+        format!(
+            "{}:{}:{}:{}.{}",
+            self.device, self.inode, self.size, self.mtime_seconds, self.mtime_nanoseconds
+        )
+    }
+
+    fn decode(encoded: &str) -> Option<Self> {
+        let mut fields = encoded.split(':');
+        let (device, inode, size) = (fields.next()?, fields.next()?, fields.next()?);
+        let (seconds, nanoseconds) = fields.next()?.split_once('.')?;
+        if fields.next().is_some() {
+            return None;
+        }
+
+        Some(Self {
+            device: device.parse().ok()?,
+            inode: inode.parse().ok()?,
+            size: size.parse().ok()?,
+            mtime_seconds: seconds.parse().ok()?,
+            mtime_nanoseconds: nanoseconds.parse().ok()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CacheEntry {
+    algorithm: String,
+    #[serde(flatten)]
+    state: FileState,
+    /// Digest in lowercase hex
+    digest: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    entries: Vec<CacheEntry>,
+}
+
+#[cfg(target_os = "linux")]
+mod xattr {
+    use std::{ffi::CString, io, os::unix::ffi::OsStrExt, path::Path};
+
+    fn c_string(bytes: &[u8]) -> io::Result<CString> {
+        CString::new(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+    }
+
+    /// Value of an extended attribute, None if the file does not have it
+    pub fn get(path: &Path, name: &str) -> io::Result<Option<Vec<u8>>> {
+        let (path, name) = (
+            c_string(path.as_os_str().as_bytes())?,
+            c_string(name.as_bytes())?,
+        );
+        let mut value: Vec<u8> = Vec::new();
+        loop {
+            // SAFETY: both strings are NUL-terminated and the buffer is as large as given,
+            // an empty buffer only queries the size of the value
+            let length = unsafe {
+                libc::getxattr(
+                    path.as_ptr(),
+                    name.as_ptr(),
+                    value.as_mut_ptr().cast(),
+                    value.len(),
+                )
+            };
+
+            if length < 0 {
+                let err = io::Error::last_os_error();
+                match err.raw_os_error() {
+                    Some(libc::ENODATA) => return Ok(None),
+                    // The value grew since its size was queried
+                    Some(libc::ERANGE) => value.clear(),
+                    _ => return Err(err),
+                }
+            } else if value.is_empty() && length > 0 {
+                value.resize(length as usize, 0);
+            } else {
+                value.truncate(length as usize);
+                return Ok(Some(value));
+            }
+        }
+    }
+
+    pub fn set(path: &Path, name: &str, value: &[u8]) -> io::Result<()> {
+        let (path, name) = (
+            c_string(path.as_os_str().as_bytes())?,
+            c_string(name.as_bytes())?,
+        );
+        // SAFETY: both strings are NUL-terminated and the value is as long as given
+        let result = unsafe {
+            libc::setxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_ptr().cast(),
+                value.len(),
+                0,
+            )
+        };
+
+        match result {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod xattr {
+    use std::{io, path::Path};
+
+    fn unsupported() -> io::Error {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            "extended attributes are only supported on Linux",
+        )
+    }
+
+    pub fn get(_: &Path, _: &str) -> io::Result<Option<Vec<u8>>> {
+        Err(unsupported())
+    }
+
+    pub fn set(_: &Path, _: &str, _: &[u8]) -> io::Result<()> {
+        Err(unsupported())
+    }
+}
+
+/// Cache of the digests of files in one algorithm, keyed on the device, inode,
+/// size and modification time of the files. Any change to a file changes at least
+/// its modification time, so a matching entry can be reused instead of rehashing.
+///
+/// A sample of the hits can be recomputed to check that the cache can be trusted,
+/// digests which turn out wrong are replaced.
+pub struct DigestCache {
+    store: CacheStore,
+    algorithm: String,
+    /// Entries of the cache file by algorithm, device and inode, so that a changed
+    /// file replaces its stale entry
+    entries: Mutex<HashMap<(String, u64, u64), CacheEntry>>,
+    changed: AtomicBool,
+    /// Percentage of hits which are recomputed
+    verify_percent: u8,
+    sampler: AtomicU64,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+    mismatches: AtomicUsize,
+    verbose: bool,
+}
+
+/// SplitMix64 output for the given state
+fn mix(state: u64) -> u64 {
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+impl DigestCache {
+    /// Opens the cache for an algorithm, a cache file which does not exist yet is
+    /// created when saving
+    pub fn open(
+        store: CacheStore,
+        algorithm: String,
+        verify_percent: u8,
+        verbose: bool,
+    ) -> io::Result<Self> {
+        let mut entries = HashMap::new();
+        if let CacheStore::File(path) = &store {
+            match fs::read_to_string(path) {
+                Ok(content) => {
+                    let file: CacheFile = serde_json::from_str(&content)?;
+                    if file.version != CACHE_VERSION {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("unsupported cache version {}", file.version),
+                        ));
+                    }
+                    for entry in file.entries {
+                        let key = (
+                            entry.algorithm.clone(),
+                            entry.state.device,
+                            entry.state.inode,
+                        );
+                        entries.insert(key, entry);
+                    }
+                }
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+
+        Ok(Self {
+            store,
+            algorithm,
+            entries: Mutex::new(entries),
+            changed: AtomicBool::new(false),
+            verify_percent,
+            sampler: AtomicU64::new(seed),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+            mismatches: AtomicUsize::new(0),
+            verbose,
+        })
+    }
+
+    fn xattr_name(&self) -> String {
+        format!("user.hashy.{}", self.algorithm)
+    }
+
+    /// Cached digest of a file in its current state, None if there is none or it is
+    /// stale or malformed
+    fn lookup(&self, path: &Path, state: &FileState) -> io::Result<Option<Vec<u8>>> {
+        let digest = match &self.store {
+            CacheStore::File(_) => {
+                let entries = self.entries.lock().unwrap();
+                entries
+                    .get(&(self.algorithm.clone(), state.device, state.inode))
+                    .filter(|entry| entry.state == *state)
+                    .map(|entry| entry.digest.clone())
+            }
+            CacheStore::Xattr => xattr::get(path, &self.xattr_name())?
+                .and_then(|value| String::from_utf8(value).ok())
+                .and_then(|value| {
+                    let (cached_state, digest) = value.split_once(' ')?;
+                    (FileState::decode(cached_state)? == *state).then(|| digest.to_string())
+                }),
+        };
+
+        Ok(digest.and_then(|digest| hex::decode(digest).ok()))
+    }
+
+    fn store(&self, path: &Path, state: FileState, digest: &[u8]) {
+        let digest = hex::encode(digest);
+        match &self.store {
+            CacheStore::File(_) => {
+                let entry = CacheEntry {
+                    algorithm: self.algorithm.clone(),
+                    state,
+                    digest,
+                };
+                let key = (self.algorithm.clone(), state.device, state.inode);
+                self.entries.lock().unwrap().insert(key, entry);
+                self.changed.store(true, Ordering::Relaxed);
+            }
+            CacheStore::Xattr => {
+                let value = format!("{} {}", state.encode(), digest);
+                // Files which cannot be written to are simply hashed again next time
+                if let Err(err) = xattr::set(path, &self.xattr_name(), value.as_bytes()) {
+                    if self.verbose {
+                        eprintln!("Cache not stored ({}): {}", path.display(), err);
+                    }
+                }
+            }
+        }
+    }
+
+    fn should_verify(&self) -> bool {
+        let state = self
+            .sampler
+            .fetch_add(0x9e3779b97f4a7c15, Ordering::Relaxed);
+        mix(state) % 100 < self.verify_percent as u64
+    }
+
+    /// Digest of a file, reused from the cache if the file did not change since it was
+    /// computed. Digests are only stored if the file did not change while being read.
+    pub fn digest(
+        &self,
+        path: &Path,
+        data: ChunkedStream,
+        algorithm: &dyn Algorithm,
+    ) -> io::Result<Vec<u8>> {
+        let state = match fs::metadata(path).ok().as_ref().and_then(FileState::of) {
+            Some(state) => state,
+            None => return algorithm.digest(data),
+        };
+
+        // Files whose cache cannot be read are hashed as if they had no entry
+        let cached = self.lookup(path, &state).unwrap_or_else(|err| {
+            eprintln!("Cache not read ({}): {}", path.display(), err);
+            None
+        });
+        if let Some(cached) = cached {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            if !self.should_verify() {
+                if self.verbose {
+                    eprintln!("Cache hit: {}", path.display());
+                }
+                return Ok(cached);
+            }
+
+            let digest = algorithm.digest(data)?;
+            if digest == cached {
+                if self.verbose {
+                    eprintln!("Cache hit (verified): {}", path.display());
+                }
+            } else {
+                eprintln!("{}: CACHED DIGEST MISMATCH", path.display());
+                self.mismatches.fetch_add(1, Ordering::Relaxed);
+                self.store(path, state, &digest);
+            }
+            return Ok(digest);
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        if self.verbose {
+            eprintln!("Cache miss: {}", path.display());
+        }
+
+        let digest = algorithm.digest(data)?;
+        let unchanged = fs::metadata(path)
+            .ok()
+            .as_ref()
+            .and_then(FileState::of)
+            .is_some_and(|after| after == state);
+        if unchanged {
+            self.store(path, state, &digest);
+        }
+        Ok(digest)
+    }
+
+    /// Hits, misses and hits whose recomputed digest did not match
+    pub fn counts(&self) -> (usize, usize, usize) {
+        (
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+            self.mismatches.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Writes the cache file if any entry changed, replacing it atomically
+    pub fn save(&self) -> io::Result<()> {
+        let path = match &self.store {
+            CacheStore::File(path) if self.changed.load(Ordering::Relaxed) => path,
+            _ => return Ok(()),
+        };
+
+        let mut entries: Vec<_> = self.entries.lock().unwrap().values().cloned().collect();
+        entries.sort_by(|first, second| {
+            (&first.algorithm, first.state.device, first.state.inode).cmp(&(
+                &second.algorithm,
+                second.state.device,
+                second.state.inode,
+            ))
+        });
+
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let mut file = tempfile::NamedTempFile::new_in(directory)?;
+        serde_json::to_writer(
+            &mut file,
+            &CacheFile {
+                version: CACHE_VERSION,
+                entries,
+            },
+        )?;
+        file.write_all(b"\n")?;
+        file.persist(path).map_err(|err| err.error)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hashy::algorithms::md5::Md5;
+
+    use super::*;
+
+    fn digest(cache: &DigestCache, path: &Path) -> Vec<u8> {
+        let data = ChunkedStream::from(fs::File::open(path).unwrap());
+        cache.digest(path, data, &Md5).unwrap()
+    }
+
+    #[test]
+    fn encodes_file_states() {
+        let state = FileState {
+            device: 2049,
+            inode: 1234,
+            size: 5,
+            mtime_seconds: 1700000000,
+            mtime_nanoseconds: 123456789,
+        };
+        assert_eq!(state.encode(), "2049:1234:5:1700000000.123456789");
+        assert_eq!(FileState::decode(&state.encode()), Some(state));
+        assert_eq!(FileState::decode("1:2:3"), None);
+        assert_eq!(FileState::decode("1:2:3:4.5:6"), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn reads_long_xattrs() {
+        let directory = tempfile::tempdir().unwrap();
+        let file = directory.path().join("file");
+        fs::write(&file, "content").unwrap();
+        assert_eq!(xattr::get(&file, "user.hashy.test").unwrap(), None);
+
+        // Longer than any digest, e.g. a 4096 bit digest in hex is 1024 bytes
+        let value = "0123456789abcdef".repeat(100);
+        match xattr::set(&file, "user.hashy.test", value.as_bytes()) {
+            Ok(()) => {}
+            // Not every filesystem supports user attributes
+            Err(err) if err.raw_os_error() == Some(libc::ENOTSUP) => return,
+            Err(err) => panic!("{}", err),
+        }
+        assert_eq!(
+            xattr::get(&file, "user.hashy.test").unwrap(),
+            Some(value.into_bytes())
+        );
+
+        xattr::set(&file, "user.hashy.test", b"").unwrap();
+        assert_eq!(xattr::get(&file, "user.hashy.test").unwrap(), Some(vec![]));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reuses_file_cache_entries() {
+        let directory = tempfile::tempdir().unwrap();
+        let cache_path = directory.path().join("cache.json");
+        let file = directory.path().join("file");
+        fs::write(&file, "content").unwrap();
+        let expected = Md5
+            .digest(ChunkedStream::from("content".to_string()))
+            .unwrap();
+
+        let open = |verify_percent| {
+            DigestCache::open(
+                CacheStore::File(cache_path.clone()),
+                "md5".to_string(),
+                verify_percent,
+                false,
+            )
+            .unwrap()
+        };
+
+        let cache = open(0);
+        assert_eq!(digest(&cache, &file), expected);
+        assert_eq!(cache.counts(), (0, 1, 0));
+        cache.save().unwrap();
+
+        let cache = open(0);
+        assert_eq!(digest(&cache, &file), expected);
+        assert_eq!(cache.counts(), (1, 0, 0));
+
+        // A wrong cached digest is caught when verifying every hit
+        let content = fs::read_to_string(&cache_path).unwrap();
+        fs::write(&cache_path, content.replace(&hex::encode(&expected), "00")).unwrap();
+        let cache = open(100);
+        assert_eq!(digest(&cache, &file), expected);
+        assert_eq!(cache.counts(), (1, 0, 1));
+
+        // Changing the file misses the cache
+        fs::write(&file, "changed content").unwrap();
+        let cache = open(0);
+        digest(&cache, &file);
+        assert_eq!(cache.counts().1, 1);
+    }
+}
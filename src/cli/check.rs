@@ -0,0 +1,338 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    path::PathBuf,
+};
+
+use anyhow::anyhow;
+use rayon::prelude::*;
+
+use hashy::chunked_stream::ChunkedStream;
+
+use super::parsers::{parse_algorithm, parse_tag, AlgorithmSpec};
+
+/// Algorithm used by SFV files
+const SFV_ALGORITHM: &str = "crc32";
+
+#[derive(Debug, PartialEq)]
+pub enum LineFormat {
+    /// `<hex digest>  <path>` or `<hex digest> *<path>` (coreutils)
+    Gnu,
+    /// `<TAG> (<path>) = <hex digest>` (BSD and `shasum --tag`)
+    Bsd,
+    /// `<path> <crc32 hex>` (Simple File Verification)
+    Sfv,
+}
+
+/// A single entry of a checksum file
+#[derive(Debug, PartialEq)]
+pub struct ChecksumLine {
+    pub format: LineFormat,
+    /// Algorithm named on the line itself (BSD tag)
+    pub algorithm: Option<String>,
+    pub path: String,
+    pub expected: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ParsedLine {
+    /// Blank line or comment
+    Skip,
+    Entry(ChecksumLine),
+    Malformed,
+}
+
+/// Detects the format of a checksum line and parses it.
+///
+/// BSD lines are tried first since they are unambiguous, then GNU lines
+/// (digest first) and finally SFV lines (digest last).
+pub fn parse_line(line: &str) -> ParsedLine {
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    if line.trim().is_empty() || line.starts_with('#') || line.starts_with(';') {
+        return ParsedLine::Skip;
+    }
+
+    parse_bsd(line)
+        .or_else(|| parse_gnu(line))
+        .or_else(|| parse_sfv(line))
+        .map_or(ParsedLine::Malformed, ParsedLine::Entry)
+}
+
+fn parse_bsd(line: &str) -> Option<ChecksumLine> {
+    let (rest, digest) = line.rsplit_once(") = ")?;
+    let (tag, path) = rest.split_once(" (")?;
+    if tag.is_empty() || tag.contains(char::is_whitespace) || path.is_empty() {
+        return None;
+    }
+
+    Some(ChecksumLine {
+        format: LineFormat::Bsd,
+        algorithm: Some(tag.to_string()),
+        path: path.to_string(),
+        expected: hex::decode(digest.trim_end()).ok()?,
+    })
+}
+
+fn parse_gnu(line: &str) -> Option<ChecksumLine> {
+    // Lines starting with a backslash have an escaped path
+    let (escaped, line) = match line.strip_prefix('\\') {
+        Some(line) => (true, line),
+        None => (false, line),
+    };
+
+    let (digest, rest) = line.split_once(' ')?;
+    // Second separator is ' ' for text mode and '*' for binary mode
+    let path = rest.strip_prefix([' ', '*'])?;
+    if path.is_empty() {
+        return None;
+    }
+
+    Some(ChecksumLine {
+        format: LineFormat::Gnu,
+        algorithm: None,
+        path: if escaped {
+            unescape_path(path)?
+        } else {
+            path.to_string()
+        },
+        expected: hex::decode(digest).ok()?,
+    })
+}
+
+fn parse_sfv(line: &str) -> Option<ChecksumLine> {
+    let (path, digest) = line.trim_end().rsplit_once(char::is_whitespace)?;
+    let path = path.trim_end();
+    if digest.len() != 8 || path.is_empty() {
+        return None;
+    }
+
+    Some(ChecksumLine {
+        format: LineFormat::Sfv,
+        algorithm: Some(SFV_ALGORITHM.to_string()),
+        path: path.to_string(),
+        expected: hex::decode(digest).ok()?,
+    })
+}
+
+/// Reverses the escaping of `\\` and `\n` done by coreutils for unusual paths
+fn unescape_path(path: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(path.len());
+    let mut chars = path.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next()? {
+                '\\' => unescaped.push('\\'),
+                'n' => unescaped.push('\n'),
+                _ => return None,
+            }
+        } else {
+            unescaped.push(c);
+        }
+    }
+
+    Some(unescaped)
+}
+
+enum CheckResult {
+    Ok,
+    Failed,
+    Unreadable(io::Error),
+    UnknownAlgorithm(String),
+    /// GNU line checked without an algorithm on the command line
+    NoAlgorithm,
+}
+
+fn check_entry(entry: &ChecksumLine, default_algorithm: Option<&AlgorithmSpec>) -> CheckResult {
+    let line_algorithm = match (&entry.format, &entry.algorithm) {
+        // Keys are not written in tags, so the given algorithm is used if the tags match
+        (LineFormat::Bsd, Some(tag))
+            if default_algorithm.is_some_and(|spec| spec.tag().eq_ignore_ascii_case(tag)) =>
+        {
+            None
+        }
+        (LineFormat::Bsd, Some(tag)) => Some(parse_tag(tag)),
+        (_, Some(name)) => Some(parse_algorithm(name)),
+        (_, None) => None,
+    };
+    let algorithm = match (&line_algorithm, default_algorithm) {
+        (Some(Ok(spec)), _) => spec.build(),
+        (Some(Err(err)), _) => return CheckResult::UnknownAlgorithm(err.to_string()),
+        (None, Some(spec)) => spec.build(),
+        (None, None) => return CheckResult::NoAlgorithm,
+    };
+
+    let digest = File::open(&entry.path)
+        .map(ChunkedStream::from)
+        .and_then(|data| algorithm.digest(data));
+
+    match digest {
+        Ok(digest) if digest == entry.expected => CheckResult::Ok,
+        Ok(_) => CheckResult::Failed,
+        Err(err) => CheckResult::Unreadable(err),
+    }
+}
+
+/// Verifies every entry of a checksum file in parallel and prints the result
+/// of each one, in the style of coreutils `sha256sum --check`.
+///
+/// GNU lines are checked with the given algorithm, BSD and SFV lines name
+/// their own algorithm.
+pub fn check(checksum_file: PathBuf, algorithm: Option<AlgorithmSpec>) -> anyhow::Result<()> {
+    let reader: Box<dyn BufRead> = if checksum_file.as_os_str() == "-" {
+        Box::new(BufReader::new(io::stdin()))
+    } else {
+        Box::new(BufReader::new(File::open(&checksum_file).map_err(
+            |err| anyhow!("Invalid path! {}: {}", checksum_file.display(), err),
+        )?))
+    };
+
+    let mut entries = vec![];
+    let mut malformed = 0;
+    for line in reader.lines() {
+        match parse_line(&line?) {
+            ParsedLine::Skip => {}
+            ParsedLine::Entry(entry) => entries.push(entry),
+            ParsedLine::Malformed => malformed += 1,
+        }
+    }
+
+    let results: Vec<CheckResult> = entries
+        .par_iter()
+        .map(|entry| check_entry(entry, algorithm.as_ref()))
+        .collect();
+
+    let mut out = io::stdout().lock();
+    let (mut failed, mut unreadable, mut unknown, mut missing) = (0, 0, 0, 0);
+    for (entry, result) in entries.iter().zip(results) {
+        match result {
+            CheckResult::Ok => writeln!(out, "{}: OK", entry.path)?,
+            CheckResult::Failed => {
+                writeln!(out, "{}: FAILED", entry.path)?;
+                failed += 1;
+            }
+            CheckResult::Unreadable(err) => {
+                writeln!(out, "{}: FAILED open or read", entry.path)?;
+                eprintln!("{}: {}", entry.path, err);
+                unreadable += 1;
+            }
+            CheckResult::UnknownAlgorithm(reason) => {
+                writeln!(out, "{}: FAILED unknown algorithm", entry.path)?;
+                eprintln!("{}: {}", entry.path, reason);
+                unknown += 1;
+            }
+            CheckResult::NoAlgorithm => {
+                writeln!(out, "{}: FAILED no algorithm given", entry.path)?;
+                missing += 1;
+            }
+        }
+    }
+
+    for (count, description) in [
+        (malformed, "line(s) improperly formatted"),
+        (unreadable, "listed file(s) could not be read"),
+        (unknown, "line(s) with an unknown algorithm"),
+        (
+            missing,
+            "line(s) without an algorithm, give one to check them",
+        ),
+        (failed, "computed checksum(s) did NOT match"),
+    ] {
+        if count > 0 {
+            eprintln!("WARNING: {} {}", count, description);
+        }
+    }
+
+    if entries.is_empty() {
+        return Err(anyhow!(
+            "No properly formatted checksum lines found in {}",
+            checksum_file.display()
+        ));
+    }
+    if failed + unreadable + unknown + missing > 0 {
+        return Err(anyhow!("Checksum verification failed"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_gnu_lines() {
+        assert_eq!(
+            parse_line("900150983cd24fb0d6963f7d28e17f72  abc.txt"),
+            ParsedLine::Entry(ChecksumLine {
+                format: LineFormat::Gnu,
+                algorithm: None,
+                path: "abc.txt".to_string(),
+                expected: hex::decode("900150983cd24fb0d6963f7d28e17f72").unwrap(),
+            })
+        );
+        assert!(matches!(
+            parse_line("900150983CD24FB0D6963F7D28E17F72 *dir/with space.bin"),
+            ParsedLine::Entry(ChecksumLine { format: LineFormat::Gnu, path, .. }) if path == "dir/with space.bin"
+        ));
+        assert!(matches!(
+            parse_line("\\900150983cd24fb0d6963f7d28e17f72  new\\nline\\\\.txt"),
+            ParsedLine::Entry(ChecksumLine { format: LineFormat::Gnu, path, .. }) if path == "new\nline\\.txt"
+        ));
+    }
+
+    #[test]
+    fn parses_bsd_lines() {
+        assert_eq!(
+            parse_line("SHA256 (a (1).txt) = ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+            ParsedLine::Entry(ChecksumLine {
+                format: LineFormat::Bsd,
+                algorithm: Some("SHA256".to_string()),
+                path: "a (1).txt".to_string(),
+                expected: hex::decode(
+                    "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+                )
+                .unwrap(),
+            })
+        );
+        assert!(matches!(
+            parse_line("SHA512/256 (x) = 53048e2681941ef99b2e29b76b4c7dabe4c2d0c634fc6d46e0e2f13107e7af23\r"),
+            ParsedLine::Entry(ChecksumLine { format: LineFormat::Bsd, algorithm: Some(tag), .. }) if tag == "SHA512/256"
+        ));
+    }
+
+    #[test]
+    fn parses_sfv_lines() {
+        assert_eq!(
+            parse_line("some file.zip 352441C2"),
+            ParsedLine::Entry(ChecksumLine {
+                format: LineFormat::Sfv,
+                algorithm: Some("crc32".to_string()),
+                path: "some file.zip".to_string(),
+                expected: vec![0x35, 0x24, 0x41, 0xc2],
+            })
+        );
+        assert_eq!(parse_line("; Generated by WIN-SFV32"), ParsedLine::Skip);
+    }
+
+    #[test]
+    fn skips_and_rejects_lines() {
+        assert_eq!(parse_line(""), ParsedLine::Skip);
+        assert_eq!(parse_line("# comment"), ParsedLine::Skip);
+        assert_eq!(parse_line("not a checksum line"), ParsedLine::Malformed);
+        assert_eq!(parse_line("90015098  "), ParsedLine::Malformed);
+        assert_eq!(parse_line("MD5 (a.txt) = xyz"), ParsedLine::Malformed);
+    }
+
+    #[test]
+    fn gnu_lines_need_an_algorithm() {
+        let entry = match parse_line("900150983cd24fb0d6963f7d28e17f72  abc.txt") {
+            ParsedLine::Entry(entry) => entry,
+            line => panic!("{:?}", line),
+        };
+        assert!(matches!(
+            check_entry(&entry, None),
+            CheckResult::NoAlgorithm
+        ));
+    }
+}
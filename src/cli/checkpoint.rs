@@ -0,0 +1,366 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    time::UNIX_EPOCH,
+};
+
+use serde::{Deserialize, Serialize};
+
+use hashy::{
+    algorithms::{
+        sha2::{Sha2, Sha2Variant},
+        Algorithm, Resumable,
+    },
+    chunked_stream::ChunkedStream,
+};
+
+use super::progress::format_bytes;
+
+const CHECKPOINT_VERSION: u32 = 1;
+
+/// Bytes hashed at once between two checks for interruptions
+const BATCH_SIZE: usize = 1 << 20;
+
+/// Bytes at the start of a file hashed into its fingerprint
+const FINGERPRINT_HEAD: u64 = 64 * 1024;
+
+/// Set once SIGINT or SIGTERM is received while hashing with checkpoints
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Identifies the file a checkpoint was saved for, even if it was moved since
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Fingerprint {
+    size: u64,
+    mtime_seconds: u64,
+    mtime_nanoseconds: u32,
+    /// SHA-256 of the first 64 KiB, in hex
+    head: String,
+}
+
+impl Fingerprint {
+    fn of(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let metadata = file.metadata()?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let head = Sha2::new(Sha2Variant::_256)
+            .digest(ChunkedStream::from(file).take(FINGERPRINT_HEAD))?;
+
+        Ok(Self {
+            size: metadata.len(),
+            mtime_seconds: modified.as_secs(),
+            mtime_nanoseconds: modified.subsec_nanos(),
+            head: hex::encode(head),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Checkpoint {
+    version: u32,
+    algorithm: String,
+    /// File being hashed, for reference only
+    path: PathBuf,
+    fingerprint: Fingerprint,
+    /// Number of bytes hashed
+    offset: u64,
+    /// Saved state of the algorithm, in hex
+    state: String,
+}
+
+/// Checkpoints of hashing a single file, saved to a file every `interval` bytes and
+/// when interrupted, so that hashing can resume from the last one
+pub struct Checkpoints {
+    path: PathBuf,
+    /// Bytes hashed between two checkpoints, 0 to only save when interrupted
+    interval: u64,
+    algorithm_name: String,
+    file: PathBuf,
+    fingerprint: Fingerprint,
+    /// Offset and saved state to resume from
+    resume: Option<(u64, Vec<u8>)>,
+    verbose: bool,
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl Checkpoints {
+    /// Loads the checkpoint at `path` if there is one, refusing it if it was saved
+    /// for another algorithm or file, or if the file changed since
+    pub fn open(
+        path: PathBuf,
+        interval: u64,
+        algorithm_name: String,
+        file: &Path,
+        verbose: bool,
+    ) -> io::Result<Self> {
+        let fingerprint = Fingerprint::of(file)?;
+        let resume = match fs::read_to_string(&path) {
+            Ok(content) => {
+                let checkpoint: Checkpoint = serde_json::from_str(&content)?;
+                if checkpoint.version != CHECKPOINT_VERSION {
+                    return Err(invalid(format!(
+                        "unsupported version {}",
+                        checkpoint.version
+                    )));
+                }
+                if checkpoint.algorithm != algorithm_name {
+                    return Err(invalid(format!(
+                        "saved while hashing with {}, not {}",
+                        checkpoint.algorithm, algorithm_name
+                    )));
+                }
+                if checkpoint.fingerprint != fingerprint {
+                    return Err(invalid(format!(
+                        "saved for another file ({}), or it changed since",
+                        checkpoint.path.display()
+                    )));
+                }
+
+                let state = hex::decode(&checkpoint.state)
+                    .map_err(|err| invalid(format!("invalid state: {}", err)))?;
+                Some((checkpoint.offset, state))
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err),
+        };
+
+        Ok(Self {
+            path,
+            interval,
+            algorithm_name,
+            file: file.to_path_buf(),
+            fingerprint,
+            resume,
+            verbose,
+        })
+    }
+
+    /// Number of bytes already hashed, which the input starts after
+    pub fn offset(&self) -> u64 {
+        self.resume.as_ref().map_or(0, |(offset, _)| *offset)
+    }
+
+    /// Hashes the rest of the file, starting after the offset of the checkpoint.
+    /// The checkpoint file is removed once the whole file is hashed.
+    pub fn digest(&self, data: ChunkedStream, algorithm: &dyn Algorithm) -> io::Result<Vec<u8>> {
+        let mut state = algorithm.resumable().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Unsupported, "checkpoints are not supported")
+        })?;
+        let mut offset = 0;
+        if let Some((resume_offset, saved)) = &self.resume {
+            state.restore(saved)?;
+            offset = *resume_offset;
+            if self.verbose {
+                eprintln!("Resuming after {} from checkpoint", format_bytes(offset));
+            }
+        }
+        let _interruptions = Interruptions::catch();
+
+        let block_size = state.block_size();
+        let batch_size = block_size * (BATCH_SIZE / block_size).max(1);
+        let mut saved_offset = offset;
+        let mut tail = vec![];
+        data.try_for_each_chunk(batch_size, |chunk| {
+            // Only the last chunk can have bytes left after its whole blocks
+            let whole = chunk.len() - chunk.len() % block_size;
+            state.update_blocks(&chunk[..whole]);
+            tail = chunk[whole..].to_vec();
+            offset += whole as u64;
+
+            if INTERRUPTED.load(Ordering::Relaxed) {
+                self.save(offset, state.as_ref())?;
+                return Err(io::Error::new(
+                    io::ErrorKind::Interrupted,
+                    format!(
+                        "Interrupted after {}, resume with --checkpoint {}",
+                        format_bytes(offset),
+                        self.path.display()
+                    ),
+                ));
+            }
+            if self.interval > 0 && offset - saved_offset >= self.interval {
+                self.save(offset, state.as_ref())?;
+                saved_offset = offset;
+            }
+            Ok(())
+        })?;
+
+        match fs::remove_file(&self.path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+        Ok(state.finalize(&tail))
+    }
+
+    /// Replaces the checkpoint file atomically, synced to disk so that it survives
+    /// a crash
+    fn save(&self, offset: u64, state: &dyn Resumable) -> io::Result<()> {
+        let checkpoint = Checkpoint {
+            version: CHECKPOINT_VERSION,
+            algorithm: self.algorithm_name.clone(),
+            path: self.file.clone(),
+            fingerprint: self.fingerprint.clone(),
+            offset,
+            state: hex::encode(state.save()),
+        };
+
+        let directory = match self.path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let mut file = tempfile::NamedTempFile::new_in(directory)?;
+        serde_json::to_writer_pretty(&mut file, &checkpoint)?;
+        file.write_all(b"\n")?;
+        file.as_file().sync_all()?;
+        file.persist(&self.path).map_err(|err| err.error)?;
+
+        if self.verbose {
+            eprintln!("Checkpoint saved after {}", format_bytes(offset));
+        }
+        Ok(())
+    }
+}
+
+/// SIGINT and SIGTERM turned into a flag checked between batches, so that a last
+/// checkpoint is saved before exiting. The previous handlers are restored when
+/// dropped, so that the signals stop the process again once hashing is over.
+#[cfg(unix)]
+struct Interruptions {
+    previous: [(libc::c_int, libc::sighandler_t); 2],
+}
+
+#[cfg(unix)]
+impl Interruptions {
+    fn catch() -> Self {
+        extern "C" fn interrupt(_: libc::c_int) {
+            INTERRUPTED.store(true, Ordering::Relaxed);
+        }
+
+        let handler = interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+        let previous = [libc::SIGINT, libc::SIGTERM]
+            .map(|signal| (signal, unsafe { libc::signal(signal, handler) }));
+        Self { previous }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Interruptions {
+    fn drop(&mut self) {
+        for (signal, handler) in self.previous {
+            // SAFETY: the handler was installed before, by this process or at its start
+            unsafe { libc::signal(signal, handler) };
+        }
+    }
+}
+
+#[cfg(not(unix))]
+struct Interruptions;
+
+#[cfg(not(unix))]
+impl Interruptions {
+    fn catch() -> Self {
+        Self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Seek, SeekFrom};
+
+    use hashy::algorithms::md5::Md5;
+
+    use super::*;
+
+    /// Handler currently installed for SIGINT
+    #[cfg(unix)]
+    fn interrupt_handler() -> libc::sighandler_t {
+        // SAFETY: a null action only reads the current one
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            libc::sigaction(libc::SIGINT, std::ptr::null(), &mut action);
+            action.sa_sigaction
+        }
+    }
+
+    fn open(checkpoint: &Path, file: &Path, algorithm_name: &str) -> io::Result<Checkpoints> {
+        Checkpoints::open(
+            checkpoint.to_path_buf(),
+            1 << 20,
+            algorithm_name.to_string(),
+            file,
+            false,
+        )
+    }
+
+    #[test]
+    fn resumes_from_checkpoints() {
+        let directory = tempfile::tempdir().unwrap();
+        let (checkpoint, path) = (
+            directory.path().join("md5.json"),
+            directory.path().join("data"),
+        );
+        let data: Vec<u8> = (0..3_000_000).map(|i| (i % 253) as u8).collect();
+        fs::write(&path, &data).unwrap();
+        let expected = Md5.digest(ChunkedStream::Bytes(data.clone())).unwrap();
+
+        // Saved halfway, as if hashing had been interrupted there
+        let checkpoints = open(&checkpoint, &path, "md5").unwrap();
+        let mut state = Md5.resumable().unwrap();
+        state.update_blocks(&data[..1 << 20]);
+        checkpoints.save(1 << 20, state.as_ref()).unwrap();
+
+        let checkpoints = open(&checkpoint, &path, "md5").unwrap();
+        assert_eq!(checkpoints.offset(), 1 << 20);
+        #[cfg(unix)]
+        let handler = interrupt_handler();
+        let rest = ChunkedStream::Bytes(data[1 << 20..].to_vec());
+        assert_eq!(checkpoints.digest(rest, &Md5).unwrap(), expected);
+        assert!(!checkpoint.exists());
+        // Interrupting stops the process again once hashing is over
+        #[cfg(unix)]
+        assert_eq!(interrupt_handler(), handler);
+
+        // Resuming from the file itself, seeked past the bytes hashed before
+        checkpoints.save(1 << 20, state.as_ref()).unwrap();
+        let checkpoints = open(&checkpoint, &path, "md5").unwrap();
+        let mut file = File::open(&path).unwrap();
+        file.seek(SeekFrom::Start(checkpoints.offset())).unwrap();
+        assert_eq!(
+            checkpoints.digest(ChunkedStream::from(file), &Md5).unwrap(),
+            expected
+        );
+        assert!(!checkpoint.exists());
+    }
+
+    #[test]
+    fn refuses_other_files_and_algorithms() {
+        let directory = tempfile::tempdir().unwrap();
+        let checkpoint = directory.path().join("checkpoint.json");
+        let (first, second) = (
+            directory.path().join("first"),
+            directory.path().join("second"),
+        );
+        fs::write(&first, b"first file").unwrap();
+        fs::write(&second, b"other file").unwrap();
+
+        let state = Md5.resumable().unwrap();
+        open(&checkpoint, &first, "md5")
+            .unwrap()
+            .save(0, state.as_ref())
+            .unwrap();
+
+        assert!(open(&checkpoint, &first, "md5").is_ok());
+        for (file, algorithm_name) in [(&second, "md5"), (&first, "sha-1")] {
+            let err = open(&checkpoint, file, algorithm_name).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+}
@@ -1,21 +1,168 @@
-use std::time::{Duration, Instant};
+use std::{
+    fs::File,
+    io::{self, stdin, stdout, Seek, SeekFrom, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
-use crate::{
-    algorithms::Algorithm,
+use anyhow::anyhow;
+use rayon::prelude::*;
+
+use hashy::{
+    algorithms::{ssdeep, Algorithm},
     chunked_stream::ChunkedStream,
-    cli::algorithms::{Specification, ALGORITHMS},
 };
 
-use super::encoding::Encoding;
+use super::{
+    algorithms::ALGORITHMS,
+    bench::{bench, BenchOptions},
+    cache::DigestCache,
+    check::check,
+    checkpoint::Checkpoints,
+    dirhash::{explain_dirhash, print_dirhash, DirhashOptions},
+    distances::print_distances,
+    dupes::{print_duplicates, DupesScript},
+    encoding::Encoding,
+    formats::{ListFormat, OutputFormat},
+    manifest::{self, print_manifest, Filters},
+    output::{DigestRecord, PieceRecord, RecordWriter},
+    parsers::AlgorithmSpec,
+    pieces::{compare_pieces, digest_pieces, DedupStats, PieceSplit},
+    progress::{format_bytes, format_throughput, Progress},
+    torrent::{print_infohashes, verify_torrent},
+    watch::watch,
+};
+
+pub enum InputSource {
+    File(PathBuf),
+    Text(String),
+    Stdin,
+}
+
+/// Input to be digested, along with what is known about it beforehand. It is only
+/// opened when digested, so that no more files are open than inputs being digested.
+pub struct Input {
+    pub source: InputSource,
+    /// Size in bytes of the range read, None if unknown
+    pub size: Option<u64>,
+    /// Range read from the input, `length` bytes from `offset`
+    pub offset: u64,
+    pub length: Option<u64>,
+    /// Files are mapped into memory instead of being read
+    pub mmap: bool,
+    /// Counts the bytes read from the input, to report progress
+    pub counter: Option<Arc<AtomicU64>>,
+}
 
 pub enum Command {
-    List,
+    List {
+        format: ListFormat,
+    },
     Digest {
         algorithm: Box<dyn Algorithm>,
-        data: ChunkedStream,
+        algorithm_name: String,
+        /// BSD tag of the algorithm if tagged output was requested
+        tag: Option<String>,
+        /// Digests are printable signatures, which are not encoded
+        textual: bool,
+        inputs: Vec<Input>,
         encoding: Encoding,
+        output_format: OutputFormat,
+        /// How inputs are split into pieces which are also digested separately
+        pieces: Option<PieceSplit>,
+        /// Offset at which the inputs start, so that pieces are numbered from the start of the file
+        offset: u64,
+        /// Encoding the inputs are decoded from, one at a time as they are digested
+        input_encoding: Option<Encoding>,
+        /// Length of the range of decoded inputs, the range of others is selected when opened
+        length: Option<u64>,
+        /// Cache of file digests, reused while the files do not change
+        cache: Option<DigestCache>,
+        /// Checkpoints of hashing a single file, resumed from the last one
+        checkpoints: Option<Box<Checkpoints>>,
+        /// Show a progress line while the inputs are read
+        progress: bool,
         verbose: bool,
     },
+    Check {
+        checksum_file: PathBuf,
+        /// Algorithm for lines which do not name their own
+        algorithm: Option<AlgorithmSpec>,
+    },
+    ComparePieces {
+        first: PathBuf,
+        second: PathBuf,
+    },
+    CompareFuzzy {
+        first: String,
+        second: String,
+    },
+    Distances {
+        paths: Vec<PathBuf>,
+    },
+    Dupes {
+        algorithm: Box<dyn Algorithm>,
+        textual: bool,
+        encoding: Encoding,
+        /// Files and directory trees to search
+        roots: Vec<PathBuf>,
+        output_format: OutputFormat,
+        /// Script written instead of the duplicate groups
+        script: Option<DupesScript>,
+    },
+    Watch {
+        algorithm: Box<dyn Algorithm>,
+        algorithm_name: String,
+        textual: bool,
+        encoding: Encoding,
+        /// Files and directory trees to watch
+        roots: Vec<PathBuf>,
+        output_format: OutputFormat,
+        verbose: bool,
+    },
+    Manifest {
+        directory: PathBuf,
+        algorithm: AlgorithmSpec,
+        filters: Filters,
+    },
+    ManifestDiff {
+        /// Manifest files or directories
+        first: PathBuf,
+        second: PathBuf,
+        /// Algorithm for directories, defaults to the one of the manifest they are compared to
+        algorithm: Option<AlgorithmSpec>,
+        filters: Filters,
+    },
+    Dirhash {
+        directory: PathBuf,
+        algorithm: Box<dyn Algorithm>,
+        textual: bool,
+        encoding: Encoding,
+        options: DirhashOptions,
+        /// Prints the canonical listing instead of its digest
+        listing: bool,
+    },
+    DirhashExplain {
+        /// Directories or saved listings
+        first: PathBuf,
+        second: PathBuf,
+        algorithm: Box<dyn Algorithm>,
+        options: DirhashOptions,
+    },
+    Bench {
+        algorithms: Vec<AlgorithmSpec>,
+        options: BenchOptions,
+    },
+    Torrent {
+        torrent_file: PathBuf,
+        /// Directory holding the payload to verify, infohashes are printed if None
+        payload_directory: Option<PathBuf>,
+        encoding: Encoding,
+    },
 }
 
 fn get_formatted_time_taken(duration: Duration) -> String {
@@ -32,50 +179,450 @@ fn get_formatted_time_taken(duration: Duration) -> String {
     }
 }
 
+impl Input {
+    /// Whole input of `size` bytes, read rather than mapped
+    pub fn new(source: InputSource, size: Option<u64>) -> Self {
+        Self {
+            source,
+            size,
+            offset: 0,
+            length: None,
+            mmap: false,
+            counter: None,
+        }
+    }
+
+    /// Name used for the input in output and diagnostics, None for text
+    pub fn name(&self) -> Option<String> {
+        match &self.source {
+            InputSource::File(path) => Some(path.display().to_string()),
+            InputSource::Text(_) => None,
+            InputSource::Stdin => Some("-".to_string()),
+        }
+    }
+
+    /// Opens the input and selects its range, seeking into regular files instead of
+    /// reading through the skipped bytes
+    pub fn open(&self) -> io::Result<ChunkedStream> {
+        let mut offset = self.offset;
+        let data = match &self.source {
+            InputSource::File(path) => {
+                let mut file = File::open(path)?;
+                if offset > 0
+                    && file.metadata()?.len() >= offset
+                    && file.seek(SeekFrom::Start(offset)).is_ok()
+                {
+                    offset = 0;
+                }
+                match self.mmap {
+                    true => ChunkedStream::mapped(file),
+                    false => ChunkedStream::from(file),
+                }
+            }
+            InputSource::Text(text) => ChunkedStream::from(text.clone()),
+            InputSource::Stdin => ChunkedStream::from(stdin()),
+        };
+
+        let mut data = data.skip(offset).map_err(|err| match err.kind() {
+            io::ErrorKind::UnexpectedEof => io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("offset {} is past the end of the input", self.offset),
+            ),
+            _ => err,
+        })?;
+        if let Some(length) = self.length {
+            data = data.take(length);
+        }
+
+        Ok(match &self.counter {
+            Some(counter) => data.counted(counter.clone()),
+            None => data,
+        })
+    }
+}
+
+/// How inputs are digested into records, shared by the digest and watch modes
+pub struct Digester<'a> {
+    pub algorithm: &'a dyn Algorithm,
+    pub algorithm_name: &'a str,
+    /// Digests are printable signatures, which are not encoded
+    pub textual: bool,
+    pub encoding: &'a Encoding,
+    /// How inputs are split into pieces which are also digested separately
+    pub pieces: Option<PieceSplit>,
+    /// Offset at which the inputs start, so that pieces are numbered from the start of the file
+    pub offset: u64,
+    /// Encoding the inputs are decoded from, their range (`length` bytes from `offset`)
+    /// only being selected once decoded
+    pub input_encoding: Option<&'a Encoding>,
+    pub length: Option<u64>,
+    pub cache: Option<&'a DigestCache>,
+    pub checkpoints: Option<&'a Checkpoints>,
+}
+
+impl Digester<'_> {
+    fn encode(&self, digest_bytes: Vec<u8>) -> String {
+        match self.textual {
+            true => String::from_utf8_lossy(&digest_bytes).into_owned(),
+            false => self.encoding.encode(digest_bytes),
+        }
+    }
+
+    /// Reads and decodes the whole input, which has to be in memory to be decoded,
+    /// then selects its range
+    fn decode(&self, data: ChunkedStream, input_encoding: &Encoding) -> io::Result<Vec<u8>> {
+        let mut encoded = vec![];
+        data.for_each_chunk(65536, |chunk| encoded.extend_from_slice(chunk))?;
+        let mut decoded = input_encoding.decode(&encoded).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("malformed input, {}", err),
+            )
+        })?;
+
+        if self.offset > decoded.len() as u64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("offset {} is past the end of the input", self.offset),
+            ));
+        }
+        decoded.drain(..self.offset as usize);
+        if let Some(length) = self.length {
+            decoded.truncate(length.min(decoded.len() as u64) as usize);
+        }
+
+        Ok(decoded)
+    }
+
+    /// Digests the input, along with its pieces if it is split
+    pub fn digest(&self, input: Input) -> io::Result<DigestRecord> {
+        let start_time = Instant::now();
+        let path = input.name();
+        let data = input.open()?;
+        let (data, size) = match self.input_encoding {
+            Some(input_encoding) => {
+                let decoded = self.decode(data, input_encoding)?;
+                let size = decoded.len() as u64;
+                (ChunkedStream::Bytes(decoded), Some(size))
+            }
+            None => (data, input.size),
+        };
+
+        let (digest, pieces) = match self.pieces {
+            Some(split) => {
+                let (pieces, digest_bytes) = digest_pieces(self.algorithm, data, split)?;
+                let pieces = pieces
+                    .into_iter()
+                    .map(|piece| PieceRecord {
+                        offset: self.offset + piece.offset,
+                        length: piece.length,
+                        digest: self.encode(piece.digest),
+                    })
+                    .collect();
+                (self.encode(digest_bytes), Some(pieces))
+            }
+            None => {
+                let digest_bytes = match (self.checkpoints, self.cache, &input.source) {
+                    (Some(checkpoints), _, _) => checkpoints.digest(data, self.algorithm),
+                    (None, Some(cache), InputSource::File(path)) => {
+                        cache.digest(path, data, self.algorithm)
+                    }
+                    _ => self.algorithm.digest(data),
+                }?;
+                (self.encode(digest_bytes), None)
+            }
+        };
+
+        Ok(DigestRecord {
+            path,
+            size,
+            algorithm: self.algorithm_name.to_string(),
+            encoding: match self.textual {
+                true => "text".to_string(),
+                false => self.encoding.to_string(),
+            },
+            digest,
+            elapsed_seconds: start_time.elapsed().as_secs_f64(),
+            pieces,
+        })
+    }
+}
+
 impl Command {
     pub fn execute(self) -> Result<(), anyhow::Error> {
         match self {
-            Self::List => {
-                println!("{}", list_algorithms());
+            Self::List { format } => {
+                let mut out = stdout().lock();
+                match format {
+                    ListFormat::Text => writeln!(out, "{}", list_algorithms())?,
+                    ListFormat::Json => {
+                        writeln!(out, "{}", serde_json::to_string_pretty(&*ALGORITHMS)?)?
+                    }
+                }
                 Ok(())
             }
             Self::Digest {
-                data,
                 algorithm,
+                algorithm_name,
+                tag,
+                textual,
+                inputs,
                 encoding,
+                output_format,
+                pieces: split,
+                offset,
+                input_encoding,
+                length,
+                cache,
+                checkpoints,
+                progress,
                 verbose,
             } => {
-                let start_time = Instant::now();
+                let with_path = inputs.len() > 1;
+                let digester = Digester {
+                    algorithm: algorithm.as_ref(),
+                    algorithm_name: &algorithm_name,
+                    textual,
+                    encoding: &encoding,
+                    pieces: split,
+                    offset,
+                    input_encoding: input_encoding.as_ref(),
+                    length,
+                    cache: cache.as_ref(),
+                    checkpoints: checkpoints.as_deref(),
+                };
+
+                // Bytes read from every input, for the progress line and throughput
+                let counters: Vec<_> = inputs.iter().map(|_| Arc::new(AtomicU64::new(0))).collect();
+                let total = inputs.iter().map(|input| input.size).sum();
+                let progress = progress
+                    .then(|| Progress::start(counters.clone(), total))
+                    .flatten();
+                let count = progress.is_some() || verbose;
+
+                // Digest inputs in parallel, results are written in the order of inputs
+                let results: Vec<_> = inputs
+                    .into_par_iter()
+                    .zip(counters)
+                    .map(|(mut input, counter)| {
+                        let name = input.name();
+                        let size = input.size;
+                        if count {
+                            input.counter = Some(counter.clone());
+                        }
+
+                        let record = digester.digest(input);
+
+                        let bytes_read = counter.load(Ordering::Relaxed);
+                        // Cached digests are not read, but the input is done all the same
+                        if let Some(size) = size {
+                            counter.fetch_max(size, Ordering::Relaxed);
+                        }
+
+                        record
+                            .map(|record| (record, bytes_read))
+                            .map_err(|err| (name, err))
+                    })
+                    .collect();
+                if let Some(progress) = progress {
+                    progress.finish();
+                }
+
+                let mut writer = RecordWriter::new(stdout().lock(), output_format, with_path);
+                if let Some(tag) = tag {
+                    writer = writer.tagged(tag);
+                }
+                let mut stats = DedupStats::default();
+                let mut failed = 0;
+                for result in results {
+                    match result {
+                        Ok((record, bytes_read)) => {
+                            if let Some(pieces) = &record.pieces {
+                                for piece in pieces {
+                                    stats.add(piece.length, &piece.digest);
+                                }
+                            }
 
-                let digest_bytes = algorithm.digest(data)?;
-                let digest_encoded = encoding.encode(digest_bytes);
+                            if verbose {
+                                let time_taken = Duration::from_secs_f64(record.elapsed_seconds);
+                                eprintln!(
+                                    "Time taken{}: {} ({}, {})",
+                                    match (&record.path, with_path) {
+                                        (Some(path), true) => format!(" ({})", path),
+                                        _ => String::new(),
+                                    },
+                                    get_formatted_time_taken(time_taken),
+                                    format_bytes(bytes_read),
+                                    format_throughput(bytes_read, time_taken)
+                                );
+                            }
 
-                let end_time = Instant::now();
-                let time_taken = end_time - start_time;
+                            writer.write(&record)?;
+                        }
+                        Err((name, err)) => {
+                            eprintln!("{}: {}", name.as_deref().unwrap_or("text"), err);
+                            failed += 1;
+                        }
+                    }
+                }
+                writer.finish()?;
 
-                println!("{}", digest_encoded);
+                if let Some(PieceSplit::ContentDefined(_)) = split {
+                    eprintln!("{}", stats);
+                }
 
-                if verbose {
-                    println!("Time taken: {}", get_formatted_time_taken(time_taken));
+                let mut mismatches = 0;
+                if let Some(cache) = &cache {
+                    let (hits, misses, mismatched) = cache.counts();
+                    if verbose {
+                        eprintln!("Cache: {} hit(s), {} miss(es)", hits, misses);
+                    }
+                    cache
+                        .save()
+                        .map_err(|err| anyhow!("Failed to save the cache: {}", err))?;
+                    if mismatched > 0 {
+                        eprintln!("WARNING: {} cached digest(s) did NOT match", mismatched);
+                        mismatches = mismatched;
+                    }
+                }
+
+                if failed > 0 {
+                    return Err(anyhow!("Failed to digest {} input(s)", failed));
+                }
+                if mismatches > 0 {
+                    return Err(anyhow!("Cache verification failed"));
                 }
 
                 Ok(())
             }
+            Self::Check {
+                checksum_file,
+                algorithm,
+            } => check(checksum_file, algorithm),
+            Self::ComparePieces { first, second } => compare_pieces(first, second),
+            Self::CompareFuzzy { first, second } => {
+                let score = ssdeep::compare(&first, &second)
+                    .ok_or_else(|| anyhow!("Invalid signature! Expected blocksize:hash1:hash2"))?;
+                let mut out = stdout().lock();
+                writeln!(out, "{}", score)?;
+                Ok(())
+            }
+            Self::Distances { paths } => print_distances(paths),
+            Self::Bench {
+                algorithms,
+                options,
+            } => bench(&algorithms, &options),
+            Self::Dupes {
+                algorithm,
+                textual,
+                encoding,
+                roots,
+                output_format,
+                script,
+            } => print_duplicates(
+                algorithm.as_ref(),
+                textual,
+                &encoding,
+                &roots,
+                output_format,
+                script,
+            ),
+            Self::Watch {
+                algorithm,
+                algorithm_name,
+                textual,
+                encoding,
+                roots,
+                output_format,
+                verbose,
+            } => watch(
+                &Digester {
+                    algorithm: algorithm.as_ref(),
+                    algorithm_name: &algorithm_name,
+                    textual,
+                    encoding: &encoding,
+                    pieces: None,
+                    offset: 0,
+                    input_encoding: None,
+                    length: None,
+                    cache: None,
+                    checkpoints: None,
+                },
+                &roots,
+                output_format,
+                verbose,
+            ),
+            Self::Manifest {
+                directory,
+                algorithm,
+                filters,
+            } => print_manifest(&directory, &algorithm, &filters),
+            Self::ManifestDiff {
+                first,
+                second,
+                algorithm,
+                filters,
+            } => manifest::diff(first, second, algorithm, &filters),
+            Self::Dirhash {
+                directory,
+                algorithm,
+                textual,
+                encoding,
+                options,
+                listing,
+            } => print_dirhash(
+                &directory,
+                algorithm.as_ref(),
+                textual,
+                &encoding,
+                &options,
+                listing,
+            ),
+            Self::DirhashExplain {
+                first,
+                second,
+                algorithm,
+                options,
+            } => explain_dirhash(&first, &second, algorithm.as_ref(), &options),
+            Self::Torrent {
+                torrent_file,
+                payload_directory,
+                encoding,
+            } => match payload_directory {
+                Some(directory) => verify_torrent(&torrent_file, &directory),
+                None => print_infohashes(&torrent_file, &encoding),
+            },
         }
     }
 }
 
 fn list_algorithms() -> String {
-    let count = ALGORITHMS.iter().fold(0, |current, spec| match spec {
-        Specification::Single(_) => current + 1,
-        Specification::Family { name: _, members } => current + members.len(),
-    });
+    // Group entries by family, keeping the order in which they appear in the registry
+    let mut groups: Vec<(Option<&str>, Vec<String>)> = vec![];
+    for entry in ALGORITHMS.iter() {
+        let mut line = entry.display_name(entry.name);
+        if !entry.aliases.is_empty() {
+            let aliases: Vec<String> = entry
+                .aliases
+                .iter()
+                .map(|alias| entry.display_name(alias))
+                .collect();
+            line = format!("{} ({})", line, aliases.join(", "));
+        }
+
+        match groups.last_mut() {
+            Some((family, members)) if entry.family.is_some() && *family == entry.family => {
+                members.push(line)
+            }
+            _ => groups.push((entry.family, vec![line])),
+        }
+    }
 
-    let list = ALGORITHMS
+    let list = groups
         .iter()
-        .map(|spec| match spec {
-            Specification::Single(name) => format!("- {}", name),
-            Specification::Family { name, members } => [
+        .map(|(family, members)| match family {
+            None => format!("- {}", members.join("\n- ")),
+            Some(name) => [
                 format!("- {} family", name),
                 members
                     .iter()
@@ -88,5 +635,5 @@ fn list_algorithms() -> String {
         .collect::<Vec<String>>()
         .join("\n");
 
-    format!("Algorithm count: {}\n{}", count, list)
+    format!("Algorithm count: {}\n{}", ALGORITHMS.len(), list)
 }
@@ -0,0 +1,432 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::anyhow;
+use rayon::prelude::*;
+
+use hashy::{algorithms::Algorithm, chunked_stream::ChunkedStream};
+
+use super::{
+    encoding::Encoding,
+    manifest::{relative_path, report_errors, Filters},
+    walk::walk_filtered,
+};
+
+/// Directories of version control systems, left out with `--ignore-vcs`
+pub const VCS_DIRECTORIES: &[&str] = &[".git", ".hg", ".svn", ".bzr", "_darcs", "CVS"];
+
+/// How directory trees are turned into listings
+#[derive(Debug, Clone, Default)]
+pub struct DirhashOptions {
+    pub filters: Filters,
+    /// Hashes the targets of symlinks instead of the links themselves
+    pub follow_symlinks: bool,
+}
+
+/// Line of a canonical listing: `<type> <digest> <path>`
+#[derive(Debug, Clone, PartialEq)]
+struct ListingEntry {
+    /// `f` for files, `x` for executable files, `l` for symlinks, `d` for directories
+    /// and `s` for special files
+    kind: char,
+    /// Digest of the content of files and of the target of symlinks, `-` otherwise
+    digest: String,
+    path: String,
+}
+
+impl ListingEntry {
+    fn parse(line: &str) -> Option<Self> {
+        let (kind, rest) = line.split_once(' ')?;
+        let (digest, path) = rest.split_once(' ')?;
+        let mut kind = kind.chars();
+        match (kind.next(), kind.next()) {
+            (Some(kind), None) if "fxlds".contains(kind) => Some(Self {
+                kind,
+                digest: digest.to_string(),
+                path: path.to_string(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Escapes backslashes and newlines, so that every entry takes one line
+fn escape_path(path: &str) -> String {
+    path.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_: &fs::Metadata) -> bool {
+    false
+}
+
+/// Canonical listing of a directory tree, which the dirhash is the digest of.
+///
+/// Every entry below the directory is listed as `<type> <digest> <path>`, sorted
+/// bytewise by its path relative to the directory (with `/` separators). Only the
+/// type, executable bit, content and path of entries count, not their timestamps,
+/// owners or the order the filesystem lists them in. With include patterns, only
+/// the directories holding an included file are listed.
+fn listing(
+    directory: &Path,
+    algorithm: &dyn Algorithm,
+    options: &DirhashOptions,
+) -> (Vec<ListingEntry>, Vec<(PathBuf, io::Error)>) {
+    let walk = walk_filtered(
+        &[directory.to_path_buf()],
+        &|path| options.filters.excludes(&relative_path(directory, path)),
+        options.follow_symlinks,
+    );
+    let mut errors = walk.errors;
+
+    let results: Vec<_> = walk
+        .entries
+        .into_par_iter()
+        .filter(|entry| entry.path != directory)
+        .filter_map(|entry| {
+            let path = relative_path(directory, &entry.path);
+            let file_type = entry.metadata.file_type();
+            let (kind, digest) = if file_type.is_dir() {
+                ('d', Ok(None))
+            } else if !options.filters.includes(&path) {
+                return None;
+            } else if file_type.is_file() {
+                let digest = fs::File::open(&entry.path)
+                    .map(ChunkedStream::from)
+                    .and_then(|data| algorithm.digest(data));
+                match is_executable(&entry.metadata) {
+                    true => ('x', digest.map(Some)),
+                    false => ('f', digest.map(Some)),
+                }
+            } else if file_type.is_symlink() {
+                let digest = fs::read_link(&entry.path).and_then(|target| {
+                    let target = target.to_string_lossy().into_owned();
+                    algorithm.digest(ChunkedStream::from(target))
+                });
+                ('l', digest.map(Some))
+            } else {
+                ('s', Ok(None))
+            };
+
+            Some(match digest {
+                Ok(digest) => Ok(ListingEntry {
+                    kind,
+                    digest: digest.map_or("-".to_string(), hex::encode),
+                    path: escape_path(&path),
+                }),
+                Err(err) => Err((entry.path, err)),
+            })
+        })
+        .collect();
+
+    let mut entries = vec![];
+    for result in results {
+        match result {
+            Ok(entry) => entries.push(entry),
+            Err(error) => errors.push(error),
+        }
+    }
+    entries.sort_by(|first, second| first.path.as_bytes().cmp(second.path.as_bytes()));
+
+    // Patterns select files, so directories only count when a file below them does
+    if !options.filters.include.is_empty() {
+        let mut parents = HashSet::new();
+        for entry in entries.iter().filter(|entry| entry.kind != 'd') {
+            let mut path = entry.path.as_str();
+            while let Some((parent, _)) = path.rsplit_once('/') {
+                if !parents.insert(parent.to_string()) {
+                    break;
+                }
+                path = parent;
+            }
+        }
+        entries.retain(|entry| entry.kind != 'd' || parents.contains(&entry.path));
+    }
+
+    (entries, errors)
+}
+
+fn write_listing(out: &mut impl Write, entries: &[ListingEntry]) -> io::Result<()> {
+    for entry in entries {
+        writeln!(out, "{} {} {}", entry.kind, entry.digest, entry.path)?;
+    }
+    Ok(())
+}
+
+/// Prints the digest of the canonical listing of the directory, or the listing
+/// itself, so that `hashy <algorithm> --dirhash <dir>` is the same as hashing the
+/// output of `hashy <algorithm> --dirhash <dir> --dirhash-listing`
+pub fn print_dirhash(
+    directory: &Path,
+    algorithm: &dyn Algorithm,
+    textual: bool,
+    encoding: &Encoding,
+    options: &DirhashOptions,
+    print_listing: bool,
+) -> anyhow::Result<()> {
+    if !directory.is_dir() {
+        return Err(anyhow!("Not a directory! {}", directory.display()));
+    }
+
+    let (entries, errors) = listing(directory, algorithm, options);
+    // A digest of an incomplete tree would look valid, so none is printed
+    report_errors(&errors)?;
+
+    let mut listing = vec![];
+    write_listing(&mut listing, &entries)?;
+
+    let mut out = io::stdout().lock();
+    if print_listing {
+        out.write_all(&listing)?;
+    } else {
+        let digest = algorithm.digest(ChunkedStream::Bytes(listing))?;
+        let digest = match textual {
+            true => String::from_utf8_lossy(&digest).into_owned(),
+            false => encoding.encode(digest),
+        };
+        writeln!(out, "{}  {}", digest, directory.display())?;
+    }
+
+    Ok(())
+}
+
+/// Reads the listing of a directory, or a listing saved with `--dirhash-listing`
+fn read_side(
+    path: &Path,
+    algorithm: &dyn Algorithm,
+    options: &DirhashOptions,
+) -> anyhow::Result<Vec<ListingEntry>> {
+    if path.is_dir() {
+        let (entries, errors) = listing(path, algorithm, options);
+        report_errors(&errors)?;
+        return Ok(entries);
+    }
+
+    let content = fs::read_to_string(path)
+        .map_err(|err| anyhow!("Invalid path! {}: {}", path.display(), err))?;
+    content
+        .lines()
+        .enumerate()
+        .map(|(index, line)| {
+            ListingEntry::parse(line).ok_or_else(|| {
+                anyhow!(
+                    "Invalid listing line {} in {}: {}",
+                    index + 1,
+                    path.display(),
+                    line
+                )
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq)]
+enum Difference {
+    Added,
+    Removed,
+    Modified,
+    TypeChanged,
+}
+
+fn diff_listings<'a>(
+    first: &'a [ListingEntry],
+    second: &'a [ListingEntry],
+) -> Vec<(&'a str, Difference)> {
+    let old: BTreeMap<_, _> = first
+        .iter()
+        .map(|entry| (entry.path.as_str(), entry))
+        .collect();
+    let new: BTreeMap<_, _> = second
+        .iter()
+        .map(|entry| (entry.path.as_str(), entry))
+        .collect();
+
+    let mut differences: Vec<_> = old
+        .iter()
+        .filter_map(|(path, entry)| match new.get(path) {
+            None => Some((*path, Difference::Removed)),
+            Some(other) if other.kind != entry.kind => Some((*path, Difference::TypeChanged)),
+            Some(other) if other.digest != entry.digest => Some((*path, Difference::Modified)),
+            Some(_) => None,
+        })
+        .chain(
+            new.keys()
+                .filter(|path| !old.contains_key(*path))
+                .map(|path| (*path, Difference::Added)),
+        )
+        .collect();
+
+    differences.sort_by(|(first, _), (second, _)| first.as_bytes().cmp(second.as_bytes()));
+    differences
+}
+
+/// Explains why the dirhashes of two directories (or saved listings) differ by
+/// printing the entries which changed. Fails if there are any.
+pub fn explain_dirhash(
+    first: &Path,
+    second: &Path,
+    algorithm: &dyn Algorithm,
+    options: &DirhashOptions,
+) -> anyhow::Result<()> {
+    let first_entries = read_side(first, algorithm, options)?;
+    let second_entries = read_side(second, algorithm, options)?;
+
+    let differences = diff_listings(&first_entries, &second_entries);
+    let mut out = io::stdout().lock();
+    for (path, difference) in &differences {
+        match difference {
+            Difference::Added => writeln!(out, "{}: ADDED", path)?,
+            Difference::Removed => writeln!(out, "{}: REMOVED", path)?,
+            Difference::Modified => writeln!(out, "{}: MODIFIED", path)?,
+            Difference::TypeChanged => writeln!(out, "{}: TYPE CHANGED", path)?,
+        }
+    }
+
+    if !differences.is_empty() {
+        eprintln!("WARNING: {} entry(ies) differ", differences.len());
+        return Err(anyhow!("Directory hashes differ"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use hashy::algorithms::md5::Md5;
+
+    use super::{super::glob::Glob, *};
+
+    fn tree() -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("src/.git")).unwrap();
+        fs::create_dir(root.path().join("empty")).unwrap();
+        fs::write(root.path().join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(root.path().join("src/.git/HEAD"), "ref").unwrap();
+        fs::write(root.path().join("b"), "b").unwrap();
+        root
+    }
+
+    #[test]
+    fn lists_trees_canonically() {
+        let root = tree();
+        let options = DirhashOptions {
+            filters: Filters {
+                include: vec![],
+                exclude: VCS_DIRECTORIES
+                    .iter()
+                    .map(|dir| Glob::new(dir).unwrap())
+                    .collect(),
+            },
+            follow_symlinks: false,
+        };
+
+        let (entries, errors) = listing(root.path(), &Md5, &options);
+        assert!(errors.is_empty());
+
+        let mut out = vec![];
+        write_listing(&mut out, &entries).unwrap();
+        let md5 =
+            |data: &str| hex::encode(Md5.digest(ChunkedStream::from(data.to_string())).unwrap());
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "f {} b\nd - empty\nd - src\nf {} src/main.rs\n",
+                md5("b"),
+                md5("fn main() {}")
+            )
+        );
+
+        // Parsed back from the listing
+        let line = format!("f {} src/main.rs", md5("fn main() {}"));
+        assert_eq!(ListingEntry::parse(&line).as_ref(), entries.get(3));
+        assert_eq!(ListingEntry::parse("q - path"), None);
+    }
+
+    #[test]
+    fn lists_directories_of_included_files() {
+        let root = tree();
+        fs::create_dir_all(root.path().join("docs/deep")).unwrap();
+        fs::write(root.path().join("docs/deep/notes.txt"), "notes").unwrap();
+        let options = DirhashOptions {
+            filters: Filters {
+                include: vec![Glob::new("*.rs").unwrap()],
+                exclude: vec![],
+            },
+            follow_symlinks: false,
+        };
+
+        let (entries, _) = listing(root.path(), &Md5, &options);
+        let paths: Vec<_> = entries
+            .iter()
+            .map(|entry| (entry.kind, entry.path.as_str()))
+            .collect();
+        assert_eq!(paths, [('d', "src"), ('f', "src/main.rs")]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn executable_bit_and_symlinks_count() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let root = tree();
+        let options = DirhashOptions::default();
+        let (before, _) = listing(root.path(), &Md5, &options);
+
+        fs::set_permissions(root.path().join("b"), fs::Permissions::from_mode(0o755)).unwrap();
+        symlink("b", root.path().join("link")).unwrap();
+        let (after, _) = listing(root.path(), &Md5, &options);
+
+        assert_eq!(
+            diff_listings(&before, &after),
+            [("b", Difference::TypeChanged), ("link", Difference::Added)]
+        );
+
+        let following = DirhashOptions {
+            follow_symlinks: true,
+            ..DirhashOptions::default()
+        };
+        let (followed, _) = listing(root.path(), &Md5, &following);
+        let link = followed.iter().find(|entry| entry.path == "link").unwrap();
+        assert_eq!(link.kind, 'x');
+    }
+
+    #[test]
+    fn diffs_listings() {
+        let entry = |kind, digest: &str, path: &str| ListingEntry {
+            kind,
+            digest: digest.to_string(),
+            path: path.to_string(),
+        };
+        let first = [
+            entry('f', "aa", "a"),
+            entry('f', "bb", "b"),
+            entry('d', "-", "c"),
+        ];
+        let second = [
+            entry('f', "aa", "a"),
+            entry('f', "cc", "b"),
+            entry('d', "-", "d"),
+        ];
+
+        assert_eq!(
+            diff_listings(&first, &second),
+            [
+                ("b", Difference::Modified),
+                ("c", Difference::Removed),
+                ("d", Difference::Added)
+            ]
+        );
+        assert_eq!(escape_path("a\\b\nc"), "a\\\\b\\nc");
+    }
+}
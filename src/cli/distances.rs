@@ -0,0 +1,91 @@
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use anyhow::anyhow;
+use rayon::prelude::*;
+
+use hashy::{
+    algorithms::{
+        tlsh::{self, Tlsh},
+        Algorithm,
+    },
+    chunked_stream::ChunkedStream,
+};
+
+/// Distances between every pair of digests, in the order of the digests.
+/// Pairs with a missing digest are left out.
+fn pairwise_distances(digests: &[Option<String>]) -> Vec<(usize, usize, u32)> {
+    let mut distances = vec![];
+    for (i, first) in digests.iter().enumerate() {
+        for (j, second) in digests.iter().enumerate().skip(i + 1) {
+            if let (Some(first), Some(second)) = (first, second) {
+                if let Some(distance) = tlsh::distance(first, second) {
+                    distances.push((i, j, distance));
+                }
+            }
+        }
+    }
+
+    distances
+}
+
+/// Digests every file with TLSH and prints the distance of every pair of them as
+/// `first<TAB>second<TAB>distance` lines, 0 meaning identical (or nearly).
+///
+/// Files which cannot be digested are reported and left out of the pairs.
+pub fn print_distances(paths: Vec<PathBuf>) -> anyhow::Result<()> {
+    let digests: Vec<Option<String>> = paths
+        .par_iter()
+        .map(|path| {
+            match File::open(path)
+                .map(ChunkedStream::from)
+                .and_then(|data| Tlsh.digest(data))
+            {
+                Ok(digest) => Some(String::from_utf8_lossy(&digest).into_owned()),
+                Err(err) => {
+                    eprintln!("{}: {}", path.display(), err);
+                    None
+                }
+            }
+        })
+        .collect();
+
+    let mut out = io::stdout().lock();
+    for (i, j, distance) in pairwise_distances(&digests) {
+        writeln!(
+            out,
+            "{}\t{}\t{}",
+            paths[i].display(),
+            paths[j].display(),
+            distance
+        )?;
+    }
+
+    let failed = digests.iter().filter(|digest| digest.is_none()).count();
+    if failed > 0 {
+        eprintln!("WARNING: {} input(s) could not be digested", failed);
+        return Err(anyhow!("Failed to digest {} input(s)", failed));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pairs_every_digest_once() {
+        let zero = format!("T1{}", "0".repeat(70));
+        let other = format!("T1{}03", "0".repeat(68));
+        let digests = [Some(zero.clone()), None, Some(other), Some(zero)];
+
+        assert_eq!(
+            pairwise_distances(&digests),
+            [(0, 2, 6), (0, 3, 0), (2, 3, 6)]
+        );
+    }
+}
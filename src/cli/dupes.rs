@@ -0,0 +1,374 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::File,
+    io::{self, BufReader, Read, Write},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use anyhow::anyhow;
+use rayon::prelude::*;
+use serde::Serialize;
+
+use hashy::{algorithms::Algorithm, chunked_stream::ChunkedStream};
+
+use super::{
+    encoding::Encoding,
+    formats::OutputFormat,
+    walk::{file_id, walk},
+};
+
+/// Files are first told apart by a digest of this many bytes at their start
+const PREFIX_SIZE: u64 = 4096;
+
+/// Shell script written instead of the duplicate groups, keeping the first file of every group
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DupesScript {
+    /// Replaces duplicates with hardlinks to the kept file
+    Hardlink,
+    /// Deletes duplicates
+    Delete,
+}
+
+impl FromStr for DupesScript {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "hardlink" => Ok(DupesScript::Hardlink),
+            "delete" => Ok(DupesScript::Delete),
+            _ => Err(format!("Invalid script {}, expected hardlink or delete", s)),
+        }
+    }
+}
+
+/// Files with the same size and digest
+#[derive(Debug, PartialEq, Serialize)]
+pub struct DuplicateGroup {
+    pub digest: String,
+    /// Size of every file in the group
+    pub size: u64,
+    /// Space taken by all the files but one
+    pub wasted: u64,
+    pub paths: Vec<String>,
+}
+
+#[derive(Clone)]
+struct Candidate {
+    path: PathBuf,
+    size: u64,
+}
+
+fn digest_file(
+    algorithm: &dyn Algorithm,
+    candidate: &Candidate,
+    limit: u64,
+) -> io::Result<Vec<u8>> {
+    let file = File::open(&candidate.path)?;
+    algorithm.digest(ChunkedStream::File(Box::new(BufReader::new(
+        file.take(limit),
+    ))))
+}
+
+/// Splits every group further by the key of its files, dropping groups left with
+/// a single file. Files which cannot be read are reported, counted and dropped.
+fn regroup<K: Ord + Send>(
+    groups: Vec<Vec<Candidate>>,
+    key: impl Fn(&Candidate) -> io::Result<K> + Sync,
+    failed: &mut usize,
+) -> Vec<(K, Vec<Candidate>)> {
+    let keyed: Vec<_> = groups
+        .into_iter()
+        .enumerate()
+        .flat_map(|(index, group)| group.into_iter().map(move |candidate| (index, candidate)))
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .map(|(index, candidate)| (index, key(&candidate), candidate))
+        .collect();
+
+    let mut by_key: BTreeMap<(usize, K), Vec<Candidate>> = BTreeMap::new();
+    for (index, key, candidate) in keyed {
+        match key {
+            Ok(key) => by_key.entry((index, key)).or_default().push(candidate),
+            Err(err) => {
+                eprintln!("{}: {}", candidate.path.display(), err);
+                *failed += 1;
+            }
+        }
+    }
+
+    by_key
+        .into_iter()
+        .filter(|(_, members)| members.len() > 1)
+        .map(|((_, key), members)| (key, members))
+        .collect()
+}
+
+/// Groups files by size, then by a digest of their first 4 KiB and finally by a
+/// digest of the whole file, so that only files which could be duplicates are read
+/// in full. Hardlinks to the same file are only counted once.
+///
+/// Groups are sorted by wasted space, largest first.
+fn find_duplicates(
+    algorithm: &dyn Algorithm,
+    candidates: Vec<Candidate>,
+    failed: &mut usize,
+) -> Vec<(Vec<u8>, Vec<Candidate>)> {
+    let by_size = regroup(vec![candidates], |candidate| Ok(candidate.size), failed);
+
+    let by_prefix = regroup(
+        by_size.into_iter().map(|(_, members)| members).collect(),
+        |candidate| digest_file(algorithm, candidate, PREFIX_SIZE),
+        failed,
+    );
+
+    // The prefix digest already covers small files
+    let (small, large): (Vec<_>, Vec<_>) = by_prefix
+        .into_iter()
+        .partition(|(_, members)| members[0].size <= PREFIX_SIZE);
+    let mut groups = regroup(
+        large.into_iter().map(|(_, members)| members).collect(),
+        |candidate| digest_file(algorithm, candidate, u64::MAX),
+        failed,
+    );
+    groups.extend(small);
+
+    groups.sort_by(|(_, first), (_, second)| {
+        let wasted = |members: &[Candidate]| members[0].size * (members.len() as u64 - 1);
+        wasted(second)
+            .cmp(&wasted(first))
+            .then_with(|| first[0].path.cmp(&second[0].path))
+    });
+    groups
+}
+
+/// Quotes a path for a POSIX shell
+fn shell_quote(path: &str) -> String {
+    format!("'{}'", path.replace('\'', "'\\''"))
+}
+
+fn write_script(
+    out: &mut impl Write,
+    groups: &[DuplicateGroup],
+    script: DupesScript,
+) -> io::Result<()> {
+    writeln!(out, "#!/bin/sh")?;
+    writeln!(
+        out,
+        "# Written by hashy --dupes, review before running! The first file of every group is kept."
+    )?;
+
+    for group in groups {
+        let (kept, duplicates) = group
+            .paths
+            .split_first()
+            .expect("groups have 2 or more files");
+        writeln!(out)?;
+        writeln!(
+            out,
+            "# {} ({} bytes x {}), keeping {}",
+            group.digest,
+            group.size,
+            group.paths.len(),
+            shell_quote(kept)
+        )?;
+        for duplicate in duplicates {
+            match script {
+                DupesScript::Hardlink => writeln!(
+                    out,
+                    "ln -f -- {} {}",
+                    shell_quote(kept),
+                    shell_quote(duplicate)
+                )?,
+                DupesScript::Delete => writeln!(out, "rm -- {}", shell_quote(duplicate))?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn write_groups(
+    out: &mut impl Write,
+    groups: &[DuplicateGroup],
+    output_format: OutputFormat,
+) -> io::Result<()> {
+    match output_format {
+        OutputFormat::Json => writeln!(out, "{}", serde_json::to_string_pretty(groups)?)?,
+        OutputFormat::Ndjson => {
+            for group in groups {
+                writeln!(out, "{}", serde_json::to_string(group)?)?;
+            }
+        }
+        // CSV is rejected when parsing options
+        OutputFormat::Text | OutputFormat::Csv => {
+            for (i, group) in groups.iter().enumerate() {
+                if i > 0 {
+                    writeln!(out)?;
+                }
+                writeln!(
+                    out,
+                    "{} {} bytes x {}, {} bytes wasted",
+                    group.digest,
+                    group.size,
+                    group.paths.len(),
+                    group.wasted
+                )?;
+                for path in &group.paths {
+                    writeln!(out, "{}", path)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Finds duplicate files under the roots and prints their groups, or a script
+/// dealing with them. Empty files are not considered duplicates.
+pub fn print_duplicates(
+    algorithm: &dyn Algorithm,
+    textual: bool,
+    encoding: &Encoding,
+    roots: &[PathBuf],
+    output_format: OutputFormat,
+    script: Option<DupesScript>,
+) -> anyhow::Result<()> {
+    let walk = walk(roots);
+    let mut failed = walk.errors.len();
+    for (path, err) in &walk.errors {
+        eprintln!("{}: {}", path.display(), err);
+    }
+
+    let mut seen = HashSet::new();
+    let candidates = walk
+        .entries
+        .into_iter()
+        .filter(|entry| entry.metadata.is_file() && entry.metadata.len() > 0)
+        .filter(|entry| file_id(&entry.metadata).is_none_or(|id| seen.insert(id)))
+        .map(|entry| Candidate {
+            size: entry.metadata.len(),
+            path: entry.path,
+        })
+        .collect();
+
+    let groups: Vec<DuplicateGroup> = find_duplicates(algorithm, candidates, &mut failed)
+        .into_iter()
+        .map(|(digest, members)| DuplicateGroup {
+            digest: match textual {
+                true => String::from_utf8_lossy(&digest).into_owned(),
+                false => encoding.encode(digest),
+            },
+            size: members[0].size,
+            wasted: members[0].size * (members.len() as u64 - 1),
+            paths: members
+                .iter()
+                .map(|member| member.path.display().to_string())
+                .collect(),
+        })
+        .collect();
+
+    let mut out = io::stdout().lock();
+    match script {
+        Some(script) => write_script(&mut out, &groups, script)?,
+        None => write_groups(&mut out, &groups, output_format)?,
+    }
+
+    eprintln!(
+        "{} duplicate group(s), {} bytes wasted",
+        groups.len(),
+        groups.iter().map(|group| group.wasted).sum::<u64>()
+    );
+
+    if failed > 0 {
+        eprintln!("WARNING: {} path(s) could not be read", failed);
+        return Err(anyhow!("Failed to read {} path(s)", failed));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use hashy::algorithms::md5::Md5;
+
+    use super::*;
+
+    fn candidates(root: &std::path::Path) -> Vec<Candidate> {
+        walk(&[root.to_path_buf()])
+            .entries
+            .into_iter()
+            .filter(|entry| entry.metadata.is_file())
+            .map(|entry| Candidate {
+                size: entry.metadata.len(),
+                path: entry.path,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn groups_duplicates() {
+        let root = tempfile::tempdir().unwrap();
+        let large = vec![7; 10_000];
+        let mut large_changed = large.clone();
+        // Same size and first 4 KiB
+        large_changed[9_000] = 8;
+
+        for (name, content) in [
+            ("a", &b"small"[..]),
+            ("b", b"small"),
+            ("c", b"other"),
+            ("d", &large),
+            ("e", &large),
+            ("f", &large),
+            ("g", &large_changed),
+        ] {
+            fs::write(root.path().join(name), content).unwrap();
+        }
+
+        let mut failed = 0;
+        let groups = find_duplicates(&Md5, candidates(root.path()), &mut failed);
+        assert_eq!(failed, 0);
+
+        let names: Vec<Vec<_>> = groups
+            .iter()
+            .map(|(_, members)| {
+                members
+                    .iter()
+                    .map(|member| member.path.file_name().unwrap().to_str().unwrap())
+                    .collect()
+            })
+            .collect();
+        assert_eq!(names, [vec!["d", "e", "f"], vec!["a", "b"]]);
+        assert_eq!(
+            groups[1].0,
+            Md5.digest(ChunkedStream::Bytes(b"small".to_vec())).unwrap()
+        );
+    }
+
+    #[test]
+    fn writes_scripts() {
+        let groups = [DuplicateGroup {
+            digest: "aa".to_string(),
+            size: 5,
+            wasted: 10,
+            paths: vec!["a".to_string(), "it's".to_string(), "c".to_string()],
+        }];
+
+        let mut out = vec![];
+        write_script(&mut out, &groups, DupesScript::Hardlink).unwrap();
+        let script = String::from_utf8(out).unwrap();
+        assert!(script.ends_with(
+            "# aa (5 bytes x 3), keeping 'a'\n\
+             ln -f -- 'a' 'it'\\''s'\n\
+             ln -f -- 'a' 'c'\n"
+        ));
+
+        let mut out = vec![];
+        write_script(&mut out, &groups, DupesScript::Delete).unwrap();
+        let script = String::from_utf8(out).unwrap();
+        assert!(script.ends_with("rm -- 'it'\\''s'\nrm -- 'c'\n"));
+    }
+}
@@ -8,6 +8,8 @@ pub enum Encoding {
     Hex(bool),
     /// Base64 encoding using the standard character set with '=' padding
     Base64,
+    /// Base32 encoding using the RFC 4648 character set with '=' padding
+    Base32,
     /// Binary encoding
     Binary,
 }
@@ -15,6 +17,16 @@ pub enum Encoding {
 #[derive(Debug)]
 pub struct InvalidEncoding(String);
 
+/// Errors from decoding malformed input, positions are byte offsets
+/// into the original (whitespace included) input
+#[derive(Debug, PartialEq)]
+pub enum DecodeError {
+    InvalidByte { byte: u8, position: usize },
+    InvalidLength(usize),
+}
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
 impl FromStr for Encoding {
     type Err = InvalidEncoding;
 
@@ -24,6 +36,7 @@ impl FromStr for Encoding {
             "hex" => Ok(Encoding::Hex(false)),
             "hex_upper" => Ok(Encoding::Hex(true)),
             "base64" => Ok(Encoding::Base64),
+            "base32" => Ok(Encoding::Base32),
             "bin" => Ok(Encoding::Binary),
             _ => Err(InvalidEncoding(enc_lower)),
         }
@@ -37,6 +50,7 @@ impl Encoding {
             Encoding::Hex(false) => hex::encode(bytes),
             Encoding::Hex(true) => hex::encode_upper(bytes),
             Encoding::Base64 => base64::encode(bytes),
+            Encoding::Base32 => encode_base32(&bytes),
             Encoding::Binary => bytes
                 .into_iter()
                 .map(|byte| {
@@ -55,6 +69,126 @@ impl Encoding {
                 .join(""),
         }
     }
+
+    /// Decodes the given encoded bytes according to the encoding type given.
+    ///
+    /// ASCII whitespace is ignored so that line-wrapped input can be decoded,
+    /// hex input is accepted in either case regardless of the variant.
+    pub fn decode(&self, input: &[u8]) -> Result<Vec<u8>, DecodeError> {
+        // Keep the original position of every significant byte for error reporting
+        let (positions, stripped): (Vec<usize>, Vec<u8>) = input
+            .iter()
+            .enumerate()
+            .filter(|(_, byte)| !byte.is_ascii_whitespace())
+            .unzip();
+        let invalid_byte = |index: usize| DecodeError::InvalidByte {
+            byte: stripped[index],
+            position: positions[index],
+        };
+
+        match self {
+            Encoding::Hex(_) => hex::decode(&stripped).map_err(|err| match err {
+                hex::FromHexError::InvalidHexCharacter { index, .. } => invalid_byte(index),
+                _ => DecodeError::InvalidLength(stripped.len()),
+            }),
+            Encoding::Base64 => base64::decode(&stripped).map_err(|err| match err {
+                base64::DecodeError::InvalidByte(index, _)
+                | base64::DecodeError::InvalidLastSymbol(index, _) => invalid_byte(index),
+                base64::DecodeError::InvalidLength => DecodeError::InvalidLength(stripped.len()),
+            }),
+            Encoding::Base32 => decode_base32(&stripped).map_err(|err| match err {
+                DecodeError::InvalidByte { position, .. } => invalid_byte(position),
+                length_err => length_err,
+            }),
+            Encoding::Binary => {
+                if !stripped.len().is_multiple_of(8) {
+                    return Err(DecodeError::InvalidLength(stripped.len()));
+                }
+
+                stripped
+                    .chunks(8)
+                    .enumerate()
+                    .map(|(i, bits)| {
+                        bits.iter()
+                            .enumerate()
+                            .try_fold(0u8, |byte, (j, bit)| match bit {
+                                b'0' => Ok(byte << 1),
+                                b'1' => Ok((byte << 1) | 1),
+                                _ => Err(invalid_byte(8 * i + j)),
+                            })
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+fn encode_base32(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(5) * 8);
+
+    for group in bytes.chunks(5) {
+        let mut buffer = [0u8; 5];
+        buffer[..group.len()].copy_from_slice(group);
+        let bits = buffer
+            .iter()
+            .fold(0u64, |acc, &byte| (acc << 8) | byte as u64);
+
+        // Number of symbols carrying data for 1-5 input bytes
+        let symbol_count = [2, 4, 5, 7, 8][group.len() - 1];
+        for i in 0..8 {
+            if i < symbol_count {
+                let index = (bits >> (35 - 5 * i)) & 0x1F;
+                out.push(BASE32_ALPHABET[index as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
+
+/// Decodes base32 with optional padding, positions in errors refer to the given slice.
+fn decode_base32(input: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    let data_len = input.len() - input.iter().rev().take_while(|&&b| b == b'=').count();
+    if !input.len().is_multiple_of(8) && input.len() != data_len {
+        return Err(DecodeError::InvalidLength(input.len()));
+    }
+    if matches!(data_len % 8, 1 | 3 | 6) {
+        return Err(DecodeError::InvalidLength(input.len()));
+    }
+
+    let mut out = Vec::with_capacity(data_len * 5 / 8);
+    let mut bits: u64 = 0;
+    let mut bit_count = 0;
+    for (position, &byte) in input[..data_len].iter().enumerate() {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&symbol| symbol == byte.to_ascii_uppercase())
+            .ok_or(DecodeError::InvalidByte { byte, position })?;
+
+        bits = (bits << 5) | value as u64;
+        bit_count += 5;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            out.push((bits >> bit_count) as u8);
+        }
+    }
+
+    Ok(out)
+}
+
+impl Display for Encoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Encoding::Hex(false) => "hex",
+            Encoding::Hex(true) => "hex_upper",
+            Encoding::Base64 => "base64",
+            Encoding::Base32 => "base32",
+            Encoding::Binary => "bin",
+        };
+        write!(f, "{}", name)
+    }
 }
 
 impl Display for InvalidEncoding {
@@ -63,6 +197,24 @@ impl Display for InvalidEncoding {
     }
 }
 
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::InvalidByte { byte, position } if byte.is_ascii_graphic() => {
+                write!(
+                    f,
+                    "invalid character '{}' at position {}",
+                    *byte as char, position
+                )
+            }
+            DecodeError::InvalidByte { byte, position } => {
+                write!(f, "invalid byte 0x{:02x} at position {}", byte, position)
+            }
+            DecodeError::InvalidLength(length) => write!(f, "invalid input length {}", length),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -93,6 +245,23 @@ mod test {
         );
     }
 
+    #[test]
+    fn encode_base32() {
+        assert_eq!(Encoding::Base32.encode(vec![]), "".to_string());
+        assert_eq!(
+            Encoding::Base32.encode(b"f".to_vec()),
+            "MY======".to_string()
+        );
+        assert_eq!(
+            Encoding::Base32.encode(b"foob".to_vec()),
+            "MZXW6YQ=".to_string()
+        );
+        assert_eq!(
+            Encoding::Base32.encode(b"foobar".to_vec()),
+            "MZXW6YTBOI======".to_string()
+        );
+    }
+
     #[test]
     fn encode_binary() {
         assert_eq!(Encoding::Binary.encode(vec![]), "".to_string());
@@ -101,4 +270,80 @@ mod test {
             "110001011101000100000011".to_string()
         );
     }
+
+    #[test]
+    fn decode_round_trip() {
+        let bytes = vec![0, 1, 2, 3, 25, 26, 129, 130, 255];
+
+        for encoding in [
+            Encoding::Hex(false),
+            Encoding::Hex(true),
+            Encoding::Base64,
+            Encoding::Base32,
+            Encoding::Binary,
+        ] {
+            let encoded = encoding.encode(bytes.clone());
+            assert_eq!(encoding.decode(encoded.as_bytes()), Ok(bytes.clone()));
+        }
+    }
+
+    #[test]
+    fn decode_ignores_whitespace() {
+        assert_eq!(
+            Encoding::Hex(false).decode(b"01 02\n03 1A\n"),
+            Ok(vec![1, 2, 3, 26])
+        );
+        assert_eq!(
+            Encoding::Base32.decode(b"mzxw6\nytboi"),
+            Ok(b"foobar".to_vec())
+        );
+        assert_eq!(
+            Encoding::Binary.decode(b"11000101 11010001\n"),
+            Ok(vec![197, 209])
+        );
+    }
+
+    #[test]
+    fn decode_malformed() {
+        assert_eq!(
+            Encoding::Hex(false).decode(b"01 0g"),
+            Err(DecodeError::InvalidByte {
+                byte: b'g',
+                position: 4
+            })
+        );
+        assert_eq!(
+            Encoding::Hex(false).decode(b"012"),
+            Err(DecodeError::InvalidLength(3))
+        );
+        assert_eq!(
+            Encoding::Base64.decode(b"AQID\nGR*B"),
+            Err(DecodeError::InvalidByte {
+                byte: b'*',
+                position: 7
+            })
+        );
+        assert_eq!(
+            Encoding::Base32.decode(b"MZXW6Y1="),
+            Err(DecodeError::InvalidByte {
+                byte: b'1',
+                position: 6
+            })
+        );
+        assert_eq!(
+            Encoding::Base32.decode(b"MZX"),
+            Err(DecodeError::InvalidLength(3))
+        );
+        assert_eq!(
+            Encoding::Binary.decode(b"0101"),
+            Err(DecodeError::InvalidLength(4))
+        );
+        assert_eq!(
+            Encoding::Binary.decode(b"0101 0102"),
+            Err(DecodeError::InvalidByte {
+                byte: b'2',
+                position: 8
+            })
+        );
+    }
 }
@@ -0,0 +1,60 @@
+use std::{fmt::Display, str::FromStr};
+
+/// Output formats for the algorithm list
+#[derive(Debug)]
+pub enum ListFormat {
+    /// Human readable tree grouped by family
+    Text,
+    /// JSON array with the metadata of every algorithm
+    Json,
+}
+
+/// Output formats for digest results
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    /// Digest only, prefixed by the path if there are multiple inputs
+    Text,
+    /// JSON array of records
+    Json,
+    /// CSV with a header row
+    Csv,
+    /// One JSON record per line
+    Ndjson,
+}
+
+#[derive(Debug)]
+pub struct InvalidFormat(String);
+
+impl FromStr for ListFormat {
+    type Err = InvalidFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let format_lower = s.to_lowercase();
+        match format_lower.as_str() {
+            "text" => Ok(ListFormat::Text),
+            "json" => Ok(ListFormat::Json),
+            _ => Err(InvalidFormat(format_lower)),
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = InvalidFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let format_lower = s.to_lowercase();
+        match format_lower.as_str() {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            _ => Err(InvalidFormat(format_lower)),
+        }
+    }
+}
+
+impl Display for InvalidFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid format: {}", self.0)
+    }
+}
@@ -0,0 +1,144 @@
+use std::fmt::{self, Display};
+
+/// Path pattern where `*` matches within a path component, `**` matches across
+/// components and `?` matches a single character. Patterns with a `/` match the
+/// whole path from the root, a leading `/` only anchoring them, while others match
+/// the file name at any depth. Patterns ending with a `/` would only match
+/// directories, which is not supported, so they are rejected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Glob {
+    /// Pattern as given, with its leading `/` if any
+    pattern: String,
+    anchored: bool,
+}
+
+/// Pattern ending with a `/`
+#[derive(Debug, PartialEq)]
+pub struct DirectoryPattern(String);
+
+impl Display for DirectoryPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "\"{}\" only matches directories, which is not supported, use \"{}\" instead",
+            self.0,
+            self.0.trim_end_matches('/')
+        )
+    }
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Result<Self, DirectoryPattern> {
+        if pattern.ends_with('/') {
+            return Err(DirectoryPattern(pattern.to_string()));
+        }
+
+        Ok(Self {
+            pattern: pattern.to_string(),
+            anchored: pattern.contains('/'),
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Whether the pattern matches a relative path using `/` as separator
+    pub fn matches(&self, path: &str) -> bool {
+        let pattern = self.pattern.trim_start_matches('/').as_bytes();
+        if self.anchored {
+            matches(pattern, path.as_bytes())
+        } else {
+            let name = path.rsplit('/').next().unwrap_or(path);
+            matches(pattern, name.as_bytes())
+        }
+    }
+}
+
+/// Whether every suffix of the pattern matches every suffix of the text is filled in
+/// from the ends, so that wildcards do not retry the same suffixes again and again
+fn matches(pattern: &[u8], text: &[u8]) -> bool {
+    let width = text.len() + 1;
+    let mut matched = vec![false; (pattern.len() + 1) * width];
+    matched[pattern.len() * width + text.len()] = true;
+
+    for i in (0..pattern.len()).rev() {
+        // Whether the text from `j` continues to the end of a directory after which
+        // `**/` matches again
+        let mut directory_skipped = false;
+        for j in (0..=text.len()).rev() {
+            let at = |i: usize, j: usize| matched[i * width + j];
+            let in_component = text.get(j).is_some_and(|&c| c != b'/');
+            matched[i * width + j] = match &pattern[i..] {
+                // `**/` matches no directory at all, or whole directories
+                [b'*', b'*', b'/', rest @ ..] => {
+                    directory_skipped = match text.get(j) {
+                        Some(b'/') => at(i, j + 1),
+                        Some(_) => directory_skipped,
+                        None => false,
+                    };
+                    at(pattern.len() - rest.len(), j) || directory_skipped
+                }
+                [b'*', b'*', rest @ ..] => {
+                    at(pattern.len() - rest.len(), j) || (j < text.len() && at(i, j + 1))
+                }
+                [b'*', ..] => at(i + 1, j) || (in_component && at(i, j + 1)),
+                [b'?', ..] => in_component && at(i + 1, j + 1),
+                [c, ..] => text.get(j) == Some(c) && at(i + 1, j + 1),
+                [] => unreachable!(),
+            };
+        }
+    }
+
+    matched[0]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn glob(pattern: &str) -> Glob {
+        Glob::new(pattern).unwrap()
+    }
+
+    #[test]
+    fn matches_globs() {
+        assert!(glob("*.rs").matches("src/cli/glob.rs"));
+        assert!(!glob("*.rs").matches("src/cli/glob.rsx"));
+        assert!(glob("src/*.rs").matches("src/lib.rs"));
+        assert!(!glob("src/*.rs").matches("src/cli/glob.rs"));
+        assert!(glob("src/**/*.rs").matches("src/cli/glob.rs"));
+        assert!(glob("src/**/*.rs").matches("src/lib.rs"));
+        assert!(glob("/target").matches("target"));
+        assert!(!glob("/target").matches("a/target"));
+        assert!(glob("target").matches("a/target"));
+        assert!(!glob("a/target").matches("b/a/target"));
+        assert!(glob("file-?").matches("a/file-1"));
+        assert!(!glob("file-?").matches("a/file-10"));
+        assert!(glob("**").matches("any/thing"));
+        assert!(glob("***").matches("any/thing"));
+        assert!(glob("a/**/b").matches("a/b"));
+        assert!(glob("a/**/b").matches("a/x/y/b"));
+        assert!(!glob("a/**/b").matches("a/xb"));
+        assert!(glob("**/b").matches("b"));
+        assert!(glob("**/b").matches("x/b"));
+        assert!(!glob("**/b").matches("xb"));
+        assert!(glob("").matches(""));
+    }
+
+    #[test]
+    fn rejects_directory_patterns() {
+        assert_eq!(
+            Glob::new("target/"),
+            Err(DirectoryPattern("target/".to_string()))
+        );
+        assert!(Glob::new("/").is_err());
+    }
+
+    #[test]
+    fn matches_without_backtracking() {
+        let name = "a".repeat(100);
+        assert!(!glob(&format!("{}b", "*a".repeat(20))).matches(&name));
+        assert!(!glob(&format!("{}b", "**a".repeat(20))).matches(&name));
+    }
+}
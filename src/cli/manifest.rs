@@ -0,0 +1,598 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File, Metadata},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::anyhow;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use hashy::chunked_stream::ChunkedStream;
+
+use super::{
+    glob::Glob,
+    parsers::{parse_algorithm, AlgorithmSpec},
+    walk::walk_filtered,
+};
+
+const MANIFEST_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EntryType {
+    File,
+    Symlink,
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharDevice,
+}
+
+/// File of a manifest, directories are implied by the paths of their files
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Path relative to the root of the tree, with `/` as separator
+    pub path: String,
+    #[serde(rename = "type")]
+    pub entry_type: EntryType,
+    /// Permission bits in octal, such as 0644, None for symlinks and on platforms without them
+    pub mode: Option<String>,
+    pub size: u64,
+    /// Digest of the content of regular files, special files are never read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+    /// Target of symlinks, which are not followed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+}
+
+/// Digests, sizes and modes of every file under a directory, sorted by path
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    pub algorithm: String,
+    /// Patterns the files were selected with, reused when diffing against a live tree
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// Include and exclude patterns selecting the files of a manifest
+#[derive(Debug, Clone, Default)]
+pub struct Filters {
+    pub include: Vec<Glob>,
+    pub exclude: Vec<Glob>,
+}
+
+impl Filters {
+    fn from_manifest(manifest: &Manifest) -> anyhow::Result<Self> {
+        let parse = |globs: &[String]| {
+            globs
+                .iter()
+                .map(|glob| {
+                    Glob::new(glob).map_err(|err| anyhow!("Invalid manifest pattern! {}", err))
+                })
+                .collect::<anyhow::Result<_>>()
+        };
+
+        Ok(Self {
+            include: parse(&manifest.include)?,
+            exclude: parse(&manifest.exclude)?,
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Excluded paths are skipped along with their contents while walking
+    pub fn excludes(&self, path: &str) -> bool {
+        self.exclude.iter().any(|glob| glob.matches(path))
+    }
+
+    pub fn includes(&self, path: &str) -> bool {
+        self.include.is_empty() || self.include.iter().any(|glob| glob.matches(path))
+    }
+}
+
+/// Path relative to the root with `/` separators
+pub fn relative_path(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(unix)]
+fn entry_type_and_mode(metadata: &Metadata) -> Option<(EntryType, Option<String>)> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    let file_type = metadata.file_type();
+    let entry_type = if file_type.is_file() {
+        EntryType::File
+    } else if file_type.is_symlink() {
+        return Some((EntryType::Symlink, None));
+    } else if file_type.is_fifo() {
+        EntryType::Fifo
+    } else if file_type.is_socket() {
+        EntryType::Socket
+    } else if file_type.is_block_device() {
+        EntryType::BlockDevice
+    } else if file_type.is_char_device() {
+        EntryType::CharDevice
+    } else {
+        return None;
+    };
+
+    let mode = format!("{:04o}", metadata.permissions().mode() & 0o7777);
+    Some((entry_type, Some(mode)))
+}
+
+#[cfg(not(unix))]
+fn entry_type_and_mode(metadata: &Metadata) -> Option<(EntryType, Option<String>)> {
+    let file_type = metadata.file_type();
+    if file_type.is_file() {
+        Some((EntryType::File, None))
+    } else if file_type.is_symlink() {
+        Some((EntryType::Symlink, None))
+    } else {
+        None
+    }
+}
+
+/// Walks the directory and digests its regular files in parallel. Paths which
+/// cannot be read are returned separately, the manifest leaves them out.
+pub fn create_manifest(
+    directory: &Path,
+    algorithm: &AlgorithmSpec,
+    filters: &Filters,
+) -> (Manifest, Vec<(PathBuf, io::Error)>) {
+    let walk = walk_filtered(
+        &[directory.to_path_buf()],
+        &|path| filters.excludes(&relative_path(directory, path)),
+        false,
+    );
+    let mut errors = walk.errors;
+
+    let files: Vec<_> = walk
+        .entries
+        .into_iter()
+        .filter(|entry| !entry.metadata.is_dir())
+        .filter_map(|entry| {
+            let path = relative_path(directory, &entry.path);
+            let (entry_type, mode) = entry_type_and_mode(&entry.metadata)?;
+            filters
+                .includes(&path)
+                .then_some((entry, path, entry_type, mode))
+        })
+        .collect();
+
+    let hasher = algorithm.build();
+    let results: Vec<_> = files
+        .into_par_iter()
+        .map(|(entry, path, entry_type, mode)| {
+            let (digest, target) = match entry_type {
+                EntryType::File => {
+                    let digest = File::open(&entry.path)
+                        .map(ChunkedStream::from)
+                        .and_then(|data| hasher.digest(data));
+                    match digest {
+                        Ok(digest) => (Some(encode_digest(algorithm, digest)), None),
+                        Err(err) => return Err((entry.path, err)),
+                    }
+                }
+                EntryType::Symlink => match fs::read_link(&entry.path) {
+                    Ok(target) => (None, Some(target.to_string_lossy().into_owned())),
+                    Err(err) => return Err((entry.path, err)),
+                },
+                _ => (None, None),
+            };
+
+            Ok(ManifestEntry {
+                path,
+                entry_type,
+                mode,
+                size: match entry_type {
+                    EntryType::File | EntryType::Symlink => entry.metadata.len(),
+                    _ => 0,
+                },
+                digest,
+                target,
+            })
+        })
+        .collect();
+
+    let mut entries = vec![];
+    for result in results {
+        match result {
+            Ok(entry) => entries.push(entry),
+            Err(error) => errors.push(error),
+        }
+    }
+    entries.sort_by(|first, second| first.path.cmp(&second.path));
+
+    let manifest = Manifest {
+        version: MANIFEST_VERSION,
+        algorithm: algorithm.name(),
+        include: filters
+            .include
+            .iter()
+            .map(|glob| glob.as_str().to_string())
+            .collect(),
+        exclude: filters
+            .exclude
+            .iter()
+            .map(|glob| glob.as_str().to_string())
+            .collect(),
+        entries,
+    };
+    (manifest, errors)
+}
+
+/// Textual digests are stored as-is, others in lowercase hex
+fn encode_digest(algorithm: &AlgorithmSpec, digest: Vec<u8>) -> String {
+    match algorithm.entry.textual {
+        true => String::from_utf8_lossy(&digest).into_owned(),
+        false => hex::encode(digest),
+    }
+}
+
+/// Prints the paths which could not be read, failing if there are any
+pub fn report_errors(errors: &[(PathBuf, io::Error)]) -> anyhow::Result<()> {
+    for (path, err) in errors {
+        eprintln!("{}: {}", path.display(), err);
+    }
+
+    if !errors.is_empty() {
+        eprintln!("WARNING: {} path(s) could not be read", errors.len());
+        return Err(anyhow!("Failed to read {} path(s)", errors.len()));
+    }
+
+    Ok(())
+}
+
+/// Writes the manifest of a directory to stdout as pretty-printed JSON, which
+/// keeps one field per line so that manifests diff well in version control
+pub fn print_manifest(
+    directory: &Path,
+    algorithm: &AlgorithmSpec,
+    filters: &Filters,
+) -> anyhow::Result<()> {
+    if !directory.is_dir() {
+        return Err(anyhow!("Not a directory! {}", directory.display()));
+    }
+
+    let (manifest, errors) = create_manifest(directory, algorithm, filters);
+    writeln!(
+        io::stdout().lock(),
+        "{}",
+        serde_json::to_string_pretty(&manifest)?
+    )?;
+
+    report_errors(&errors)
+}
+
+#[derive(Debug, PartialEq)]
+enum Change<'a> {
+    Added(&'a str),
+    Removed(&'a str),
+    /// Type, size, digest or symlink target changed
+    Modified(&'a str),
+    /// Only the permission bits changed
+    ModeChanged(&'a str),
+    Moved {
+        from: &'a str,
+        to: &'a str,
+    },
+}
+
+impl Change<'_> {
+    /// Path the change is listed under
+    fn path(&self) -> &str {
+        match self {
+            Change::Added(path)
+            | Change::Removed(path)
+            | Change::Modified(path)
+            | Change::ModeChanged(path) => path,
+            Change::Moved { from, .. } => from,
+        }
+    }
+}
+
+/// Lists the changes from the first manifest to the second, by path. Files removed
+/// from one path and added at another with the same digest are reported as moves.
+fn diff_manifests<'a>(first: &'a Manifest, second: &'a Manifest) -> Vec<Change<'a>> {
+    let old: BTreeMap<_, _> = first
+        .entries
+        .iter()
+        .map(|entry| (&entry.path, entry))
+        .collect();
+    let new: BTreeMap<_, _> = second
+        .entries
+        .iter()
+        .map(|entry| (&entry.path, entry))
+        .collect();
+
+    let mut changes = vec![];
+    let mut removed = vec![];
+    for (path, entry) in &old {
+        match new.get(path) {
+            None => removed.push(*entry),
+            Some(other)
+                if (other.entry_type, other.size, &other.digest, &other.target)
+                    != (entry.entry_type, entry.size, &entry.digest, &entry.target) =>
+            {
+                changes.push(Change::Modified(path))
+            }
+            Some(other) if other.mode != entry.mode => changes.push(Change::ModeChanged(path)),
+            Some(_) => {}
+        }
+    }
+
+    // Added files, by digest so that removed files can be matched to them
+    let mut added: BTreeMap<(u64, &str), Vec<&str>> = BTreeMap::new();
+    let mut added_without_digest = vec![];
+    for (path, entry) in new.iter().filter(|(path, _)| !old.contains_key(*path)) {
+        match &entry.digest {
+            Some(digest) => added.entry((entry.size, digest)).or_default().push(path),
+            None => added_without_digest.push(path.as_str()),
+        }
+    }
+
+    for entry in removed {
+        let moved_to = entry.digest.as_ref().and_then(|digest| {
+            let paths = added.get_mut(&(entry.size, digest.as_str()))?;
+            (!paths.is_empty()).then(|| paths.remove(0))
+        });
+
+        changes.push(match moved_to {
+            Some(to) => Change::Moved {
+                from: &entry.path,
+                to,
+            },
+            None => Change::Removed(&entry.path),
+        });
+    }
+
+    changes.extend(
+        added
+            .into_values()
+            .flatten()
+            .chain(added_without_digest)
+            .map(Change::Added),
+    );
+    changes.sort_by(|first, second| first.path().cmp(second.path()));
+    changes
+}
+
+/// Reads a manifest written by `--manifest`
+fn read_manifest(path: &Path) -> anyhow::Result<Manifest> {
+    let content = fs::read_to_string(path)
+        .map_err(|err| anyhow!("Invalid path! {}: {}", path.display(), err))?;
+    let manifest: Manifest = serde_json::from_str(&content)
+        .map_err(|err| anyhow!("Invalid manifest {}: {}", path.display(), err))?;
+
+    if manifest.version != MANIFEST_VERSION {
+        return Err(anyhow!(
+            "Unsupported manifest version {} in {}",
+            manifest.version,
+            path.display()
+        ));
+    }
+
+    Ok(manifest)
+}
+
+/// Side of a diff, either a manifest file or a live directory tree
+enum Side {
+    Manifest(Manifest),
+    Directory(PathBuf),
+}
+
+impl Side {
+    fn read(path: PathBuf) -> anyhow::Result<Self> {
+        match path.is_dir() {
+            true => Ok(Side::Directory(path)),
+            false => read_manifest(&path).map(Side::Manifest),
+        }
+    }
+}
+
+/// Compares two manifests, or a manifest and a directory tree, or two directory
+/// trees, and prints the added, removed, modified and moved files.
+///
+/// Trees are hashed with the algorithm and filters of the manifest they are compared
+/// to, unless given. Fails if anything changed.
+pub fn diff(
+    first: PathBuf,
+    second: PathBuf,
+    algorithm: Option<AlgorithmSpec>,
+    filters: &Filters,
+) -> anyhow::Result<()> {
+    let (first, second) = (Side::read(first)?, Side::read(second)?);
+
+    let reference = match (&first, &second) {
+        (Side::Manifest(manifest), _) | (_, Side::Manifest(manifest)) => Some(manifest),
+        _ => None,
+    };
+    let algorithm = match (algorithm, reference) {
+        (Some(algorithm), _) => algorithm,
+        (None, Some(manifest)) => parse_algorithm(&manifest.algorithm)
+            .map_err(|err| anyhow!("Invalid algorithm! {}", err))?,
+        (None, None) => {
+            return Err(anyhow!(
+                "An algorithm is required to compare two directories"
+            ))
+        }
+    };
+    let filters = match (filters.is_empty(), reference) {
+        (true, Some(manifest)) => Filters::from_manifest(manifest)?,
+        _ => filters.clone(),
+    };
+
+    let mut errors = vec![];
+    let mut load = |side: Side| match side {
+        Side::Manifest(manifest) => manifest,
+        Side::Directory(directory) => {
+            let (manifest, walk_errors) = create_manifest(&directory, &algorithm, &filters);
+            errors.extend(walk_errors);
+            manifest
+        }
+    };
+    let (first, second) = (load(first), load(second));
+
+    if first.algorithm != second.algorithm {
+        return Err(anyhow!(
+            "Cannot compare digests of different algorithms ({} and {})",
+            first.algorithm,
+            second.algorithm
+        ));
+    }
+
+    let changes = diff_manifests(&first, &second);
+    let mut out = io::stdout().lock();
+    for change in &changes {
+        match change {
+            Change::Added(path) => writeln!(out, "{}: ADDED", path)?,
+            Change::Removed(path) => writeln!(out, "{}: REMOVED", path)?,
+            Change::Modified(path) => writeln!(out, "{}: MODIFIED", path)?,
+            Change::ModeChanged(path) => writeln!(out, "{}: MODE CHANGED", path)?,
+            Change::Moved { from, to } => writeln!(out, "{}: MOVED TO {}", from, to)?,
+        }
+    }
+
+    report_errors(&errors)?;
+    if !changes.is_empty() {
+        eprintln!("WARNING: {} change(s)", changes.len());
+        return Err(anyhow!("Trees differ"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(path: &str, digest: &str, mode: &str) -> ManifestEntry {
+        ManifestEntry {
+            path: path.to_string(),
+            entry_type: EntryType::File,
+            mode: Some(mode.to_string()),
+            size: 1,
+            digest: Some(digest.to_string()),
+            target: None,
+        }
+    }
+
+    fn manifest(entries: Vec<ManifestEntry>) -> Manifest {
+        Manifest {
+            version: MANIFEST_VERSION,
+            algorithm: "md5".to_string(),
+            include: vec![],
+            exclude: vec![],
+            entries,
+        }
+    }
+
+    #[test]
+    fn diffs_manifests() {
+        let first = manifest(vec![
+            entry("kept", "aa", "0644"),
+            entry("modified", "bb", "0644"),
+            entry("chmod", "cc", "0644"),
+            entry("old-name", "dd", "0644"),
+            entry("removed", "ee", "0644"),
+        ]);
+        let second = manifest(vec![
+            entry("kept", "aa", "0644"),
+            entry("modified", "ff", "0644"),
+            entry("chmod", "cc", "0755"),
+            entry("new-name", "dd", "0644"),
+            entry("added", "ee0", "0644"),
+        ]);
+
+        assert_eq!(
+            diff_manifests(&first, &second),
+            [
+                Change::Added("added"),
+                Change::ModeChanged("chmod"),
+                Change::Modified("modified"),
+                Change::Moved {
+                    from: "old-name",
+                    to: "new-name"
+                },
+                Change::Removed("removed"),
+            ]
+        );
+        assert!(diff_manifests(&first, &first).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn creates_manifests() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("src/.git")).unwrap();
+        fs::write(root.path().join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(root.path().join("src/.git/HEAD"), "ref").unwrap();
+        fs::write(root.path().join("run.sh"), "#!/bin/sh").unwrap();
+        fs::set_permissions(
+            root.path().join("run.sh"),
+            fs::Permissions::from_mode(0o755),
+        )
+        .unwrap();
+        symlink("src/main.rs", root.path().join("link")).unwrap();
+
+        let algorithm = parse_algorithm("md5").unwrap();
+        let filters = Filters {
+            include: vec![],
+            exclude: vec![Glob::new(".git").unwrap()],
+        };
+        let (manifest, errors) = create_manifest(root.path(), &algorithm, &filters);
+        assert!(errors.is_empty());
+
+        let paths: Vec<_> = manifest
+            .entries
+            .iter()
+            .map(|entry| entry.path.as_str())
+            .collect();
+        assert_eq!(paths, ["link", "run.sh", "src/main.rs"]);
+        assert_eq!(manifest.entries[0].entry_type, EntryType::Symlink);
+        assert_eq!(manifest.entries[0].target.as_deref(), Some("src/main.rs"));
+        assert_eq!(manifest.entries[1].mode.as_deref(), Some("0755"));
+        assert_eq!(
+            manifest.entries[2].digest.as_deref(),
+            Some(hex::encode(
+                parse_algorithm("md5")
+                    .unwrap()
+                    .build()
+                    .digest(ChunkedStream::from("fn main() {}".to_string()))
+                    .unwrap()
+            ))
+            .as_deref()
+        );
+        assert_eq!(manifest.exclude, [".git"]);
+
+        // Round trip through JSON
+        let json = serde_json::to_string_pretty(&manifest).unwrap();
+        assert_eq!(serde_json::from_str::<Manifest>(&json).unwrap(), manifest);
+
+        let filters = Filters {
+            include: vec![Glob::new("*.rs").unwrap()],
+            exclude: vec![],
+        };
+        let (manifest, _) = create_manifest(root.path(), &algorithm, &filters);
+        let paths: Vec<_> = manifest
+            .entries
+            .iter()
+            .map(|entry| entry.path.as_str())
+            .collect();
+        assert_eq!(paths, ["src/main.rs"]);
+    }
+}
@@ -2,5 +2,22 @@ pub mod command;
 pub mod opts;
 
 mod algorithms;
+mod bench;
+mod bencode;
+mod cache;
+mod check;
+mod checkpoint;
+mod dirhash;
+mod distances;
+mod dupes;
 mod encoding;
+mod formats;
+mod glob;
+mod manifest;
+mod output;
 mod parsers;
+mod pieces;
+mod progress;
+mod torrent;
+mod walk;
+mod watch;
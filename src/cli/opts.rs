@@ -1,29 +1,71 @@
 use std::{
-    fs::File,
+    fs,
     io::{self, stdin, IsTerminal},
     path::PathBuf,
 };
 
-use structopt::StructOpt;
+use structopt::{clap::ArgGroup, StructOpt};
 
-use crate::chunked_stream::ChunkedStream;
+use hashy::chunking::ChunkSizes;
 
-use super::{command::Command, encoding::Encoding, parsers::parse_algorithm};
+use super::{
+    algorithms::ALGORITHMS,
+    bench::BenchOptions,
+    cache::{CacheStore, DigestCache},
+    checkpoint::Checkpoints,
+    command::{Command, Input, InputSource},
+    dirhash::{DirhashOptions, VCS_DIRECTORIES},
+    dupes::DupesScript,
+    encoding::Encoding,
+    formats::{ListFormat, OutputFormat},
+    glob::Glob,
+    manifest::Filters,
+    parsers::{parse_algorithm, parse_size, AlgorithmSpec},
+    pieces::PieceSplit,
+};
+
+/// Options which select what is done instead of digesting the inputs, at most one of which
+/// can be given
+const MODES: &[&str] = &[
+    "list",
+    "check",
+    "compare-pieces",
+    "compare-fuzzy",
+    "distances",
+    "dupes",
+    "watch",
+    "manifest",
+    "manifest-diff",
+    "dirhash",
+    "dirhash-explain",
+    "torrent",
+    "bench",
+];
 
 #[derive(Debug, StructOpt)]
+#[structopt(group = ArgGroup::with_name("mode").args(MODES))]
 pub struct Opts {
     /// Prints the list of all the available hashing algorithms
     #[structopt(short, long)]
     pub list: bool,
 
-    /// Chosen hashing algorithm name
-    #[structopt(required_unless = "list")]
+    /// Format of the algorithm list (text or json)
+    #[structopt(long, default_value = "text")]
+    pub format: ListFormat,
+
+    /// Chosen hashing algorithm name. Optional with --check and --manifest-diff, not
+    /// taken by modes which do not hash with a chosen algorithm.
+    #[structopt(
+        required_unless_one = &["list", "check", "compare-pieces", "compare-fuzzy", "distances", "manifest-diff", "torrent", "bench"],
+        conflicts_with_all = &["list", "compare-pieces", "compare-fuzzy", "distances", "torrent", "bench"]
+    )]
     pub algorithm: Option<String>,
 
-    /// Path to file to read from, cannot be a directory.
+    /// Paths to files to read from, cannot be directories.
     /// Defaults to stdin if not present.
-    #[structopt()]
-    pub file_path: Option<PathBuf>,
+    /// With --dupes and --watch, files and directory trees to go through.
+    #[structopt(conflicts_with_all = &["check", "manifest", "manifest-diff", "dirhash", "dirhash-explain"])]
+    pub file_paths: Vec<PathBuf>,
 
     /// Text to generate hash from.
     /// Use this option to pass a text instead of through stdin.
@@ -34,37 +76,314 @@ pub struct Opts {
     #[structopt(short, long, default_value = "hex")]
     pub encoding: Encoding,
 
-    /// Show verbose output
+    /// Encoding of the input (text, stdin or file), which is decoded before hashing.
+    /// Input is hashed as-is if not present.
+    #[structopt(short, long)]
+    pub input_encoding: Option<Encoding>,
+
+    /// Reads digests and paths from a checksum file ("-" for stdin) and verifies them.
+    /// GNU, BSD-tagged and SFV lines are detected automatically, the algorithm
+    /// is required for GNU lines only.
+    #[structopt(short, long)]
+    pub check: Option<PathBuf>,
+
+    /// Writes text output in the BSD-style "TAG (path) = digest" format, with hex digests
+    /// of files or stdin which --check can read back
+    #[structopt(long, conflicts_with = "text")]
+    pub tag: bool,
+
+    /// Output format of the digest results (text, json, csv or ndjson)
+    #[structopt(long, default_value = "text")]
+    pub output_format: OutputFormat,
+
+    /// Skips this many bytes at the start of every input, such as 4K or 1MiB.
+    /// Files are seeked into, other inputs are read through.
+    #[structopt(long, default_value = "0", parse(try_from_str = parse_size))]
+    pub offset: u64,
+
+    /// Only hashes up to this many bytes of every input (after the offset), such as 4K or 1MiB
+    #[structopt(long, parse(try_from_str = parse_size))]
+    pub length: Option<u64>,
+
+    /// Also digests every piece of this many bytes separately (such as 1MiB), printed as
+    /// "start-end digest" lines (end exclusive) before the digest of the whole input
+    #[structopt(long, parse(try_from_str = parse_piece_size))]
+    pub piece_size: Option<usize>,
+
+    /// Splits inputs into content-defined (FastCDC) chunks of this average size, or
+    /// "min:avg:max" sizes, digested like --piece-size pieces. Deduplication statistics
+    /// across all inputs are printed to stderr.
+    #[structopt(long, parse(try_from_str = parse_chunk_sizes))]
+    pub cdc: Option<ChunkSizes>,
+
+    /// Compares two piece lists written with --piece-size ("-" for stdin)
+    /// and prints the byte ranges which differ
+    #[structopt(long, number_of_values = 2, value_names = &["first", "second"])]
+    pub compare_pieces: Option<Vec<PathBuf>>,
+
+    /// Scores the similarity of two fuzzy hash signatures (ssdeep) from 0 to 100
+    #[structopt(long, number_of_values = 2, value_names = &["first", "second"])]
+    pub compare_fuzzy: Option<Vec<String>>,
+
+    /// Digests every file with TLSH and prints the distance between every pair of them,
+    /// from 0 for identical (or nearly) files
+    #[structopt(long, min_values = 2)]
+    pub distances: Option<Vec<PathBuf>>,
+
+    /// Finds duplicate files among the given files and directory trees (the current
+    /// directory by default), comparing sizes, then digests of the first 4 KiB and
+    /// finally full digests
+    #[structopt(long)]
+    pub dupes: bool,
+
+    /// With --dupes, prints a shell script which replaces duplicates with hardlinks
+    /// (hardlink) or deletes them (delete), keeping the first file of every group
+    #[structopt(long, requires = "dupes")]
+    pub dupes_script: Option<DupesScript>,
+
+    /// Keeps watching the given files and directory trees (the current directory by
+    /// default) with inotify, and prints their digests again whenever they change,
+    /// once writes have settled. Linux only.
+    #[structopt(long)]
+    pub watch: bool,
+
+    /// Writes a JSON manifest of every file under the directory to stdout: digest,
+    /// size, mode and type, sorted by path. Symlinks are recorded with their target
+    /// rather than followed, and special files are never read.
+    #[structopt(long)]
+    pub manifest: Option<PathBuf>,
+
+    /// Compares two manifests, or a manifest and a directory, or two directories,
+    /// and prints the files which were added, removed, modified or moved. Directories
+    /// are hashed with the algorithm and patterns of the other manifest by default.
+    #[structopt(long, number_of_values = 2, value_names = &["first", "second"])]
+    pub manifest_diff: Option<Vec<PathBuf>>,
+
+    /// Only includes files matching one of these patterns in manifests, such as "*.rs" or
+    /// "src/**". "*" stays within a directory while "**" does not. Patterns with a "/"
+    /// match from the root (like "/target"), others match file names at any depth.
+    #[structopt(long, number_of_values = 1, parse(try_from_str = Glob::new))]
+    pub include: Vec<Glob>,
+
+    /// Leaves out files and directories matching one of these patterns from manifests
+    #[structopt(long, number_of_values = 1, parse(try_from_str = Glob::new))]
+    pub exclude: Vec<Glob>,
+
+    /// Prints a single digest for the directory, which only depends on the sorted relative
+    /// paths, types, executable bits and contents of its entries. Respects --include and --exclude.
+    #[structopt(long)]
+    pub dirhash: Option<PathBuf>,
+
+    /// With --dirhash, prints the canonical listing which the directory digest is computed over
+    #[structopt(long, requires = "dirhash")]
+    pub dirhash_listing: bool,
+
+    /// Prints the entries which differ between two directories, or listings saved with
+    /// --dirhash-listing, to explain why their dirhashes differ
+    #[structopt(long, number_of_values = 2, value_names = &["first", "second"])]
+    pub dirhash_explain: Option<Vec<PathBuf>>,
+
+    /// Hashes the targets of symlinks in --dirhash instead of the links themselves
+    #[structopt(long)]
+    pub follow_symlinks: bool,
+
+    /// Leaves version control directories (.git, .hg, .svn, ...) out of --dirhash
+    #[structopt(long)]
+    pub ignore_vcs: bool,
+
+    /// Prints the v1 (SHA-1) and v2 (SHA-256) infohashes of a .torrent file
+    #[structopt(long)]
+    pub torrent: Option<PathBuf>,
+
+    /// Verifies the payload of the --torrent file in this directory against its
+    /// piece hashes (v1) and per-file merkle roots (v2)
+    #[structopt(long, requires = "torrent")]
+    pub verify: Option<PathBuf>,
+
+    /// Reuses the digests of files which did not change (same device, inode, size and
+    /// modification time) from this cache file, and records new ones in it
+    #[structopt(long, conflicts_with = "cache-xattr")]
+    pub cache: Option<PathBuf>,
+
+    /// Caches digests in a user.hashy.<algorithm> extended attribute of every file
+    /// instead of a cache file (Linux only)
+    #[structopt(long)]
+    pub cache_xattr: bool,
+
+    /// Recomputes this percentage of cache hits to check that the cache can be trusted,
+    /// failing if any cached digest was wrong
+    #[structopt(long, default_value = "0", parse(try_from_str = parse_percent))]
+    pub verify_cache: u8,
+
+    /// Benchmarks these algorithms over in-memory buffers ("all" for every algorithm
+    /// without a parameter or key), reporting MB/s, its variation and estimated cycles per byte
+    #[structopt(long, min_values = 1)]
+    pub bench: Option<Vec<String>>,
+
+    /// Sizes of the buffers digested by --bench
+    #[structopt(long, use_delimiter = true, default_value = "64,4K,1M", parse(try_from_str = parse_buffer_size))]
+    pub bench_sizes: Vec<usize>,
+
+    /// Timed samples of every algorithm and size with --bench, after a warm-up sample
+    #[structopt(long, default_value = "10", parse(try_from_str = parse_repetitions))]
+    pub bench_repetitions: usize,
+
+    /// Saves the --bench results as a JSON baseline
+    #[structopt(long, requires = "bench")]
+    pub save_baseline: Option<PathBuf>,
+
+    /// Compares the --bench results to a saved baseline, failing if any throughput
+    /// dropped by more than 10% and the noise of the runs
+    #[structopt(long, requires = "bench")]
+    pub baseline: Option<PathBuf>,
+
+    /// Saves the state of hashing a single file to this checkpoint file as it goes and when
+    /// interrupted, and resumes from it if it exists. Removed once the file is hashed.
+    #[structopt(long)]
+    pub checkpoint: Option<PathBuf>,
+
+    /// Bytes hashed between two checkpoints, 0 to only save one when interrupted
+    #[structopt(long, default_value = "1G", parse(try_from_str = parse_size))]
+    pub checkpoint_interval: u64,
+
+    /// Maps input files into memory instead of reading them. The process is killed if a file
+    /// is truncated while it is hashed, so only use it for files which do not change.
+    #[structopt(long, conflicts_with_all = &["watch", "checkpoint"])]
+    pub mmap: bool,
+
+    /// Shows the bytes read, throughput and time left while hashing on stderr, if it is a terminal
+    #[structopt(long)]
+    pub progress: bool,
+
+    /// Show verbose output, printed to stderr
     #[structopt(short, long)]
     pub verbose: bool,
 }
 
 pub enum CommandParseError {
-    FileDoesNotExist,
-    PathIsDirectory,
-    InvalidPath(io::Error),
+    FileDoesNotExist(PathBuf),
+    PathIsDirectory(PathBuf),
+    InvalidPath(PathBuf, io::Error),
     InvalidAlgorithm(String),
     InvalidEnvironment,
+    ConflictingOptions(&'static str),
+    OffsetOutOfRange(u64),
+    InvalidCheckpoint(PathBuf, io::Error),
     NotImplemented,
 }
 
-fn get_data(opts: &Opts) -> Result<ChunkedStream, CommandParseError> {
-    // Use file_path first
-    if let Some(path) = &opts.file_path {
-        let file = File::open(&path).map_err(|err| match err.kind() {
-            io::ErrorKind::NotFound => CommandParseError::FileDoesNotExist,
-            _ => CommandParseError::InvalidPath(err),
-        })?;
+fn parse_piece_size(input: &str) -> Result<usize, String> {
+    match parse_size(input) {
+        Ok(0) => Err("Piece size cannot be 0".to_string()),
+        Ok(size) => usize::try_from(size).map_err(|err| err.to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn parse_buffer_size(input: &str) -> Result<usize, String> {
+    match parse_size(input) {
+        Ok(0) => Err("Buffer size cannot be 0".to_string()),
+        Ok(size) => usize::try_from(size).map_err(|err| err.to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
 
-        if path.is_dir() {
-            return Err(CommandParseError::PathIsDirectory);
+fn parse_repetitions(input: &str) -> Result<usize, String> {
+    match input.parse() {
+        Ok(0) | Err(_) => Err(format!("Invalid number of repetitions {}", input)),
+        Ok(repetitions) => Ok(repetitions),
+    }
+}
+
+fn parse_percent(input: &str) -> Result<u8, String> {
+    match input.trim_end_matches('%').parse::<u8>() {
+        Ok(percent) if percent <= 100 => Ok(percent),
+        _ => Err(format!("Invalid percentage {}, expected 0 to 100", input)),
+    }
+}
+
+/// Parses an average chunk size, with a quarter of it as the minimum and four
+/// times as much as the maximum, or all three sizes as `min:avg:max`
+fn parse_chunk_sizes(input: &str) -> Result<ChunkSizes, String> {
+    let sizes = input
+        .split(':')
+        .map(|size| {
+            parse_size(size)
+                .map_err(|err| err.to_string())
+                .and_then(|size| usize::try_from(size).map_err(|err| err.to_string()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match sizes[..] {
+        [avg] => ChunkSizes::from_average(avg),
+        [min, avg, max] => ChunkSizes::new(min, avg, max),
+        _ => return Err("Expected an average chunk size or min:avg:max".to_string()),
+    }
+    .ok_or_else(|| {
+        format!(
+            "Invalid chunk sizes {}, expected min <= avg <= max with an average of at least {}",
+            input,
+            ChunkSizes::MIN_AVERAGE
+        )
+    })
+}
+
+/// Restricts the input to `length` bytes from `offset`, which are only selected once
+/// it is opened. Offsets past the end of inputs of known size are rejected.
+fn select_range(
+    input: &mut Input,
+    offset: u64,
+    length: Option<u64>,
+) -> Result<(), CommandParseError> {
+    if input.size.is_some_and(|size| offset > size) {
+        return Err(CommandParseError::OffsetOutOfRange(offset));
+    }
+
+    input.offset = offset;
+    input.length = length;
+    input.size = input
+        .size
+        .map(|size| (size - offset).min(length.unwrap_or(u64::MAX)));
+    Ok(())
+}
+
+/// Checks the inputs, starting `offset` bytes in: the --offset option, or where hashing
+/// resumes from a checkpoint. Files are only opened once they are digested.
+fn get_inputs(opts: &Opts, offset: u64) -> Result<Vec<Input>, CommandParseError> {
+    // The range of decoded inputs is only selected once they are decoded, when digested
+    let with_range = |mut input: Input| {
+        if opts.input_encoding.is_none() {
+            select_range(&mut input, offset, opts.length)?;
         }
+        Ok(input)
+    };
+
+    // Use file_paths first
+    if !opts.file_paths.is_empty() {
+        opts.file_paths
+            .iter()
+            .map(|path| {
+                let metadata = fs::metadata(path).map_err(|err| match err.kind() {
+                    io::ErrorKind::NotFound => CommandParseError::FileDoesNotExist(path.clone()),
+                    _ => CommandParseError::InvalidPath(path.clone(), err),
+                })?;
 
-        return Ok(ChunkedStream::from(file));
+                if metadata.is_dir() {
+                    return Err(CommandParseError::PathIsDirectory(path.clone()));
+                }
+
+                let mut input = Input::new(InputSource::File(path.clone()), Some(metadata.len()));
+                input.mmap = opts.mmap;
+                with_range(input)
+            })
+            .collect()
     }
     // Otherwise use "text" option
     else if let Some(text) = &opts.text {
-        return Ok(ChunkedStream::from(text.clone()));
+        Ok(vec![with_range(Input::new(
+            InputSource::Text(text.clone()),
+            Some(text.len() as u64),
+        ))?])
     }
     // Otherwise use stdin
     else {
@@ -72,7 +391,7 @@ fn get_data(opts: &Opts) -> Result<ChunkedStream, CommandParseError> {
             return Err(CommandParseError::InvalidEnvironment);
         }
 
-        return Ok(ChunkedStream::from(stdin()));
+        Ok(vec![with_range(Input::new(InputSource::Stdin, None))?])
     }
 }
 
@@ -81,22 +400,407 @@ impl TryInto<Command> for Opts {
 
     fn try_into(self) -> Result<Command, Self::Error> {
         if self.list {
-            return Ok(Command::List);
+            return Ok(Command::List {
+                format: self.format,
+            });
+        }
+
+        if let Some(torrent_file) = self.torrent {
+            return Ok(Command::Torrent {
+                torrent_file,
+                payload_directory: self.verify,
+                encoding: self.encoding,
+            });
         }
 
-        if let Some(algorithm) = &self.algorithm {
-            let data = get_data(&self)?;
-            let (_, algorithm) = parse_algorithm(algorithm)
-                .map_err(|err| CommandParseError::InvalidAlgorithm(err.to_string()))?;
+        if let Some(mut signatures) = self.compare_fuzzy {
+            let second = signatures.pop().unwrap();
+            let first = signatures.pop().unwrap();
+            return Ok(Command::CompareFuzzy { first, second });
+        }
 
-            return Ok(Command::Digest {
+        if let Some(paths) = self.distances {
+            return Ok(Command::Distances { paths });
+        }
+
+        if let Some(mut lists) = self.compare_pieces {
+            let second = lists.pop().unwrap();
+            let first = lists.pop().unwrap();
+            return Ok(Command::ComparePieces { first, second });
+        }
+
+        if let Some(names) = self.bench {
+            if self.output_format == OutputFormat::Csv {
+                return Err(CommandParseError::ConflictingOptions(
+                    "Benchmark results cannot be written as CSV, use JSON or NDJSON instead",
+                ));
+            }
+
+            let algorithms = match names.iter().any(|name| name == "all") {
+                true => ALGORITHMS
+                    .iter()
+                    .filter(|entry| entry.parameter.is_none() && !entry.keyed)
+                    .map(|entry| AlgorithmSpec {
+                        entry,
+                        parameter: None,
+                        seed: None,
+                    })
+                    .collect(),
+                false => names
+                    .iter()
+                    .map(|name| parse_algorithm(name))
+                    .collect::<Result<_, _>>()
+                    .map_err(|err| CommandParseError::InvalidAlgorithm(err.to_string()))?,
+            };
+
+            return Ok(Command::Bench {
+                algorithms,
+                options: BenchOptions {
+                    sizes: self.bench_sizes,
+                    repetitions: self.bench_repetitions,
+                    output_format: self.output_format,
+                    baseline: self.baseline,
+                    save_baseline: self.save_baseline,
+                },
+            });
+        }
+
+        let algorithm = self
+            .algorithm
+            .as_deref()
+            .map(parse_algorithm)
+            .transpose()
+            .map_err(|err| CommandParseError::InvalidAlgorithm(err.to_string()))?;
+
+        let filters = Filters {
+            include: self.include.clone(),
+            exclude: self.exclude.clone(),
+        };
+
+        if let Some(mut trees) = self.manifest_diff {
+            let second = trees.pop().unwrap();
+            let first = trees.pop().unwrap();
+            return Ok(Command::ManifestDiff {
+                first,
+                second,
+                algorithm,
+                filters,
+            });
+        }
+
+        if let Some(checksum_file) = self.check {
+            return Ok(Command::Check {
+                checksum_file,
                 algorithm,
-                data,
+            });
+        }
+
+        if let Some(algorithm) = algorithm {
+            let mut dirhash_options = DirhashOptions {
+                filters: filters.clone(),
+                follow_symlinks: self.follow_symlinks,
+            };
+            if self.ignore_vcs {
+                dirhash_options
+                    .filters
+                    .exclude
+                    .extend(VCS_DIRECTORIES.iter().map(|directory| {
+                        Glob::new(directory).expect("VCS directories are valid patterns")
+                    }));
+            }
+
+            if let Some(directory) = self.dirhash {
+                return Ok(Command::Dirhash {
+                    directory,
+                    algorithm: algorithm.build(),
+                    textual: algorithm.entry.textual,
+                    encoding: self.encoding,
+                    options: dirhash_options,
+                    listing: self.dirhash_listing,
+                });
+            }
+
+            if let Some(mut trees) = self.dirhash_explain {
+                let second = trees.pop().unwrap();
+                let first = trees.pop().unwrap();
+                return Ok(Command::DirhashExplain {
+                    first,
+                    second,
+                    algorithm: algorithm.build(),
+                    options: dirhash_options,
+                });
+            }
+
+            if let Some(directory) = self.manifest {
+                // Keys are left out of algorithm names, so the files could not be hashed
+                // the same way again to compare them to the manifest
+                if algorithm.entry.keyed {
+                    return Err(CommandParseError::ConflictingOptions(
+                        "Manifests cannot be written with keyed algorithms",
+                    ));
+                }
+                return Ok(Command::Manifest {
+                    directory,
+                    algorithm,
+                    filters,
+                });
+            }
+
+            if self.dupes {
+                if self.output_format == OutputFormat::Csv {
+                    return Err(CommandParseError::ConflictingOptions(
+                        "Duplicate groups cannot be written as CSV, use JSON or NDJSON instead",
+                    ));
+                }
+
+                return Ok(Command::Dupes {
+                    algorithm: algorithm.build(),
+                    textual: algorithm.entry.textual,
+                    encoding: self.encoding,
+                    roots: match self.file_paths.is_empty() {
+                        true => vec![PathBuf::from(".")],
+                        false => self.file_paths,
+                    },
+                    output_format: self.output_format,
+                    script: self.dupes_script,
+                });
+            }
+
+            if self.watch {
+                if self.output_format == OutputFormat::Json {
+                    return Err(CommandParseError::ConflictingOptions(
+                        "Watched digests are written as they change, use NDJSON instead of JSON",
+                    ));
+                }
+
+                return Ok(Command::Watch {
+                    algorithm: algorithm.build(),
+                    algorithm_name: algorithm.name(),
+                    textual: algorithm.entry.textual,
+                    encoding: self.encoding,
+                    roots: match self.file_paths.is_empty() {
+                        true => vec![PathBuf::from(".")],
+                        false => self.file_paths,
+                    },
+                    output_format: self.output_format,
+                    verbose: self.verbose,
+                });
+            }
+
+            // --check only reads tagged lines back with hex digests
+            if self.tag && (algorithm.entry.textual || !matches!(self.encoding, Encoding::Hex(_))) {
+                return Err(CommandParseError::ConflictingOptions(
+                    "--tag only writes hex digests, it cannot be used with another --encoding or with ssdeep or TLSH",
+                ));
+            }
+
+            let pieces = match (self.piece_size, self.cdc) {
+                (Some(_), Some(_)) => {
+                    return Err(CommandParseError::ConflictingOptions(
+                        "--piece-size and --cdc cannot be used together",
+                    ))
+                }
+                (Some(piece_size), None) => Some(PieceSplit::Fixed(piece_size)),
+                (None, Some(sizes)) => Some(PieceSplit::ContentDefined(sizes)),
+                (None, None) => None,
+            };
+
+            if pieces.is_some() && self.output_format == OutputFormat::Csv {
+                return Err(CommandParseError::ConflictingOptions(
+                    "Piece digests cannot be written as CSV, use JSON or NDJSON instead",
+                ));
+            }
+
+            let store = match (&self.cache, self.cache_xattr) {
+                (Some(path), _) => Some(CacheStore::File(path.clone())),
+                (None, true) => Some(CacheStore::Xattr),
+                (None, false) => None,
+            };
+            let cache = match store {
+                Some(_) if algorithm.entry.keyed => {
+                    return Err(CommandParseError::ConflictingOptions(
+                        "Digests of keyed algorithms cannot be cached",
+                    ))
+                }
+                Some(_)
+                    if pieces.is_some()
+                        || self.offset > 0
+                        || self.length.is_some()
+                        || self.input_encoding.is_some() =>
+                {
+                    return Err(CommandParseError::ConflictingOptions(
+                        "Only digests of whole files can be cached, without --offset, --length, --input-encoding, --piece-size or --cdc",
+                    ))
+                }
+                Some(store) => Some(
+                    DigestCache::open(store, algorithm.name(), self.verify_cache, self.verbose)
+                        .map_err(|err| {
+                            CommandParseError::InvalidPath(
+                                self.cache.clone().unwrap_or_default(),
+                                err,
+                            )
+                        })?,
+                ),
+                None if self.verify_cache > 0 => {
+                    return Err(CommandParseError::ConflictingOptions(
+                        "--verify-cache requires --cache or --cache-xattr",
+                    ))
+                }
+                None => None,
+            };
+
+            if self.checkpoint.is_some() {
+                if pieces.is_some()
+                    || cache.is_some()
+                    || self.offset > 0
+                    || self.length.is_some()
+                    || self.input_encoding.is_some()
+                {
+                    return Err(CommandParseError::ConflictingOptions(
+                        "Checkpoints are only saved while hashing whole files, without --offset, --length, --input-encoding, --piece-size, --cdc or a cache",
+                    ));
+                }
+                if algorithm.build().resumable().is_none() {
+                    return Err(CommandParseError::InvalidAlgorithm(format!(
+                        "{} does not support checkpoints, only MD4, MD5, SHA-1, SHA-2, SHA-3 and SHAKE do",
+                        algorithm.name()
+                    )));
+                }
+            }
+
+            let checkpoints = match (&self.checkpoint, &self.file_paths[..]) {
+                (None, _) => None,
+                (Some(path), [file]) => {
+                    if file.is_dir() {
+                        return Err(CommandParseError::PathIsDirectory(file.clone()));
+                    }
+                    let checkpoints = Checkpoints::open(
+                        path.clone(),
+                        self.checkpoint_interval,
+                        algorithm.name(),
+                        file,
+                        self.verbose,
+                    )
+                    .map_err(|err| match err.kind() {
+                        io::ErrorKind::NotFound => {
+                            CommandParseError::FileDoesNotExist(file.clone())
+                        }
+                        _ => CommandParseError::InvalidCheckpoint(path.clone(), err),
+                    })?;
+                    Some(Box::new(checkpoints))
+                }
+                (Some(_), []) => {
+                    return Err(CommandParseError::ConflictingOptions(
+                        "Checkpoints can only be saved while hashing a file",
+                    ))
+                }
+                (Some(_), _) => {
+                    return Err(CommandParseError::ConflictingOptions(
+                        "Checkpoints can only be saved while hashing a single file",
+                    ))
+                }
+            };
+
+            // Hashing resumes where the checkpoint was saved, seeking past the bytes
+            // hashed before
+            let offset = checkpoints
+                .as_ref()
+                .map_or(self.offset, |checkpoints| checkpoints.offset());
+            let inputs = get_inputs(&self, offset)?;
+
+            return Ok(Command::Digest {
+                algorithm: algorithm.build(),
+                algorithm_name: algorithm.name(),
+                tag: self.tag.then(|| algorithm.tag()),
+                textual: algorithm.entry.textual,
+                inputs,
                 encoding: self.encoding,
+                output_format: self.output_format,
+                pieces,
+                offset: self.offset,
+                input_encoding: self.input_encoding,
+                length: self.length,
+                cache,
+                checkpoints,
+                progress: self.progress,
                 verbose: self.verbose,
             });
         }
 
-        return Err(CommandParseError::NotImplemented);
+        Err(CommandParseError::NotImplemented)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Opts, structopt::clap::Error> {
+        Opts::from_iter_safe(std::iter::once("hashy").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn modes_conflict() {
+        assert!(parse(&["sha256", "--dupes", "dir"]).is_ok());
+        assert!(parse(&["--torrent", "a.torrent", "--verify", "dir"]).is_ok());
+
+        for args in [
+            &["--torrent", "a.torrent", "--dupes"][..],
+            &["sha256", "--dupes", "--watch"],
+            &["--list", "--bench", "md5"],
+            &["sha256", "--dirhash", "dir", "--manifest", "dir"],
+        ] {
+            assert!(parse(args).is_err(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn positionals_are_not_ignored() {
+        assert!(parse(&["--check", "sums"]).is_ok());
+        assert!(parse(&["sha256", "--check", "sums"]).is_ok());
+
+        for args in [
+            &["--list", "sha256"][..],
+            &["sha256", "--torrent", "a.torrent"],
+            &["sha256", "--dirhash", "dir", "file"],
+            &["sha256", "--check", "sums", "file"],
+        ] {
+            assert!(parse(args).is_err(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn tagged_lines_can_be_checked() {
+        let command = |args: &[&str]| -> Result<Command, CommandParseError> {
+            parse(args).unwrap().try_into()
+        };
+
+        assert!(parse(&["sha256", "--tag", "--text", "abc"]).is_err());
+        for args in [
+            &["sha256", "--tag", "-e", "base64", "file"][..],
+            &["tlsh", "--tag", "file"],
+        ] {
+            assert!(
+                matches!(command(args), Err(CommandParseError::ConflictingOptions(_))),
+                "{:?}",
+                args
+            );
+        }
+    }
+
+    #[test]
+    fn keyed_digests_are_not_kept() {
+        let key = "siphash:000102030405060708090a0b0c0d0e0f";
+        for args in [
+            &[key, "--manifest", "dir"][..],
+            &[key, "--cache-xattr", "file"],
+        ] {
+            let command: Result<Command, _> = parse(args).unwrap().try_into();
+            assert!(
+                matches!(command, Err(CommandParseError::ConflictingOptions(_))),
+                "{:?}",
+                args
+            );
+        }
     }
 }
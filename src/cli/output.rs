@@ -0,0 +1,285 @@
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use super::{formats::OutputFormat, pieces::format_range};
+
+/// Result of digesting a single input
+#[derive(Debug, Serialize)]
+pub struct DigestRecord {
+    /// Path of the input, "-" for stdin and None for text
+    pub path: Option<String>,
+    /// Number of bytes hashed, None if unknown (stdin)
+    pub size: Option<u64>,
+    pub algorithm: String,
+    pub encoding: String,
+    pub digest: String,
+    pub elapsed_seconds: f64,
+    /// Digests of every piece, only present in piecewise mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pieces: Option<Vec<PieceRecord>>,
+}
+
+/// Digest of a byte range of the input
+#[derive(Debug, Serialize)]
+pub struct PieceRecord {
+    pub offset: u64,
+    pub length: u64,
+    pub digest: String,
+}
+
+const CSV_HEADER: &str = "path,size,algorithm,encoding,digest,elapsed_seconds";
+
+/// Writes digest records in the chosen format as they come in.
+///
+/// JSON output is an array, so `finish` has to be called to close it.
+pub struct RecordWriter<W: Write> {
+    out: W,
+    format: OutputFormat,
+    /// Whether text output is prefixed by the path
+    with_path: bool,
+    /// BSD tag of the algorithm, text output is tagged if present
+    tag: Option<String>,
+    count: usize,
+}
+
+impl<W: Write> RecordWriter<W> {
+    pub fn new(out: W, format: OutputFormat, with_path: bool) -> Self {
+        Self {
+            out,
+            format,
+            with_path,
+            tag: None,
+            count: 0,
+        }
+    }
+
+    /// Writes text output in the BSD-style `TAG (path) = digest` format
+    pub fn tagged(mut self, tag: String) -> Self {
+        self.tag = Some(tag);
+        self
+    }
+
+    pub fn write(&mut self, record: &DigestRecord) -> io::Result<()> {
+        // Piece lines come before the line of the whole input in text output, after
+        // a header naming the input if there are several
+        if let (OutputFormat::Text, Some(pieces)) = (self.format, &record.pieces) {
+            if self.with_path {
+                writeln!(
+                    self.out,
+                    "==> {} <==",
+                    record.path.as_deref().unwrap_or("-")
+                )?;
+            }
+            for piece in pieces {
+                writeln!(
+                    self.out,
+                    "{} {}",
+                    format_range(piece.offset, piece.length),
+                    piece.digest
+                )?;
+            }
+        }
+
+        match (self.format, &self.tag) {
+            (OutputFormat::Text, Some(tag)) => writeln!(
+                self.out,
+                "{} ({}) = {}",
+                tag,
+                record.path.as_deref().unwrap_or("-"),
+                record.digest
+            )?,
+            (OutputFormat::Text, None) if self.with_path => writeln!(
+                self.out,
+                "{}  {}",
+                record.digest,
+                record.path.as_deref().unwrap_or("-")
+            )?,
+            (OutputFormat::Text, None) => writeln!(self.out, "{}", record.digest)?,
+            (OutputFormat::Json, _) => {
+                let separator = if self.count == 0 { "[\n" } else { ",\n" };
+                write!(
+                    self.out,
+                    "{}  {}",
+                    separator,
+                    serde_json::to_string(record)?
+                )?;
+            }
+            (OutputFormat::Ndjson, _) => writeln!(self.out, "{}", serde_json::to_string(record)?)?,
+            (OutputFormat::Csv, _) => {
+                if self.count == 0 {
+                    writeln!(self.out, "{}", CSV_HEADER)?;
+                }
+
+                let fields = [
+                    csv_field(record.path.as_deref().unwrap_or_default()),
+                    record.size.map(|size| size.to_string()).unwrap_or_default(),
+                    csv_field(&record.algorithm),
+                    csv_field(&record.encoding),
+                    csv_field(&record.digest),
+                    record.elapsed_seconds.to_string(),
+                ];
+                writeln!(self.out, "{}", fields.join(","))?;
+            }
+        }
+
+        self.count += 1;
+        self.out.flush()
+    }
+
+    /// Writes whatever is needed to complete the output (closing the JSON array).
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.format {
+            OutputFormat::Json if self.count == 0 => writeln!(self.out, "[]")?,
+            OutputFormat::Json => writeln!(self.out, "\n]")?,
+            OutputFormat::Csv if self.count == 0 => writeln!(self.out, "{}", CSV_HEADER)?,
+            _ => {}
+        }
+
+        self.out.flush()
+    }
+}
+
+/// Quotes a CSV field if it contains separators, quotes or line breaks
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn record(path: &str) -> DigestRecord {
+        DigestRecord {
+            path: Some(path.to_string()),
+            size: Some(3),
+            algorithm: "md5".to_string(),
+            encoding: "hex".to_string(),
+            digest: "900150983cd24fb0d6963f7d28e17f72".to_string(),
+            elapsed_seconds: 0.5,
+            pieces: None,
+        }
+    }
+
+    fn write_all(format: OutputFormat, with_path: bool, records: &[DigestRecord]) -> String {
+        let mut out = vec![];
+        let mut writer = RecordWriter::new(&mut out, format, with_path);
+        for record in records {
+            writer.write(record).unwrap();
+        }
+        writer.finish().unwrap();
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn text_output() {
+        let records = [record("a.txt"), record("b.txt")];
+
+        assert_eq!(
+            write_all(OutputFormat::Text, false, &records[..1]),
+            "900150983cd24fb0d6963f7d28e17f72\n"
+        );
+        assert_eq!(
+            write_all(OutputFormat::Text, true, &records),
+            "900150983cd24fb0d6963f7d28e17f72  a.txt\n900150983cd24fb0d6963f7d28e17f72  b.txt\n"
+        );
+    }
+
+    #[test]
+    fn piece_output() {
+        let mut with_pieces = record("a.txt");
+        with_pieces.pieces = Some(vec![
+            PieceRecord {
+                offset: 0,
+                length: 2,
+                digest: "aa".to_string(),
+            },
+            PieceRecord {
+                offset: 2,
+                length: 1,
+                digest: "bb".to_string(),
+            },
+        ]);
+
+        assert_eq!(
+            write_all(OutputFormat::Text, false, &[with_pieces]),
+            "0-2 aa\n2-3 bb\n900150983cd24fb0d6963f7d28e17f72\n"
+        );
+
+        let mut second = record("b.txt");
+        second.pieces = Some(vec![]);
+        let mut first = record("a.txt");
+        first.pieces = Some(vec![PieceRecord {
+            offset: 0,
+            length: 3,
+            digest: "aa".to_string(),
+        }]);
+        assert_eq!(
+            write_all(OutputFormat::Text, true, &[first, second]),
+            "==> a.txt <==\n0-3 aa\n900150983cd24fb0d6963f7d28e17f72  a.txt\n==> b.txt <==\n900150983cd24fb0d6963f7d28e17f72  b.txt\n"
+        );
+
+        let output = write_all(OutputFormat::Ndjson, false, &[record("a.txt")]);
+        let parsed: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert!(parsed.get("pieces").is_none());
+    }
+
+    #[test]
+    fn tagged_output() {
+        let mut out = vec![];
+        let mut writer =
+            RecordWriter::new(&mut out, OutputFormat::Text, false).tagged("MD5".to_string());
+        writer.write(&record("a.txt")).unwrap();
+        writer.finish().unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "MD5 (a.txt) = 900150983cd24fb0d6963f7d28e17f72\n"
+        );
+    }
+
+    #[test]
+    fn json_output() {
+        assert_eq!(write_all(OutputFormat::Json, false, &[]), "[]\n");
+
+        let output = write_all(
+            OutputFormat::Json,
+            true,
+            &[record("a.txt"), record("b.txt")],
+        );
+        let parsed: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(parsed[1]["path"], "b.txt");
+        assert_eq!(parsed[1]["size"], 3);
+        assert_eq!(parsed[1]["digest"], "900150983cd24fb0d6963f7d28e17f72");
+    }
+
+    #[test]
+    fn ndjson_output() {
+        let output = write_all(
+            OutputFormat::Ndjson,
+            true,
+            &[record("a.txt"), record("b.txt")],
+        );
+
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let parsed: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(parsed["path"], "a.txt");
+    }
+
+    #[test]
+    fn csv_output() {
+        assert_eq!(
+            write_all(OutputFormat::Csv, true, &[record("a,\"b\".txt")]),
+            format!(
+                "{}\n\"a,\"\"b\"\".txt\",3,md5,hex,900150983cd24fb0d6963f7d28e17f72,0.5\n",
+                CSV_HEADER
+            )
+        );
+    }
+}
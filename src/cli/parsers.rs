@@ -1,100 +1,550 @@
+use std::fmt::Display;
+
 use nom::{
-    branch::alt,
     bytes::complete::tag,
     character::{char, complete::digit1},
-    combinator::{map, map_res},
-    sequence::{preceded, separated_pair},
+    combinator::{all_consuming, map_res},
+    sequence::preceded,
     IResult, Parser,
 };
 
-use crate::algorithms::{
-    md2::Md2,
-    md4::Md4,
-    md5::Md5,
-    md6::Md6,
-    sha1::Sha1,
-    sha2::{Sha2, Sha2Variant},
-    sha3::{Sha3, Sha3Variant, Shake, ShakeVariant},
-    Algorithm,
-};
+use hashy::algorithms::Algorithm;
+
+use super::algorithms::{AlgorithmEntry, Parameter, Seed, SeedParameter, ALGORITHMS};
+
+/// Registry entry resolved from an algorithm name, along with its parameter and seed
+pub struct AlgorithmSpec {
+    pub entry: &'static AlgorithmEntry,
+    pub parameter: Option<usize>,
+    pub seed: Option<Seed>,
+}
+
+impl AlgorithmSpec {
+    /// Canonical name of the algorithm, including the parameter and integer seed
+    pub fn name(&self) -> String {
+        self.with_arguments(self.entry.name)
+    }
+
+    /// Name used in BSD-style tagged output, including the parameter and integer seed
+    pub fn tag(&self) -> String {
+        self.with_arguments(self.entry.tag)
+    }
+
+    /// Appends the parameter and seed to the name. Keys are left out so
+    /// they do not end up in the output.
+    fn with_arguments(&self, name: &str) -> String {
+        let name = match self.parameter {
+            Some(parameter) => format!("{}-{}", name, parameter),
+            None => name.to_string(),
+        };
+
+        match &self.seed {
+            Some(seed @ Seed::Integer(_)) => format!("{}:{}", name, seed),
+            _ => name,
+        }
+    }
+
+    pub fn build(&self) -> Box<dyn Algorithm> {
+        (self.entry.constructor)(self.parameter, self.seed.as_ref())
+    }
+
+    /// Parses the seed given after the name, if the entry takes one
+    fn with_seed(mut self, seed: Option<&str>) -> Result<Self, AlgorithmParseError> {
+        let algorithm = self.entry.name.to_string();
+
+        match (&self.entry.seed, seed) {
+            (None, None) | (Some(SeedParameter::Integer { .. }), None) => Ok(self),
+            (None, Some(_)) => Err(AlgorithmParseError::UnexpectedSeed { algorithm }),
+            (Some(SeedParameter::Key { .. }), None) => Err(AlgorithmParseError::MissingKey {
+                algorithm: self.entry.display_name(self.entry.name),
+            }),
+            (Some(seed_parameter), Some(value)) => match seed_parameter.parse(value) {
+                Some(seed) => {
+                    self.seed = Some(seed);
+                    Ok(self)
+                }
+                None => Err(AlgorithmParseError::InvalidSeed {
+                    algorithm,
+                    value: value.to_string(),
+                    seed: seed_parameter,
+                }),
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AlgorithmParseError {
+    Unknown {
+        input: String,
+        suggestion: Option<String>,
+    },
+    MissingParameter {
+        algorithm: String,
+    },
+    InvalidParameter {
+        algorithm: String,
+        value: usize,
+        parameter: &'static Parameter,
+    },
+    UnexpectedSeed {
+        algorithm: String,
+    },
+    MissingKey {
+        algorithm: String,
+    },
+    InvalidSeed {
+        algorithm: String,
+        value: String,
+        seed: &'static SeedParameter,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+pub struct InvalidSize(String);
+
+impl Display for InvalidSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid size {}, expected a number of bytes with an optional unit (K, KB, KiB, M, MB, MiB, ...)",
+            self.0
+        )
+    }
+}
 
 fn num(input: &str) -> IResult<&str, usize> {
     map_res(digit1, |v: &str| v.parse::<usize>()).parse(input)
 }
 
-fn md6(input: &str) -> IResult<&str, Box<dyn Algorithm>> {
-    map(
-        preceded(tag("md6-"), num),
-        |output_length| -> Box<dyn Algorithm> { Box::new(Md6::new(output_length, None)) },
-    )
-    .parse(input)
-}
-
-fn sha2(input: &str) -> IResult<&str, Box<dyn Algorithm>> {
-    map(
-        preceded(
-            (tag("sha"), alt((tag("2-"), tag("-")))),
-            alt((
-                map(tag("224"), |_| Sha2Variant::_224),
-                map(tag("256"), |_| Sha2Variant::_256),
-                map(tag("384"), |_| Sha2Variant::_384),
-                map(tag("512"), |_| Sha2Variant::_512),
-                map(tag("512-224"), |_| Sha2Variant::_512_224),
-                map(tag("512-256"), |_| Sha2Variant::_512_256),
-            )),
-        ),
-        |variant| -> Box<dyn Algorithm> { Box::new(Sha2::new(variant)) },
-    )
-    .parse(input)
-}
-
-fn sha3(input: &str) -> IResult<&str, Box<dyn Algorithm>> {
-    map(
-        preceded(
-            tag("sha3-"),
-            alt((
-                map(tag("224"), |_| Sha3Variant::_224),
-                map(tag("256"), |_| Sha3Variant::_256),
-                map(tag("384"), |_| Sha3Variant::_384),
-                map(tag("512"), |_| Sha3Variant::_512),
-            )),
-        ),
-        |variant| -> Box<dyn Algorithm> { Box::new(Sha3::new(variant)) },
-    )
-    .parse(input)
-}
-
-fn shake(input: &str) -> IResult<&str, Box<dyn Algorithm>> {
-    map_res(
-        preceded(
-            tag("shake"),
-            separated_pair(
-                alt((
-                    map(tag("128"), |_| ShakeVariant::_128),
-                    map(tag("256"), |_| ShakeVariant::_256),
-                )),
-                char('-'),
-                num,
+/// Parses `<name>-<num>`, requiring the whole input to be consumed
+fn parameterized(name: &str) -> impl Parser<&str, Output = usize, Error = nom::error::Error<&str>> {
+    all_consuming(preceded((tag(name), char('-')), num))
+}
+
+/// Multiplier of a size unit, which follows dd: K and KiB are 1024 bytes while KB is 1000
+fn unit_multiplier(unit: &str) -> Option<u64> {
+    let unit = unit.to_lowercase();
+    let (prefix, base) = if let Some(prefix) = unit.strip_suffix("ib") {
+        (prefix, 1024)
+    } else {
+        match unit.strip_suffix('b') {
+            Some("") => return Some(1),
+            Some(prefix) => (prefix, 1000),
+            None => (unit.as_str(), 1024),
+        }
+    };
+
+    let exponent = match prefix {
+        "" if unit.is_empty() => 0,
+        "k" => 1,
+        "m" => 2,
+        "g" => 3,
+        "t" => 4,
+        _ => return None,
+    };
+
+    Some(u64::pow(base, exponent))
+}
+
+/// Parses a size in bytes such as `512`, `4K` or `1MiB`
+pub fn parse_size(input: &str) -> Result<u64, InvalidSize> {
+    let invalid = || InvalidSize(input.to_string());
+
+    let (unit, count) = map_res(digit1::<&str, nom::error::Error<&str>>, str::parse::<u64>)
+        .parse(input.trim())
+        .map_err(|_| invalid())?;
+
+    unit_multiplier(unit)
+        .and_then(|multiplier| count.checked_mul(multiplier))
+        .ok_or_else(invalid)
+}
+
+/// Splits the seed or key following the name with a colon, if any
+fn split_seed(input: &str) -> (&str, Option<&str>) {
+    match input.split_once(':') {
+        Some((name, seed)) => (name, Some(seed)),
+        None => (input, None),
+    }
+}
+
+/// Looks up the algorithm in the registry by its full name or alias (case-insensitive),
+/// followed by its parameter if it requires one and its seed or key if it takes one.
+pub fn parse_algorithm(input: &str) -> Result<AlgorithmSpec, AlgorithmParseError> {
+    let input = input.to_lowercase();
+    let (name, seed) = split_seed(&input);
+
+    find_by_name(name)?.with_seed(seed)
+}
+
+fn find_by_name(input: &str) -> Result<AlgorithmSpec, AlgorithmParseError> {
+    for entry in ALGORITHMS.iter() {
+        for name in entry.names() {
+            match &entry.parameter {
+                None if input == name => {
+                    return Ok(AlgorithmSpec {
+                        entry,
+                        parameter: None,
+                        seed: None,
+                    })
+                }
+                None => {}
+                Some(_) if input == name => {
+                    return Err(AlgorithmParseError::MissingParameter {
+                        algorithm: entry.display_name(name),
+                    })
+                }
+                Some(parameter) => {
+                    if let Ok((_, value)) = parameterized(name).parse(input) {
+                        if !parameter.accepts(value) {
+                            return Err(AlgorithmParseError::InvalidParameter {
+                                algorithm: entry.display_name(name),
+                                value,
+                                parameter,
+                            });
+                        }
+
+                        return Ok(AlgorithmSpec {
+                            entry,
+                            parameter: Some(value),
+                            seed: None,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    Err(AlgorithmParseError::Unknown {
+        suggestion: suggest(input),
+        input: input.to_string(),
+    })
+}
+
+/// Looks up the algorithm by its BSD tag (like `SHA512/256` or `SHAKE128-256`),
+/// falling back to names and aliases.
+pub fn parse_tag(input: &str) -> Result<AlgorithmSpec, AlgorithmParseError> {
+    let input_upper = input.to_uppercase();
+    let (tag, seed) = split_seed(&input_upper);
+
+    for entry in ALGORITHMS.iter() {
+        match &entry.parameter {
+            None if tag == entry.tag => {
+                return AlgorithmSpec {
+                    entry,
+                    parameter: None,
+                    seed: None,
+                }
+                .with_seed(seed)
+            }
+            None => {}
+            Some(parameter) => {
+                if let Ok((_, value)) = parameterized(entry.tag).parse(tag) {
+                    if parameter.accepts(value) {
+                        return AlgorithmSpec {
+                            entry,
+                            parameter: Some(value),
+                            seed: None,
+                        }
+                        .with_seed(seed);
+                    }
+                }
+            }
+        }
+    }
+
+    parse_algorithm(input)
+}
+
+/// Finds the entry closest to the (unknown) input by edit distance and returns
+/// its canonical name, keeping the parameter of the input if it has one.
+fn suggest(input: &str) -> Option<String> {
+    let (base, parameter) = match input.rsplit_once('-') {
+        Some((base, parameter)) if num(parameter).is_ok_and(|(rest, _)| rest.is_empty()) => {
+            (base, Some(parameter))
+        }
+        _ => (input, None),
+    };
+
+    let (distance, suggestion) = ALGORITHMS
+        .iter()
+        .flat_map(|entry| entry.names().map(move |name| (entry, name)))
+        .map(|(entry, name)| match &entry.parameter {
+            None => (edit_distance(input, name), entry.name.to_string()),
+            Some(_) => (
+                edit_distance(base, name),
+                match parameter {
+                    Some(parameter) => format!("{}-{}", entry.name, parameter),
+                    None => entry.display_name(entry.name),
+                },
             ),
-        ),
-        |(variant, output_length)| {
-            Shake::new(variant, output_length).map(|shake| Box::new(shake) as Box<dyn Algorithm>)
-        },
-    )
-    .parse(input)
-}
-
-pub fn parse_algorithm(input: &str) -> IResult<&str, Box<dyn Algorithm>> {
-    alt((
-        map(tag("md2"), |_| -> Box<dyn Algorithm> { Box::new(Md2) }),
-        map(tag("md4"), |_| -> Box<dyn Algorithm> { Box::new(Md4) }),
-        map(tag("md5"), |_| -> Box<dyn Algorithm> { Box::new(Md5) }),
-        md6,
-        map(tag("sha1"), |_| -> Box<dyn Algorithm> { Box::new(Sha1) }),
-        map(tag("sha1"), |_| -> Box<dyn Algorithm> { Box::new(Sha1) }),
-        sha2,
-        sha3,
-        shake,
-    ))
-    .parse(input)
+        })
+        .min_by_key(|(distance, _)| *distance)?;
+
+    (distance <= (input.len() / 3).max(1)).then_some(suggestion)
+}
+
+/// Edit distance between two strings, counting insertions, deletions, substitutions
+/// and transpositions of adjacent characters (optimal string alignment distance)
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    // d[i][j] is the distance between the first i chars of a and the first j chars of b
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    d[0] = (0..=b.len()).collect();
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    d[a.len()][b.len()]
+}
+
+impl Display for AlgorithmParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AlgorithmParseError::Unknown {
+                input,
+                suggestion: Some(suggestion),
+            } => write!(
+                f,
+                "Unknown algorithm {}, did you mean {}?",
+                input, suggestion
+            ),
+            AlgorithmParseError::Unknown {
+                input,
+                suggestion: None,
+            } => write!(f, "Unknown algorithm {}, see --list", input),
+            AlgorithmParseError::MissingParameter { algorithm } => {
+                write!(f, "Missing parameter, expected {}", algorithm)
+            }
+            AlgorithmParseError::InvalidParameter {
+                algorithm,
+                value,
+                parameter,
+            } => write!(
+                f,
+                "Invalid {} {} for {}, must be a multiple of {} from {} to {}",
+                parameter.description,
+                value,
+                algorithm,
+                parameter.multiple_of,
+                parameter.min,
+                parameter.max
+            ),
+            AlgorithmParseError::UnexpectedSeed { algorithm } => {
+                write!(f, "{} does not take a seed or key", algorithm)
+            }
+            AlgorithmParseError::MissingKey { algorithm } => {
+                write!(f, "Missing key, expected {}", algorithm)
+            }
+            AlgorithmParseError::InvalidSeed {
+                algorithm,
+                value,
+                seed,
+            } => write!(
+                f,
+                "Invalid {} {} for {}, must be {}",
+                seed.placeholder(),
+                value,
+                algorithm,
+                seed
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hashy::chunked_stream::ChunkedStream;
+
+    use super::*;
+
+    fn digest_hex(algorithm: &str, input: &str) -> String {
+        let algorithm = parse_algorithm(algorithm).unwrap().build();
+        hex::encode(
+            algorithm
+                .digest(ChunkedStream::from(input.to_string()))
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn parses_names_and_aliases() {
+        let sha_256 = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+        for name in ["sha-256", "sha2", "sha2-256", "sha256", "SHA256"] {
+            assert_eq!(digest_hex(name, ""), sha_256);
+        }
+
+        assert_eq!(
+            digest_hex("sha-512-224", ""),
+            "6ed0dd02806fa89e25de060c19d3ac86cabb87d6a0ddd05c333b84f4"
+        );
+        assert_eq!(
+            digest_hex("sha2-512-256", ""),
+            "c672b8d1ef56ed28ab87c3622c5114069bdd3ad7b8f9737498d0c01ecef0967a"
+        );
+        assert_eq!(digest_hex("shake128-64", ""), "7f9c2ba4e88f827d");
+    }
+
+    #[test]
+    fn canonical_names() {
+        assert_eq!(parse_algorithm("SHA2").unwrap().name(), "sha-256");
+        assert_eq!(
+            parse_algorithm("shake256-72").unwrap().name(),
+            "shake256-72"
+        );
+    }
+
+    #[test]
+    fn parses_tags() {
+        for (tag, expected) in [
+            ("MD5", "md5"),
+            ("SHA256", "sha-256"),
+            ("SHA512/224", "sha-512-224"),
+            ("SHA3-256", "sha3-256"),
+            ("SHAKE128-256", "shake128-256"),
+            ("sha-384", "sha-384"),
+        ] {
+            let spec = parse_tag(tag).unwrap();
+            assert_eq!(spec.name(), expected);
+        }
+
+        assert_eq!(parse_algorithm("sha-512-256").unwrap().tag(), "SHA512/256");
+        assert_eq!(parse_algorithm("md6-256").unwrap().tag(), "MD6-256");
+        assert!(parse_tag("SHA256X").is_err());
+    }
+
+    #[test]
+    fn rejects_trailing_input() {
+        for name in ["md5x", "sha-256-", "sha1 ", "shake128-64a", "md6-256-1"] {
+            assert!(
+                matches!(
+                    parse_algorithm(name),
+                    Err(AlgorithmParseError::Unknown { .. })
+                ),
+                "{} should not parse",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn validates_parameters() {
+        assert!(matches!(
+            parse_algorithm("md6"),
+            Err(AlgorithmParseError::MissingParameter { algorithm }) if algorithm == "md6-n"
+        ));
+        assert!(matches!(
+            parse_algorithm("shake256-65"),
+            Err(AlgorithmParseError::InvalidParameter { value: 65, .. })
+        ));
+        assert!(matches!(
+            parse_algorithm("md6-1024"),
+            Err(AlgorithmParseError::InvalidParameter { value: 1024, .. })
+        ));
+    }
+
+    #[test]
+    fn suggests_closest_name() {
+        for (input, expected) in [
+            ("sha265", Some("sha-256")),
+            ("md55", Some("md5")),
+            ("sha3-265", Some("sha3-256")),
+            ("shake129-64", Some("shake128-64")),
+            ("mdd6", Some("md6-n")),
+            ("blake2b", None),
+        ] {
+            match parse_algorithm(input) {
+                Err(AlgorithmParseError::Unknown { suggestion, .. }) => {
+                    assert_eq!(suggestion.as_deref(), expected, "suggestion for {}", input)
+                }
+                _ => panic!("{} should be unknown", input),
+            }
+        }
+    }
+
+    #[test]
+    fn parses_seeds_and_keys() {
+        assert_eq!(digest_hex("xxh64", "abc"), "44bc2cf5ad770999");
+        assert_eq!(digest_hex("xxh64:42", "abc"), "13c1d910702770e6");
+        assert_eq!(digest_hex("XXH64:0x2A", "abc"), "13c1d910702770e6");
+        assert_eq!(
+            digest_hex("siphash-2-4:000102030405060708090a0b0c0d0e0f", ""),
+            "726fdb47dd0e0e31"
+        );
+
+        assert_eq!(parse_algorithm("xxh3:0x10").unwrap().name(), "xxh3-64:16");
+        assert_eq!(parse_algorithm("murmur3:7").unwrap().tag(), "MURMUR3-32:7");
+        assert_eq!(parse_tag("XXH128:5").unwrap().name(), "xxh3-128:5");
+
+        // Keys are not part of the name
+        let siphash = parse_algorithm("siphash:000102030405060708090a0b0c0d0e0f").unwrap();
+        assert_eq!(siphash.name(), "siphash-2-4");
+    }
+
+    #[test]
+    fn validates_seeds_and_keys() {
+        assert!(matches!(
+            parse_algorithm("md5:1"),
+            Err(AlgorithmParseError::UnexpectedSeed { .. })
+        ));
+        assert!(matches!(
+            parse_algorithm("siphash"),
+            Err(AlgorithmParseError::MissingKey { algorithm }) if algorithm == "siphash-2-4:key"
+        ));
+        assert!(matches!(
+            parse_algorithm("siphash:0011"),
+            Err(AlgorithmParseError::InvalidSeed { .. })
+        ));
+        assert!(matches!(
+            parse_algorithm("xxh32:4294967296"),
+            Err(AlgorithmParseError::InvalidSeed { value, .. }) if value == "4294967296"
+        ));
+    }
+
+    #[test]
+    fn parses_sizes() {
+        for (input, expected) in [
+            ("512", 512),
+            ("0", 0),
+            ("4K", 4096),
+            ("4k", 4096),
+            ("4KiB", 4096),
+            ("4KB", 4000),
+            ("1MiB", 1 << 20),
+            ("2M", 2 << 20),
+            ("1GB", 1_000_000_000),
+            ("3T", 3 << 40),
+            ("10B", 10),
+        ] {
+            assert_eq!(parse_size(input), Ok(expected), "{}", input);
+        }
+
+        for input in ["", "K", "4X", "4iB", "1.5M", "-1", "99999999999T"] {
+            assert_eq!(parse_size(input), Err(InvalidSize(input.to_string())));
+        }
+    }
+
+    #[test]
+    fn edit_distance_works() {
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("sha265", "sha256"), 1);
+        assert_eq!(edit_distance("sha265", "sha224"), 2);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
 }
@@ -0,0 +1,426 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Display,
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    sync::mpsc::{sync_channel, Receiver},
+    thread,
+};
+
+use anyhow::anyhow;
+
+use hashy::{
+    algorithms::Algorithm,
+    chunked_stream::ChunkedStream,
+    chunking::{ChunkSizes, FastCdc},
+};
+
+/// How inputs are split into pieces
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PieceSplit {
+    /// Pieces of the same size, other than the last one
+    Fixed(usize),
+    /// FastCDC chunks, whose boundaries depend on the content
+    ContentDefined(ChunkSizes),
+}
+
+impl PieceSplit {
+    /// Calls `process` with every piece of the input in order, stopping at the first
+    /// error it returns
+    fn try_for_each_piece(
+        self,
+        data: ChunkedStream,
+        process: impl FnMut(&[u8]) -> io::Result<()>,
+    ) -> io::Result<()> {
+        match self {
+            PieceSplit::Fixed(piece_size) => data.try_for_each_chunk(piece_size, process),
+            PieceSplit::ContentDefined(sizes) => {
+                FastCdc::new(sizes).try_for_each_chunk(data, process)
+            }
+        }
+    }
+}
+
+/// Digest of a single piece of the input
+#[derive(Debug, PartialEq)]
+pub struct PieceDigest {
+    pub offset: u64,
+    pub length: u64,
+    pub digest: Vec<u8>,
+}
+
+/// Reads the chunks sent through a channel as one continuous stream,
+/// so that a whole-stream digest can run alongside the piece digests.
+struct ChannelReader {
+    receiver: Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.chunk.len() {
+            match self.receiver.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                }
+                // Sender is dropped once the input is exhausted
+                Err(_) => return Ok(0),
+            }
+        }
+
+        let count = buf.len().min(self.chunk.len() - self.position);
+        buf[..count].copy_from_slice(&self.chunk[self.position..self.position + count]);
+        self.position += count;
+        Ok(count)
+    }
+}
+
+/// Digests every piece of the input separately, along with the whole input,
+/// which is only read once.
+///
+/// Fixed pieces are shorter at the end if the input size is not a multiple of the piece size.
+/// Pieces are digested incrementally, so a piece too short for TLSH gets its `TNULL`
+/// digest instead of failing the input.
+pub fn digest_pieces(
+    algorithm: &dyn Algorithm,
+    data: ChunkedStream,
+    split: PieceSplit,
+) -> io::Result<(Vec<PieceDigest>, Vec<u8>)> {
+    let (sender, receiver) = sync_channel(2);
+
+    thread::scope(|scope| {
+        let whole = scope.spawn(move || {
+            algorithm.digest(ChunkedStream::File(Box::new(BufReader::new(
+                ChannelReader {
+                    receiver,
+                    chunk: vec![],
+                    position: 0,
+                },
+            ))))
+        });
+
+        let mut pieces = vec![];
+        let mut offset = 0;
+        split.try_for_each_piece(data, |piece| {
+            let length = piece.len() as u64;
+
+            // Pieces are digested in place, copying them only for algorithms which
+            // need a whole stream
+            let digest = match algorithm.incremental() {
+                Some(mut state) => {
+                    state.update(piece);
+                    state.finalize()
+                }
+                None => algorithm.digest(ChunkedStream::Bytes(piece.to_vec()))?,
+            };
+            pieces.push(PieceDigest {
+                offset,
+                length,
+                digest,
+            });
+            offset += length;
+
+            // The whole digest only stops reading early if it failed, which is reported below
+            let _ = sender.send(piece.to_vec());
+            Ok(())
+        })?;
+        drop(sender);
+
+        let digest = whole.join().expect("whole input digest panicked")?;
+        Ok((pieces, digest))
+    })
+}
+
+/// Deduplication statistics of the pieces of a set of inputs, where pieces
+/// of the same length and digest are counted once
+#[derive(Default)]
+pub struct DedupStats {
+    pieces: usize,
+    bytes: u64,
+    unique: HashSet<(u64, String)>,
+    unique_bytes: u64,
+    /// Piece counts by the power of 2 at or below their size
+    histogram: BTreeMap<u32, usize>,
+}
+
+impl DedupStats {
+    pub fn add(&mut self, length: u64, digest: &str) {
+        self.pieces += 1;
+        self.bytes += length;
+        if self.unique.insert((length, digest.to_string())) {
+            self.unique_bytes += length;
+        }
+        *self.histogram.entry(length.max(1).ilog2()).or_default() += 1;
+    }
+
+    /// Total size over the size of unique pieces, 1 if nothing is duplicated
+    pub fn ratio(&self) -> f64 {
+        match self.unique_bytes {
+            0 => 1.0,
+            unique_bytes => self.bytes as f64 / unique_bytes as f64,
+        }
+    }
+}
+
+/// Power of 2 with a binary unit, such as 4K
+fn format_power(bits: u32) -> String {
+    match bits {
+        0..=9 => format!("{}", 1u64 << bits),
+        10..=19 => format!("{}K", 1u64 << (bits - 10)),
+        20..=29 => format!("{}M", 1u64 << (bits - 20)),
+        _ => format!("{}G", 1u64 << (bits - 30)),
+    }
+}
+
+impl Display for DedupStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Chunks: {} ({} unique)", self.pieces, self.unique.len())?;
+        writeln!(f, "Bytes: {} ({} unique)", self.bytes, self.unique_bytes)?;
+        writeln!(f, "Dedup ratio: {:.2}", self.ratio())?;
+        write!(f, "Chunk sizes:")?;
+        for (bits, count) in &self.histogram {
+            write!(
+                f,
+                "\n  {}-{}: {}",
+                format_power(*bits),
+                format_power(bits + 1),
+                count
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats the byte range of a piece as `start-end`, the end being exclusive
+pub fn format_range(offset: u64, length: u64) -> String {
+    format!("{}-{}", offset, offset + length)
+}
+
+/// Parses a `start-end digest` piece line, None for any other line
+/// (such as the whole-input digest) which is skipped when comparing.
+fn parse_piece_line(line: &str) -> Option<((u64, u64), String)> {
+    let (range, digest) = line.trim_end().split_once(' ')?;
+    let (start, end) = range.split_once('-')?;
+    let (start, end) = (start.parse().ok()?, end.parse().ok()?);
+    if end < start || digest.is_empty() || digest.contains(char::is_whitespace) {
+        return None;
+    }
+
+    Some(((start, end), digest.to_string()))
+}
+
+fn read_piece_list(path: &Path) -> anyhow::Result<BTreeMap<(u64, u64), String>> {
+    let reader: Box<dyn BufRead> = if path.as_os_str() == "-" {
+        Box::new(BufReader::new(io::stdin()))
+    } else {
+        Box::new(BufReader::new(File::open(path).map_err(|err| {
+            anyhow!("Invalid path! {}: {}", path.display(), err)
+        })?))
+    };
+
+    let mut pieces = BTreeMap::new();
+    for line in reader.lines() {
+        if let Some((range, digest)) = parse_piece_line(&line?) {
+            if pieces.insert(range, digest).is_some() {
+                return Err(anyhow!(
+                    "Duplicate range {} in {}",
+                    format_range(range.0, range.1 - range.0),
+                    path.display()
+                ));
+            }
+        }
+    }
+
+    if pieces.is_empty() {
+        return Err(anyhow!("No piece digests found in {}", path.display()));
+    }
+
+    Ok(pieces)
+}
+
+#[derive(Debug, PartialEq)]
+enum PieceDifference {
+    Differs,
+    /// Range only present in the first (false) or second (true) list
+    OnlyIn(bool),
+}
+
+/// Lists the ranges which differ between two piece lists, in order of offset.
+/// Digests are compared case-insensitively so that hex and hex_upper lists match.
+fn diff_pieces(
+    first: &BTreeMap<(u64, u64), String>,
+    second: &BTreeMap<(u64, u64), String>,
+) -> Vec<((u64, u64), PieceDifference)> {
+    let mut differences: Vec<_> = first
+        .iter()
+        .filter_map(|(range, digest)| match second.get(range) {
+            Some(other) if other.eq_ignore_ascii_case(digest) => None,
+            Some(_) => Some((*range, PieceDifference::Differs)),
+            None => Some((*range, PieceDifference::OnlyIn(false))),
+        })
+        .chain(
+            second
+                .keys()
+                .filter(|range| !first.contains_key(range))
+                .map(|range| (*range, PieceDifference::OnlyIn(true))),
+        )
+        .collect();
+
+    differences.sort_by_key(|(range, _)| *range);
+    differences
+}
+
+/// Compares two piece lists written by `--piece-size` and prints the ranges
+/// which differ, failing if there are any.
+pub fn compare_pieces(first: PathBuf, second: PathBuf) -> anyhow::Result<()> {
+    let first_pieces = read_piece_list(&first)?;
+    let second_pieces = read_piece_list(&second)?;
+
+    let differences = diff_pieces(&first_pieces, &second_pieces);
+    let mut out = io::stdout().lock();
+    for ((start, end), difference) in &differences {
+        let range = format_range(*start, end - start);
+        match difference {
+            PieceDifference::Differs => writeln!(out, "{}: DIFFERS", range)?,
+            PieceDifference::OnlyIn(in_second) => writeln!(
+                out,
+                "{}: ONLY IN {}",
+                range,
+                if *in_second { &second } else { &first }.display()
+            )?,
+        }
+    }
+
+    if !differences.is_empty() {
+        let only_in_second = differences
+            .iter()
+            .filter(|(_, difference)| *difference == PieceDifference::OnlyIn(true))
+            .count();
+        eprintln!(
+            "WARNING: {} of {} range(s) differ",
+            differences.len(),
+            first_pieces.len() + only_in_second
+        );
+        return Err(anyhow!("Piece lists differ"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use hashy::algorithms::md5::Md5;
+
+    use super::*;
+
+    #[test]
+    fn digests_pieces_and_whole_input() {
+        let data = b"The quick brown fox jumps over the lazy dog".to_vec();
+        let (pieces, digest) = digest_pieces(
+            &Md5,
+            ChunkedStream::Bytes(data.clone()),
+            PieceSplit::Fixed(16),
+        )
+        .unwrap();
+
+        assert_eq!(
+            pieces
+                .iter()
+                .map(|piece| (piece.offset, piece.length))
+                .collect::<Vec<_>>(),
+            [(0, 16), (16, 16), (32, 11)]
+        );
+        assert_eq!(
+            pieces[2].digest,
+            Md5.digest(ChunkedStream::Bytes(data[32..].to_vec()))
+                .unwrap()
+        );
+        assert_eq!(hex::encode(digest), "9e107d9d372bb6826bd81d3542a419d6");
+    }
+
+    #[test]
+    fn digests_empty_input() {
+        let (pieces, digest) =
+            digest_pieces(&Md5, ChunkedStream::Bytes(vec![]), PieceSplit::Fixed(16)).unwrap();
+
+        assert!(pieces.is_empty());
+        assert_eq!(hex::encode(digest), "d41d8cd98f00b204e9800998ecf8427e");
+    }
+
+    #[test]
+    fn digests_content_defined_chunks() {
+        let data = b"The quick brown fox jumps over the lazy dog. ".repeat(100);
+        let sizes = ChunkSizes::new(64, 256, 1024).unwrap();
+        let (pieces, digest) = digest_pieces(
+            &Md5,
+            ChunkedStream::Bytes(data.clone()),
+            PieceSplit::ContentDefined(sizes),
+        )
+        .unwrap();
+
+        assert_eq!(
+            pieces.iter().map(|piece| piece.length).sum::<u64>(),
+            data.len() as u64
+        );
+        assert!(pieces.iter().all(|piece| piece.length <= 1024));
+        assert_eq!(digest, Md5.digest(ChunkedStream::Bytes(data)).unwrap());
+    }
+
+    #[test]
+    fn counts_duplicate_pieces() {
+        let mut stats = DedupStats::default();
+        stats.add(1000, "aa");
+        stats.add(1000, "aa");
+        stats.add(3000, "bb");
+        stats.add(1000, "cc");
+
+        assert_eq!(stats.ratio(), 6000.0 / 5000.0);
+        assert_eq!(
+            stats.to_string(),
+            "Chunks: 4 (3 unique)\n\
+             Bytes: 6000 (5000 unique)\n\
+             Dedup ratio: 1.20\n\
+             Chunk sizes:\n  \
+             512-1K: 3\n  \
+             2K-4K: 1"
+        );
+        assert_eq!(DedupStats::default().ratio(), 1.0);
+    }
+
+    #[test]
+    fn parses_piece_lines() {
+        assert_eq!(
+            parse_piece_line("0-1024 d41d8cd98f00b204e9800998ecf8427e"),
+            Some(((0, 1024), "d41d8cd98f00b204e9800998ecf8427e".to_string()))
+        );
+        assert_eq!(parse_piece_line("d41d8cd98f00b204e9800998ecf8427e"), None);
+        assert_eq!(parse_piece_line("d41d8cd98f00b204  file-1"), None);
+        assert_eq!(parse_piece_line("1024-0 d41d8cd98f00b204"), None);
+    }
+
+    #[test]
+    fn diffs_piece_lists() {
+        let first = BTreeMap::from([
+            ((0, 4), "aa".to_string()),
+            ((4, 8), "bb".to_string()),
+            ((8, 10), "cc".to_string()),
+        ]);
+        let second = BTreeMap::from([
+            ((0, 4), "AA".to_string()),
+            ((4, 8), "ff".to_string()),
+            ((8, 12), "cc".to_string()),
+        ]);
+
+        assert_eq!(
+            diff_pieces(&first, &second),
+            [
+                ((4, 8), PieceDifference::Differs),
+                ((8, 10), PieceDifference::OnlyIn(false)),
+                ((8, 12), PieceDifference::OnlyIn(true)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use std::{
+    io::{self, IsTerminal, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// Time between two redraws of the progress line
+const REFRESH_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Progress line on stderr, redrawn by a separate thread from the bytes counted
+/// while the inputs are read
+pub struct Progress {
+    /// Dropped to stop the thread
+    stop: mpsc::Sender<()>,
+    thread: JoinHandle<()>,
+}
+
+impl Progress {
+    /// Starts drawing the progress of reading `total` bytes (if known), the sum of
+    /// `counters`. None if stderr is not a terminal, where the line would only
+    /// clutter logs.
+    pub fn start(counters: Vec<Arc<AtomicU64>>, total: Option<u64>) -> Option<Self> {
+        if !io::stderr().is_terminal() {
+            return None;
+        }
+
+        let (stop, stopped) = mpsc::channel();
+        let start_time = Instant::now();
+        let thread = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(REFRESH_INTERVAL) {
+                let read = counters
+                    .iter()
+                    .map(|counter| counter.load(Ordering::Relaxed))
+                    .sum();
+                let line = progress_line(read, total, start_time.elapsed());
+                // Clears the rest of the previous line
+                eprint!("\r{}\x1b[K", line);
+            }
+            eprint!("\r\x1b[K");
+            let _ = io::stderr().flush();
+        });
+
+        Some(Self { stop, thread })
+    }
+
+    /// Stops drawing and clears the progress line
+    pub fn finish(self) {
+        drop(self.stop);
+        let _ = self.thread.join();
+    }
+}
+
+/// Formats a number of bytes with a binary unit, such as `1.5 GiB`
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Formats the rate of reading `bytes` in `duration`, such as `250.3 MiB/s`
+pub fn format_throughput(bytes: u64, duration: Duration) -> String {
+    let seconds = duration.as_secs_f64();
+    match seconds > 0.0 {
+        true => format!("{}/s", format_bytes((bytes as f64 / seconds) as u64)),
+        false => "- B/s".to_string(),
+    }
+}
+
+fn format_eta(seconds: u64) -> String {
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m{:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h{:02}m", seconds / 3600, seconds / 60 % 60),
+    }
+}
+
+/// Line such as `42.0% 1.2 GiB / 2.9 GiB, 250.3 MiB/s, ETA 7s`, without the
+/// percentage and ETA if the total is unknown
+fn progress_line(read: u64, total: Option<u64>, elapsed: Duration) -> String {
+    let throughput = format_throughput(read, elapsed);
+    match total {
+        Some(total) => {
+            let percent = match total {
+                0 => 100.0,
+                total => read.min(total) as f64 / total as f64 * 100.0,
+            };
+            let eta = match read {
+                0 => "-".to_string(),
+                read => format_eta(
+                    (elapsed.as_secs_f64() * total.saturating_sub(read) as f64 / read as f64)
+                        as u64,
+                ),
+            };
+            format!(
+                "{:5.1}% {} / {}, {}, ETA {}",
+                percent,
+                format_bytes(read),
+                format_bytes(total),
+                throughput,
+                eta
+            )
+        }
+        None => format!("{}, {}", format_bytes(read), throughput),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn formats_progress() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(3 << 30), "3.0 GiB");
+        assert_eq!(format_eta(3725), "1h02m");
+
+        let elapsed = Duration::from_secs(10);
+        assert_eq!(
+            progress_line(250 << 20, Some(1000 << 20), elapsed),
+            " 25.0% 250.0 MiB / 1000.0 MiB, 25.0 MiB/s, ETA 30s"
+        );
+        assert_eq!(
+            progress_line(0, Some(1 << 20), elapsed),
+            "  0.0% 0 B / 1.0 MiB, 0 B/s, ETA -"
+        );
+        assert_eq!(
+            progress_line(5 << 20, None, elapsed),
+            "5.0 MiB, 512.0 KiB/s"
+        );
+    }
+}
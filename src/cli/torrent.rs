@@ -0,0 +1,837 @@
+// References:
+// - https://www.bittorrent.org/beps/bep_0003.html (v1)
+// - https://www.bittorrent.org/beps/bep_0047.html (padding files)
+// - https://www.bittorrent.org/beps/bep_0052.html (v2)
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::anyhow;
+use rayon::prelude::*;
+
+use hashy::{
+    algorithms::{
+        sha1::Sha1,
+        sha2::{Sha2, Sha2Variant},
+        Algorithm,
+    },
+    chunked_stream::ChunkedStream,
+};
+
+use super::{
+    bencode::{self, Value},
+    encoding::Encoding,
+};
+
+/// Size of the leaves of v2 merkle trees
+const BLOCK_SIZE: usize = 16 * 1024;
+
+/// Largest piece length accepted, as every piece is read into memory to be hashed
+const MAX_PIECE_LENGTH: u64 = 256 * 1024 * 1024;
+
+/// File of a torrent payload
+#[derive(Debug, PartialEq)]
+pub struct TorrentFile {
+    /// Path relative to the payload directory, starting with the torrent name
+    pub path: PathBuf,
+    pub length: u64,
+    /// Padding file (BEP 47), which is made of zeros and not stored on disk
+    pub padding: bool,
+    /// Root of the v2 merkle tree of the file, None for v1 and empty files
+    pub pieces_root: Option<Vec<u8>>,
+}
+
+/// Metadata of a v1, v2 or hybrid torrent needed to verify its payload
+#[derive(Debug)]
+pub struct Torrent {
+    pub piece_length: u64,
+    /// Files from the v1 file list, the payload is their concatenation
+    pub files: Vec<TorrentFile>,
+    /// Files from the v2 file tree
+    pub files_v2: Vec<TorrentFile>,
+    /// SHA-1 of every v1 piece
+    pub pieces: Vec<Vec<u8>>,
+    /// Hashes of the v2 pieces of every file larger than a piece, by pieces root
+    pub piece_layers: HashMap<Vec<u8>, Vec<Vec<u8>>>,
+    /// SHA-1 of the info dict, for v1 and hybrid torrents
+    pub infohash_v1: Option<Vec<u8>>,
+    /// SHA-256 of the info dict, for v2 and hybrid torrents
+    pub infohash_v2: Option<Vec<u8>>,
+}
+
+fn sha1(data: &[u8]) -> Vec<u8> {
+    Sha1.digest(ChunkedStream::Bytes(data.to_vec()))
+        .expect("digesting bytes cannot fail")
+}
+
+fn sha256(data: &[u8]) -> Vec<u8> {
+    Sha2::new(Sha2Variant::_256)
+        .digest(ChunkedStream::Bytes(data.to_vec()))
+        .expect("digesting bytes cannot fail")
+}
+
+/// Turns path components from the torrent into a relative path, rejecting
+/// components which would escape the payload directory.
+fn safe_path(name: &str, components: &[&str]) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+    for component in std::iter::once(&name).chain(components) {
+        let mut parsed = Path::new(component).components();
+        match (parsed.next(), parsed.next()) {
+            (Some(Component::Normal(part)), None) if !component.contains(['/', '\\']) => {
+                path.push(part)
+            }
+            _ => return None,
+        }
+    }
+
+    Some(path)
+}
+
+fn malformed(reason: &str) -> anyhow::Error {
+    anyhow!("Malformed torrent! {}", reason)
+}
+
+fn parse_length(value: Option<&Value>) -> anyhow::Result<u64> {
+    value
+        .and_then(Value::as_integer)
+        .and_then(|length| u64::try_from(length).ok())
+        .ok_or_else(|| malformed("missing or invalid file length"))
+}
+
+/// Files of the v1 info dict, a single file named after the torrent or a list of files
+fn parse_files_v1(info: &Value, name: &str) -> anyhow::Result<Vec<TorrentFile>> {
+    let Some(files) = info.get("files") else {
+        return Ok(vec![TorrentFile {
+            path: safe_path(name, &[]).ok_or_else(|| malformed("invalid name"))?,
+            length: parse_length(info.get("length"))?,
+            padding: false,
+            pieces_root: None,
+        }]);
+    };
+
+    files
+        .as_list()
+        .ok_or_else(|| malformed("invalid file list"))?
+        .iter()
+        .map(|file| {
+            let components = file
+                .get("path")
+                .and_then(Value::as_list)
+                .and_then(|path| path.iter().map(Value::as_str).collect::<Option<Vec<_>>>())
+                .filter(|components| !components.is_empty())
+                .ok_or_else(|| malformed("missing or invalid file path"))?;
+
+            Ok(TorrentFile {
+                path: safe_path(name, &components).ok_or_else(|| malformed("unsafe file path"))?,
+                length: parse_length(file.get("length"))?,
+                padding: file
+                    .get("attr")
+                    .and_then(Value::as_bytes)
+                    .is_some_and(|attr| attr.contains(&b'p')),
+                pieces_root: None,
+            })
+        })
+        .collect()
+}
+
+/// Path components, length and pieces root of a file in the v2 file tree
+type FileTreeEntry<'a> = (Vec<&'a str>, u64, Option<Vec<u8>>);
+
+/// Files of the v2 file tree, where every file is a dict with an empty key.
+/// A single file at the top of the tree is the whole payload, like v1 single-file torrents.
+fn parse_files_v2(file_tree: &Value, name: &str) -> anyhow::Result<Vec<TorrentFile>> {
+    fn walk<'a>(
+        node: &Value<'a>,
+        components: &mut Vec<&'a str>,
+        files: &mut Vec<FileTreeEntry<'a>>,
+    ) -> anyhow::Result<()> {
+        let entries = node
+            .as_dict()
+            .ok_or_else(|| malformed("invalid file tree"))?;
+
+        for (key, child) in entries {
+            if key.is_empty() {
+                let length = parse_length(child.get("length"))?;
+                let pieces_root = child
+                    .get("pieces root")
+                    .and_then(Value::as_bytes)
+                    .map(<[u8]>::to_vec);
+                if length > 0 && pieces_root.as_ref().is_none_or(|root| root.len() != 32) {
+                    return Err(malformed("missing or invalid pieces root"));
+                }
+
+                files.push((components.clone(), length, pieces_root));
+            } else {
+                components
+                    .push(std::str::from_utf8(key).map_err(|_| malformed("invalid file name"))?);
+                walk(child, components, files)?;
+                components.pop();
+            }
+        }
+
+        Ok(())
+    }
+
+    let mut files = vec![];
+    walk(file_tree, &mut vec![], &mut files)?;
+
+    let single_file = files.len() == 1 && files[0].0.len() == 1;
+    files
+        .into_iter()
+        .map(|(components, length, pieces_root)| {
+            let path = if single_file {
+                safe_path(components[0], &[])
+            } else {
+                safe_path(name, &components)
+            };
+
+            Ok(TorrentFile {
+                path: path.ok_or_else(|| malformed("unsafe file path"))?,
+                length,
+                padding: false,
+                pieces_root,
+            })
+        })
+        .collect()
+}
+
+/// Piece layers of a v2 torrent, outside of the info dict: the SHA-256 of every piece
+/// of a file (the roots of its subtrees) concatenated, by pieces root of the file.
+/// Torrents without piece layers (such as those fetched from magnet links) are only
+/// verified against the pieces roots.
+fn parse_piece_layers(torrent: &Value) -> anyhow::Result<HashMap<Vec<u8>, Vec<Vec<u8>>>> {
+    let Some(layers) = torrent.get("piece layers") else {
+        return Ok(HashMap::new());
+    };
+
+    layers
+        .as_dict()
+        .ok_or_else(|| malformed("invalid piece layers"))?
+        .iter()
+        .map(|(root, hashes)| match hashes.as_bytes() {
+            Some(hashes) if root.len() == 32 && hashes.len() % 32 == 0 => Ok((
+                root.to_vec(),
+                hashes.chunks(32).map(<[u8]>::to_vec).collect(),
+            )),
+            _ => Err(malformed("invalid piece layers")),
+        })
+        .collect()
+}
+
+impl Torrent {
+    pub fn parse(data: &[u8]) -> anyhow::Result<Self> {
+        let torrent = bencode::parse(data).map_err(|err| malformed(&err.to_string()))?;
+        let info = torrent
+            .get("info")
+            .ok_or_else(|| malformed("missing info dict"))?;
+        let Value::Dict { raw: info_raw, .. } = info else {
+            return Err(malformed("missing info dict"));
+        };
+
+        let name = info
+            .get("name")
+            .and_then(Value::as_str)
+            .ok_or_else(|| malformed("missing or invalid name"))?;
+        let piece_length = info
+            .get("piece length")
+            .and_then(Value::as_integer)
+            .and_then(|length| u64::try_from(length).ok())
+            .filter(|length| *length > 0)
+            .ok_or_else(|| malformed("missing or invalid piece length"))?;
+        if piece_length > MAX_PIECE_LENGTH {
+            return Err(malformed("piece length over 256 MiB"));
+        }
+
+        let is_v1 = info.get("pieces").is_some();
+        let is_v2 = info.get("meta version").and_then(Value::as_integer) == Some(2);
+        if !is_v1 && !is_v2 {
+            return Err(malformed("neither pieces nor a v2 file tree"));
+        }
+
+        let (files, pieces) = if is_v1 {
+            let pieces = info
+                .get("pieces")
+                .and_then(Value::as_bytes)
+                .filter(|pieces| pieces.len() % 20 == 0)
+                .ok_or_else(|| malformed("invalid pieces"))?;
+            let files = parse_files_v1(info, name)?;
+
+            let total_length: u64 = files.iter().map(|file| file.length).sum();
+            if total_length.div_ceil(piece_length) != pieces.len() as u64 / 20 {
+                return Err(malformed("piece count does not match the payload size"));
+            }
+
+            (files, pieces.chunks(20).map(<[u8]>::to_vec).collect())
+        } else {
+            (vec![], vec![])
+        };
+
+        let (files_v2, piece_layers) = if is_v2 {
+            if !piece_length.is_power_of_two() || piece_length < BLOCK_SIZE as u64 {
+                return Err(malformed(
+                    "v2 piece length is not a power of two of 16 KiB or more",
+                ));
+            }
+            let file_tree = info
+                .get("file tree")
+                .ok_or_else(|| malformed("missing file tree"))?;
+            (
+                parse_files_v2(file_tree, name)?,
+                parse_piece_layers(&torrent)?,
+            )
+        } else {
+            (vec![], HashMap::new())
+        };
+
+        Ok(Self {
+            piece_length,
+            files,
+            files_v2,
+            pieces,
+            piece_layers,
+            infohash_v1: is_v1.then(|| sha1(info_raw)),
+            infohash_v2: is_v2.then(|| sha256(info_raw)),
+        })
+    }
+}
+
+/// SHA-256 of every 16 KiB block of the data, the leaves of its v2 merkle tree.
+/// Returns the length of the data along with the leaves.
+fn merkle_leaves(data: ChunkedStream) -> io::Result<(Vec<Vec<u8>>, u64)> {
+    let mut length = 0;
+    let mut leaves = vec![];
+    data.for_each_chunk(BLOCK_SIZE, |block| {
+        length += block.len() as u64;
+        leaves.push(sha256(block));
+    })?;
+
+    Ok((leaves, length))
+}
+
+/// Root of a merkle tree of `width` leaves (a power of two), padded with zero hashes
+fn merkle_reduce(leaves: &[Vec<u8>], width: usize) -> Vec<u8> {
+    let mut layer = leaves.to_vec();
+    layer.resize(width, vec![0; 32]);
+    while layer.len() > 1 {
+        layer = layer.chunks(2).map(|pair| sha256(&pair.concat())).collect();
+    }
+
+    layer.pop().unwrap()
+}
+
+/// Root of the v2 merkle tree of a file, whose leaves are padded with zero hashes
+/// to a power of two
+fn merkle_root(leaves: &[Vec<u8>]) -> Vec<u8> {
+    merkle_reduce(leaves, leaves.len().next_power_of_two())
+}
+
+/// Hashes of the pieces of a file in its v2 merkle tree, the roots of the subtrees
+/// of `piece_length` bytes. The last one is padded with zero hashes like the tree.
+fn piece_layer(leaves: &[Vec<u8>], piece_length: u64) -> Vec<Vec<u8>> {
+    let width = (piece_length / BLOCK_SIZE as u64) as usize;
+    leaves
+        .chunks(width)
+        .map(|piece| merkle_reduce(piece, width))
+        .collect()
+}
+
+/// Verifies a v2 file against its pieces root, None if it matches. Otherwise lists the
+/// pieces of the file which did not match its piece layer, which is empty if the
+/// torrent has none for the file or if the file could not be read.
+fn verify_file_v2(directory: &Path, file: &TorrentFile, torrent: &Torrent) -> Option<Vec<usize>> {
+    let expected = file.pieces_root.as_ref()?;
+    let Ok((leaves, length)) =
+        File::open(directory.join(&file.path)).and_then(|reader| merkle_leaves(reader.into()))
+    else {
+        return Some(vec![]);
+    };
+
+    if length == file.length && merkle_root(&leaves) == *expected {
+        return None;
+    }
+
+    let Some(expected_layer) = torrent.piece_layers.get(expected) else {
+        return Some(vec![]);
+    };
+    let layer = piece_layer(&leaves, torrent.piece_length);
+    Some(
+        (0..expected_layer.len().max(layer.len()))
+            .filter(|&index| layer.get(index) != expected_layer.get(index))
+            .collect(),
+    )
+}
+
+/// Reads up to `length` bytes of the payload from `offset`, across files.
+/// Padding files read as zeros.
+fn read_payload(
+    directory: &Path,
+    files: &[TorrentFile],
+    offset: u64,
+    length: u64,
+) -> io::Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(length as usize);
+    let mut file_start = 0;
+    for file in files {
+        let file_end = file_start + file.length;
+        let start = offset.max(file_start);
+        let end = (offset + length).min(file_end);
+
+        if start < end {
+            let count = (end - start) as usize;
+            if file.padding {
+                data.resize(data.len() + count, 0);
+            } else {
+                let mut reader = File::open(directory.join(&file.path))?;
+                reader.seek(SeekFrom::Start(start - file_start))?;
+
+                let position = data.len();
+                data.resize(position + count, 0);
+                reader.read_exact(&mut data[position..])?;
+            }
+        }
+
+        file_start = file_end;
+    }
+
+    Ok(data)
+}
+
+/// Formats piece indices as ranges, such as `3-5, 9`
+fn format_pieces(pieces: &[usize]) -> String {
+    let mut ranges: Vec<(usize, usize)> = vec![];
+    for &piece in pieces {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == piece => *end = piece,
+            _ => ranges.push((piece, piece)),
+        }
+    }
+
+    ranges
+        .iter()
+        .map(|(start, end)| match start == end {
+            true => start.to_string(),
+            false => format!("{}-{}", start, end),
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Prints the infohashes of a torrent file
+pub fn print_infohashes(torrent_file: &Path, encoding: &Encoding) -> anyhow::Result<()> {
+    let data = fs::read(torrent_file)
+        .map_err(|err| anyhow!("Invalid path! {}: {}", torrent_file.display(), err))?;
+    let torrent = Torrent::parse(&data)?;
+
+    let mut out = io::stdout().lock();
+    if let Some(infohash) = torrent.infohash_v1 {
+        writeln!(out, "infohash-v1 {}", encoding.encode(infohash))?;
+    }
+    if let Some(infohash) = torrent.infohash_v2 {
+        writeln!(out, "infohash-v2 {}", encoding.encode(infohash))?;
+    }
+
+    Ok(())
+}
+
+/// Verifies the payload of a torrent in `directory` and prints the result of each file,
+/// in the style of `--check`.
+///
+/// Every v1 piece is checked against its SHA-1 and every v2 file against its merkle
+/// root, in parallel. Failed files list the pieces which did not match: v1 pieces of
+/// the whole payload, or pieces of the file from its v2 piece layer. Files whose size
+/// differs from the torrent fail as well.
+pub fn verify_torrent(torrent_file: &Path, directory: &Path) -> anyhow::Result<()> {
+    let data = fs::read(torrent_file)
+        .map_err(|err| anyhow!("Invalid path! {}: {}", torrent_file.display(), err))?;
+    let torrent = Torrent::parse(&data)?;
+
+    let bad_pieces: Vec<usize> = torrent
+        .pieces
+        .par_iter()
+        .enumerate()
+        .filter(|(index, expected)| {
+            // The last piece is cut short at the end of the payload
+            let offset = *index as u64 * torrent.piece_length;
+            read_payload(directory, &torrent.files, offset, torrent.piece_length)
+                .map_or(true, |piece| sha1(&piece) != **expected)
+        })
+        .map(|(index, _)| index)
+        .collect();
+
+    let bad_files_v2: Vec<Option<Vec<usize>>> = torrent
+        .files_v2
+        .par_iter()
+        .map(|file| verify_file_v2(directory, file, &torrent))
+        .collect();
+
+    // Files of hybrid torrents are listed once, v1 first
+    let mut paths: Vec<&Path> = vec![];
+    for file in torrent.files.iter().chain(&torrent.files_v2) {
+        if !file.padding && !paths.contains(&file.path.as_path()) {
+            paths.push(&file.path);
+        }
+    }
+
+    let mut out = io::stdout().lock();
+    let (mut failed, mut unreadable) = (0, 0);
+    for path in paths {
+        let length = match fs::metadata(directory.join(path)) {
+            Ok(metadata) => metadata.len(),
+            Err(err) => {
+                writeln!(out, "{}: FAILED open or read", path.display())?;
+                eprintln!("{}: {}", path.display(), err);
+                unreadable += 1;
+                continue;
+            }
+        };
+
+        // Pieces overlapping the file
+        let mut file_start = 0;
+        let mut file_pieces = vec![];
+        for file in &torrent.files {
+            if file.path == path && file.length > 0 {
+                let first = (file_start / torrent.piece_length) as usize;
+                let last = ((file_start + file.length - 1) / torrent.piece_length) as usize;
+                file_pieces.extend(
+                    bad_pieces
+                        .iter()
+                        .filter(|piece| (first..=last).contains(piece)),
+                );
+            }
+            file_start += file.length;
+        }
+
+        // Pieces of v2 files are counted from the start of the file
+        let bad_file_v2 = torrent
+            .files_v2
+            .iter()
+            .zip(&bad_files_v2)
+            .find_map(|(file, bad)| bad.as_ref().filter(|_| file.path == path));
+
+        // Extra bytes are not read as part of any piece, and empty files have no pieces
+        let expected_length = torrent
+            .files
+            .iter()
+            .chain(&torrent.files_v2)
+            .find(|file| file.path == path && !file.padding)
+            .map_or(0, |file| file.length);
+
+        match bad_file_v2 {
+            _ if !file_pieces.is_empty() => writeln!(
+                out,
+                "{}: FAILED pieces {}",
+                path.display(),
+                format_pieces(&file_pieces)
+            )?,
+            Some(pieces) if !pieces.is_empty() => writeln!(
+                out,
+                "{}: FAILED pieces {}",
+                path.display(),
+                format_pieces(pieces)
+            )?,
+            Some(_) => writeln!(out, "{}: FAILED merkle root", path.display())?,
+            None if length != expected_length => writeln!(
+                out,
+                "{}: FAILED size {} instead of {}",
+                path.display(),
+                length,
+                expected_length
+            )?,
+            None => {
+                writeln!(out, "{}: OK", path.display())?;
+                continue;
+            }
+        }
+        failed += 1;
+    }
+
+    // Hybrid torrents report the v1 pieces, which cover the same bytes
+    let bad_piece_count = match torrent.pieces.is_empty() {
+        true => bad_files_v2.iter().flatten().map(Vec::len).sum(),
+        false => bad_pieces.len(),
+    };
+    for (count, description) in [
+        (unreadable, "listed file(s) could not be read"),
+        (failed, "file(s) did NOT match"),
+        (bad_piece_count, "piece(s) did NOT match"),
+    ] {
+        if count > 0 {
+            eprintln!("WARNING: {} {}", count, description);
+        }
+    }
+
+    if failed + unreadable > 0 {
+        return Err(anyhow!("Torrent verification failed"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Bencodes a byte string
+    fn string(bytes: &[u8]) -> Vec<u8> {
+        [format!("{}:", bytes.len()).as_bytes(), bytes].concat()
+    }
+
+    /// Builds a multi-file v1 torrent with 4-byte pieces for the given files
+    fn v1_torrent(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let payload: Vec<u8> = files.iter().flat_map(|(_, data)| data.to_vec()).collect();
+        let pieces: Vec<u8> = payload.chunks(4).flat_map(sha1).collect();
+
+        let file_list: Vec<u8> = files
+            .iter()
+            .flat_map(|(path, data)| {
+                [
+                    format!("d6:lengthi{}e4:pathl", data.len()).into_bytes(),
+                    string(path.as_bytes()),
+                    b"ee".to_vec(),
+                ]
+                .concat()
+            })
+            .collect();
+
+        [
+            b"d4:infod5:filesl".to_vec(),
+            file_list,
+            b"e4:name7:payload12:piece lengthi4e6:pieces".to_vec(),
+            string(&pieces),
+            b"ee".to_vec(),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn computes_infohashes() {
+        let info =
+            b"d6:lengthi3e4:name5:a.txt12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaae";
+        let data = [b"d4:info".as_slice(), info, b"e"].concat();
+        let torrent = Torrent::parse(&data).unwrap();
+
+        assert_eq!(torrent.infohash_v1, Some(sha1(info)));
+        assert_eq!(torrent.infohash_v2, None);
+        assert_eq!(
+            torrent.files,
+            [TorrentFile {
+                path: PathBuf::from("a.txt"),
+                length: 3,
+                padding: false,
+                pieces_root: None,
+            }]
+        );
+
+        let root = [7; 32];
+        let info_v2 = [
+            b"d9:file treed5:a.txtd0:d6:lengthi3e11:pieces root32:".as_slice(),
+            &root,
+            b"eee12:meta versioni2e4:name5:a.txt12:piece lengthi16384ee",
+        ]
+        .concat();
+        let data = [b"d4:info".as_slice(), &info_v2, b"e"].concat();
+        let torrent = Torrent::parse(&data).unwrap();
+
+        assert_eq!(torrent.infohash_v1, None);
+        assert_eq!(torrent.infohash_v2, Some(sha256(&info_v2)));
+        assert_eq!(torrent.files_v2[0].path, PathBuf::from("a.txt"));
+        assert_eq!(torrent.files_v2[0].pieces_root, Some(root.to_vec()));
+    }
+
+    #[test]
+    fn rejects_unsafe_paths() {
+        for path in ["..", "a/b", "/etc"] {
+            let data = v1_torrent(&[(path, b"abc")]);
+            assert!(Torrent::parse(&data).is_err(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn merkle_roots() {
+        let root = |data: &[u8]| {
+            let (leaves, _) = merkle_leaves(ChunkedStream::Bytes(data.to_vec())).unwrap();
+            hex::encode(merkle_root(&leaves))
+        };
+
+        // A single block is its own root
+        assert_eq!(root(b"abc"), hex::encode(sha256(b"abc")));
+
+        // Three blocks are padded with a zero hash to four leaves
+        let data: Vec<u8> = (0..BLOCK_SIZE * 2 + 5).map(|i| (i % 251) as u8).collect();
+        assert_eq!(
+            root(&data),
+            "90256fb7fb2f450ba11b6e6a704f04bc63a97de0c744a740447abb42dc1504b8"
+        );
+    }
+
+    #[test]
+    fn verifies_payload() {
+        let directory = tempfile::tempdir().unwrap();
+        let torrent_path = directory.path().join("payload.torrent");
+        fs::write(
+            &torrent_path,
+            v1_torrent(&[("a.bin", b"0123456789"), ("b.bin", b"abcdef")]),
+        )
+        .unwrap();
+
+        let payload = directory.path().join("payload");
+        fs::create_dir(&payload).unwrap();
+        fs::write(payload.join("a.bin"), b"0123456789").unwrap();
+        fs::write(payload.join("b.bin"), b"abcdef").unwrap();
+        assert!(verify_torrent(&torrent_path, directory.path()).is_ok());
+
+        // Piece 2 spans both files
+        fs::write(payload.join("b.bin"), b"abcdeX").unwrap();
+        assert!(verify_torrent(&torrent_path, directory.path()).is_err());
+
+        fs::remove_file(payload.join("b.bin")).unwrap();
+        assert!(verify_torrent(&torrent_path, directory.path()).is_err());
+    }
+
+    #[test]
+    fn verifies_file_sizes() {
+        let directory = tempfile::tempdir().unwrap();
+        let torrent_path = directory.path().join("payload.torrent");
+        fs::write(
+            &torrent_path,
+            v1_torrent(&[("a.bin", b"0123456789"), ("empty", b"")]),
+        )
+        .unwrap();
+
+        let payload = directory.path().join("payload");
+        fs::create_dir(&payload).unwrap();
+        fs::write(payload.join("a.bin"), b"0123456789").unwrap();
+        fs::write(payload.join("empty"), b"").unwrap();
+        assert!(verify_torrent(&torrent_path, directory.path()).is_ok());
+
+        // Bytes past the end of a file are not part of any piece
+        fs::write(payload.join("a.bin"), b"0123456789extra").unwrap();
+        assert!(verify_torrent(&torrent_path, directory.path()).is_err());
+
+        fs::write(payload.join("a.bin"), b"0123456789").unwrap();
+        fs::write(payload.join("empty"), b"data").unwrap();
+        assert!(verify_torrent(&torrent_path, directory.path()).is_err());
+
+        // Empty v2 files have no pieces root
+        let info = b"d9:file treed5:emptyd0:d6:lengthi0eeee12:meta versioni2e4:name5:empty12:piece lengthi16384ee";
+        let torrent_path = directory.path().join("empty.torrent");
+        fs::write(&torrent_path, [b"d4:info".as_slice(), info, b"e"].concat()).unwrap();
+        fs::write(directory.path().join("empty"), b"").unwrap();
+        assert!(verify_torrent(&torrent_path, directory.path()).is_ok());
+        fs::write(directory.path().join("empty"), b"data").unwrap();
+        assert!(verify_torrent(&torrent_path, directory.path()).is_err());
+    }
+
+    #[test]
+    fn rejects_huge_pieces() {
+        let info = b"d6:lengthi3e4:name5:a.txt12:piece lengthi4611686018427387904e6:pieces20:aaaaaaaaaaaaaaaaaaaae";
+        let data = [b"d4:info".as_slice(), info, b"e"].concat();
+        assert!(Torrent::parse(&data).is_err());
+    }
+
+    #[test]
+    fn locates_bad_pieces_of_v2_files() {
+        // Three pieces of two blocks, the last one cut short
+        let piece_length = 2 * BLOCK_SIZE as u64;
+        let data: Vec<u8> = (0..BLOCK_SIZE * 4 + 5).map(|i| (i % 251) as u8).collect();
+        let (leaves, _) = merkle_leaves(ChunkedStream::Bytes(data.clone())).unwrap();
+        let root = merkle_root(&leaves);
+        let layer = piece_layer(&leaves, piece_length);
+
+        // Pieces are the subtrees of the file tree, padded with the root of zero leaves
+        let zeros = sha256(&[0; 64]);
+        assert_eq!(layer.len(), 3);
+        assert_eq!(
+            merkle_reduce(&[layer.clone(), vec![zeros]].concat(), 4),
+            root
+        );
+
+        let info = [
+            format!(
+                "d9:file treed5:a.bind0:d6:lengthi{}e11:pieces root32:",
+                data.len()
+            )
+            .as_bytes(),
+            &root,
+            format!(
+                "eee12:meta versioni2e4:name5:a.bin12:piece lengthi{}ee",
+                piece_length
+            )
+            .as_bytes(),
+        ]
+        .concat();
+        let torrent = [
+            b"d4:info".as_slice(),
+            &info,
+            b"12:piece layersd32:",
+            &root,
+            &string(&layer.concat()),
+            b"ee",
+        ]
+        .concat();
+        let torrent = Torrent::parse(&torrent).unwrap();
+
+        let directory = tempfile::tempdir().unwrap();
+        let file = &torrent.files_v2[0];
+        fs::write(directory.path().join("a.bin"), &data).unwrap();
+        assert_eq!(verify_file_v2(directory.path(), file, &torrent), None);
+
+        let mut corrupted = data.clone();
+        corrupted[BLOCK_SIZE * 3] ^= 1;
+        fs::write(directory.path().join("a.bin"), &corrupted).unwrap();
+        assert_eq!(
+            verify_file_v2(directory.path(), file, &torrent),
+            Some(vec![1])
+        );
+
+        // Truncated files miss their last pieces
+        fs::write(directory.path().join("a.bin"), &data[..BLOCK_SIZE]).unwrap();
+        assert_eq!(
+            verify_file_v2(directory.path(), file, &torrent),
+            Some(vec![0, 1, 2])
+        );
+    }
+
+    #[test]
+    fn reads_payload_across_files() {
+        let directory = tempfile::tempdir().unwrap();
+        fs::write(directory.path().join("a"), b"0123").unwrap();
+        fs::write(directory.path().join("b"), b"4567").unwrap();
+
+        let files = [
+            TorrentFile {
+                path: PathBuf::from("a"),
+                length: 4,
+                padding: false,
+                pieces_root: None,
+            },
+            TorrentFile {
+                path: PathBuf::from("pad"),
+                length: 2,
+                padding: true,
+                pieces_root: None,
+            },
+            TorrentFile {
+                path: PathBuf::from("b"),
+                length: 4,
+                padding: false,
+                pieces_root: None,
+            },
+        ];
+
+        assert_eq!(
+            read_payload(directory.path(), &files, 2, 6).unwrap(),
+            b"23\0\x0045"
+        );
+    }
+
+    #[test]
+    fn formats_piece_ranges() {
+        assert_eq!(format_pieces(&[0, 1, 2, 5, 7, 8]), "0-2, 5, 7-8");
+        assert_eq!(format_pieces(&[3]), "3");
+    }
+}
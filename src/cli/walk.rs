@@ -0,0 +1,178 @@
+use std::{
+    fs::{self, Metadata},
+    io,
+    path::{Path, PathBuf},
+};
+
+/// File or directory found while walking, with the metadata of the entry itself,
+/// or of its target when symlinks are followed
+pub struct Entry {
+    pub path: PathBuf,
+    pub metadata: Metadata,
+}
+
+/// Entries found under a set of roots, along with the paths which could not be read
+#[derive(Default)]
+pub struct Walk {
+    pub entries: Vec<Entry>,
+    pub errors: Vec<(PathBuf, io::Error)>,
+}
+
+/// Device and inode of a file, which hardlinks share
+#[cfg(unix)]
+pub fn file_id(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+pub fn file_id(_: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+struct Walker<'a> {
+    walk: Walk,
+    skip: &'a dyn Fn(&Path) -> bool,
+    follow_symlinks: bool,
+    /// Directories being walked, to detect loops through symlinks
+    ancestors: Vec<(u64, u64)>,
+}
+
+impl Walker<'_> {
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        match self.follow_symlinks {
+            // Broken symlinks are listed as symlinks
+            true => fs::metadata(path).or_else(|_| fs::symlink_metadata(path)),
+            false => fs::symlink_metadata(path),
+        }
+    }
+
+    fn visit(&mut self, path: PathBuf) {
+        let metadata = match self.metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(err) => return self.walk.errors.push((path, err)),
+        };
+
+        if metadata.is_dir() {
+            let id = file_id(&metadata);
+            if id.is_some_and(|id| self.ancestors.contains(&id)) {
+                let err = io::Error::other("filesystem loop, the directory contains itself");
+                return self.walk.errors.push((path, err));
+            }
+
+            match read_sorted(&path) {
+                Ok(children) => {
+                    self.ancestors.extend(id);
+                    for child in children.into_iter().filter(|child| !(self.skip)(child)) {
+                        self.visit(child);
+                    }
+                    if id.is_some() {
+                        self.ancestors.pop();
+                    }
+                }
+                Err(err) => self.walk.errors.push((path.clone(), err)),
+            }
+        }
+
+        self.walk.entries.push(Entry { path, metadata });
+    }
+}
+
+/// Directory entries sorted by name, so that walks do not depend on the filesystem order
+fn read_sorted(directory: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut children = fs::read_dir(directory)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    children.sort();
+    Ok(children)
+}
+
+/// Walks every root recursively, directories coming after their contents.
+/// Symlinks are listed but never followed.
+pub fn walk(roots: &[PathBuf]) -> Walk {
+    walk_filtered(roots, &|_| false, false)
+}
+
+/// Walks every root like `walk`, leaving out the paths below them for which `skip`
+/// is true, along with everything in skipped directories. Symlinks are walked
+/// through as their target if `follow_symlinks` is set, loops being reported as errors.
+pub fn walk_filtered(
+    roots: &[PathBuf],
+    skip: &dyn Fn(&Path) -> bool,
+    follow_symlinks: bool,
+) -> Walk {
+    let mut walker = Walker {
+        walk: Walk::default(),
+        skip,
+        follow_symlinks,
+        ancestors: vec![],
+    };
+    for root in roots {
+        walker.visit(root.clone());
+    }
+    walker.walk
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn walks_sorted_trees() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("b/c")).unwrap();
+        fs::write(root.path().join("b/c/file"), "").unwrap();
+        fs::write(root.path().join("a"), "").unwrap();
+        fs::write(root.path().join("b/d"), "").unwrap();
+
+        let walk = walk(&[root.path().to_path_buf(), root.path().join("missing")]);
+        let paths: Vec<_> = walk
+            .entries
+            .iter()
+            .map(|entry| entry.path.strip_prefix(root.path()).unwrap())
+            .collect();
+
+        assert_eq!(
+            paths,
+            ["a", "b/c/file", "b/c", "b/d", "b", ""].map(Path::new)
+        );
+        assert_eq!(walk.errors.len(), 1);
+        assert_eq!(walk.errors[0].0, root.path().join("missing"));
+
+        let filtered = walk_filtered(
+            &[root.path().to_path_buf()],
+            &|path| path.ends_with("c"),
+            false,
+        );
+        assert_eq!(filtered.entries.len(), 4);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follows_symlinks() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir(root.path().join("dir")).unwrap();
+        fs::write(root.path().join("dir/file"), "").unwrap();
+        std::os::unix::fs::symlink("dir", root.path().join("link")).unwrap();
+        std::os::unix::fs::symlink("..", root.path().join("dir/loop")).unwrap();
+
+        let roots = [root.path().to_path_buf()];
+        let walk = walk_filtered(&roots, &|_| false, false);
+        assert_eq!(walk.entries.len(), 5);
+        assert!(walk.errors.is_empty());
+
+        // The link is walked as a copy of the directory, the loop is reported
+        let walk = walk_filtered(&roots, &|_| false, true);
+        let link = root.path().join("link");
+        assert!(walk
+            .entries
+            .iter()
+            .any(|entry| entry.path == link.join("file") && entry.metadata.is_file()));
+        assert_eq!(walk.errors.len(), 2);
+        assert_eq!(
+            walk.errors[0].1.to_string(),
+            "filesystem loop, the directory contains itself"
+        );
+    }
+}
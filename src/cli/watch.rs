@@ -0,0 +1,439 @@
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::anyhow;
+
+use super::{command::Digester, formats::OutputFormat};
+
+/// Time without any write to the watched files after which they are hashed again
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+/// Files and directory trees being watched, along with the last digest of every file
+#[cfg(target_os = "linux")]
+mod watcher {
+    use std::{
+        collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+        ffi::{CString, OsStr, OsString},
+        fs, io,
+        os::{
+            fd::{AsRawFd, FromRawFd, OwnedFd},
+            unix::ffi::OsStrExt,
+        },
+        path::{Path, PathBuf},
+        time::Duration,
+    };
+
+    use super::super::walk::walk;
+
+    /// Event of a watched directory, about the entry called `name` in it
+    #[derive(Debug, PartialEq)]
+    pub struct Event {
+        pub descriptor: i32,
+        pub mask: u32,
+        pub name: Option<OsString>,
+    }
+
+    const EVENT_HEADER_SIZE: usize = std::mem::size_of::<libc::inotify_event>();
+
+    /// Parses the `inotify_event` structures read from an inotify descriptor
+    pub fn parse_events(mut buffer: &[u8]) -> Vec<Event> {
+        let mut events = vec![];
+        while buffer.len() >= EVENT_HEADER_SIZE {
+            let field = |index: usize| {
+                let bytes = buffer[index * 4..index * 4 + 4].try_into().unwrap();
+                u32::from_ne_bytes(bytes)
+            };
+            let (descriptor, mask, length) = (field(0) as i32, field(1), field(3) as usize);
+
+            let name = buffer[EVENT_HEADER_SIZE..]
+                .get(..length)
+                .unwrap_or_default()
+                .split(|&byte| byte == 0)
+                .next()
+                .filter(|name| !name.is_empty())
+                .map(|name| OsStr::from_bytes(name).to_os_string());
+
+            events.push(Event {
+                descriptor,
+                mask,
+                name,
+            });
+            buffer = buffer.get(EVENT_HEADER_SIZE + length..).unwrap_or_default();
+        }
+        events
+    }
+
+    struct Inotify {
+        fd: OwnedFd,
+    }
+
+    impl Inotify {
+        fn new() -> io::Result<Self> {
+            // SAFETY: no pointers are involved, the descriptor is owned from here on
+            match unsafe { libc::inotify_init1(libc::IN_CLOEXEC) } {
+                -1 => Err(io::Error::last_os_error()),
+                fd => Ok(Self {
+                    fd: unsafe { OwnedFd::from_raw_fd(fd) },
+                }),
+            }
+        }
+
+        fn add_watch(&self, path: &Path) -> io::Result<i32> {
+            let path = CString::new(path.as_os_str().as_bytes())
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+            let mask = libc::IN_CLOSE_WRITE
+                | libc::IN_MODIFY
+                | libc::IN_CREATE
+                | libc::IN_DELETE
+                | libc::IN_MOVED_FROM
+                | libc::IN_MOVED_TO
+                | libc::IN_ONLYDIR;
+            // SAFETY: the path is NUL-terminated
+            match unsafe { libc::inotify_add_watch(self.fd.as_raw_fd(), path.as_ptr(), mask) } {
+                -1 => Err(io::Error::last_os_error()),
+                descriptor => Ok(descriptor),
+            }
+        }
+
+        /// Waits until events can be read, or until the timeout. Returns whether
+        /// there are any events.
+        fn wait(&self, timeout: Option<Duration>) -> io::Result<bool> {
+            let mut poll_fd = libc::pollfd {
+                fd: self.fd.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            let timeout = timeout.map_or(-1, |timeout| timeout.as_millis() as libc::c_int);
+
+            loop {
+                // SAFETY: a single valid pollfd is passed
+                match unsafe { libc::poll(&mut poll_fd, 1, timeout) } {
+                    -1 => {
+                        let err = io::Error::last_os_error();
+                        if err.kind() != io::ErrorKind::Interrupted {
+                            return Err(err);
+                        }
+                    }
+                    ready => return Ok(ready > 0),
+                }
+            }
+        }
+
+        fn read_events(&self) -> io::Result<Vec<Event>> {
+            let mut buffer = vec![0u8; 65536];
+            // SAFETY: the buffer is as large as given
+            let length = unsafe {
+                libc::read(
+                    self.fd.as_raw_fd(),
+                    buffer.as_mut_ptr().cast(),
+                    buffer.len(),
+                )
+            };
+            if length < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(parse_events(&buffer[..length as usize]))
+        }
+    }
+
+    struct Directory {
+        path: PathBuf,
+        /// Names of the files watched in the directory, every entry if None
+        files: Option<HashSet<OsString>>,
+    }
+
+    pub struct Watcher {
+        inotify: Inotify,
+        roots: Vec<PathBuf>,
+        directories: HashMap<i32, Directory>,
+        /// Last encoded digest of every file, None if it could not be hashed
+        pub digests: BTreeMap<PathBuf, Option<String>>,
+        /// Files which changed since they were last hashed
+        pub pending: BTreeSet<PathBuf>,
+    }
+
+    impl Watcher {
+        /// Watches the given files and directory trees, with every file pending
+        pub fn new(roots: &[PathBuf]) -> io::Result<Self> {
+            let mut watcher = Self {
+                inotify: Inotify::new()?,
+                roots: roots.to_vec(),
+                directories: HashMap::new(),
+                digests: BTreeMap::new(),
+                pending: BTreeSet::new(),
+            };
+
+            for root in roots {
+                if root.is_dir() {
+                    watcher.add_tree(root);
+                } else {
+                    // Files are watched through their directory, so that they are still
+                    // watched after being replaced (as editors and downloaders do)
+                    let (directory, name) = match (root.parent(), root.file_name()) {
+                        (Some(parent), Some(name)) => (parent, name),
+                        _ => return Err(io::Error::other("cannot watch the path")),
+                    };
+                    // Paths of events are joined to the directory as given, "b" staying "b"
+                    let descriptor = match directory.as_os_str().is_empty() {
+                        true => watcher.inotify.add_watch(Path::new("."))?,
+                        false => watcher.inotify.add_watch(directory)?,
+                    };
+                    let watched =
+                        watcher
+                            .directories
+                            .entry(descriptor)
+                            .or_insert_with(|| Directory {
+                                path: directory.to_path_buf(),
+                                files: Some(HashSet::new()),
+                            });
+                    if let Some(files) = &mut watched.files {
+                        files.insert(name.to_os_string());
+                    }
+                    watcher.pending.insert(root.clone());
+                }
+            }
+
+            Ok(watcher)
+        }
+
+        /// Watches every directory of the tree, its files becoming pending
+        fn add_tree(&mut self, root: &Path) {
+            let walk = walk(&[root.to_path_buf()]);
+            for (path, err) in &walk.errors {
+                eprintln!("{}: {}", path.display(), err);
+            }
+
+            for entry in walk.entries {
+                if entry.metadata.is_dir() {
+                    match self.inotify.add_watch(&entry.path) {
+                        Ok(descriptor) => {
+                            self.directories.insert(
+                                descriptor,
+                                Directory {
+                                    path: entry.path,
+                                    files: None,
+                                },
+                            );
+                        }
+                        Err(err) => eprintln!("{}: {}", entry.path.display(), err),
+                    }
+                } else if entry.metadata.is_file() {
+                    self.pending.insert(entry.path);
+                }
+            }
+        }
+
+        fn handle(&mut self, event: Event) {
+            if event.mask & libc::IN_Q_OVERFLOW != 0 {
+                // Events were lost, so everything is checked again
+                self.pending.extend(self.digests.keys().cloned());
+                for root in self.roots.clone() {
+                    match root.is_dir() {
+                        true => self.add_tree(&root),
+                        false => drop(self.pending.insert(root)),
+                    }
+                }
+                return;
+            }
+            if event.mask & libc::IN_IGNORED != 0 {
+                self.directories.remove(&event.descriptor);
+                return;
+            }
+
+            let (directory, name) = match (self.directories.get(&event.descriptor), event.name) {
+                (Some(directory), Some(name)) => (directory, name),
+                _ => return,
+            };
+            if directory
+                .files
+                .as_ref()
+                .is_some_and(|files| !files.contains(&name))
+            {
+                return;
+            }
+            let path = directory.path.join(name);
+
+            if event.mask & libc::IN_ISDIR == 0 {
+                self.pending.insert(path);
+            } else if event.mask & (libc::IN_CREATE | libc::IN_MOVED_TO) != 0 {
+                self.add_tree(&path);
+            } else {
+                let removed = self.digests.keys().filter(|file| file.starts_with(&path));
+                self.pending.extend(removed.cloned().collect::<Vec<_>>());
+            }
+        }
+
+        /// Waits for changes to the watched files, until no file was written to for
+        /// the settle time. Returns immediately if files are already pending.
+        pub fn wait(&mut self, settle_time: Duration) -> io::Result<()> {
+            loop {
+                let timeout = match self.pending.is_empty() {
+                    true => None,
+                    false => Some(settle_time),
+                };
+                if !self.inotify.wait(timeout)? {
+                    return Ok(());
+                }
+                for event in self.inotify.read_events()? {
+                    self.handle(event);
+                }
+            }
+        }
+    }
+
+    /// Whether a pending path is a file which can be hashed, rather than a removed one
+    pub fn is_file(path: &Path) -> bool {
+        fs::metadata(path).is_ok_and(|metadata| metadata.is_file())
+    }
+}
+
+/// Hashes the given files and directory trees, then hashes them again whenever they
+/// change and prints the digests which changed. Files are only hashed once no file
+/// was written to for the settle time, so that a file being written is not hashed
+/// over and over.
+///
+/// Runs until interrupted.
+#[cfg(target_os = "linux")]
+pub fn watch(
+    digester: &Digester,
+    roots: &[PathBuf],
+    output_format: OutputFormat,
+    verbose: bool,
+) -> anyhow::Result<()> {
+    use std::{fs, io::stdout};
+
+    use rayon::prelude::*;
+
+    use super::{
+        command::{Input, InputSource},
+        output::RecordWriter,
+    };
+
+    let mut watcher = watcher::Watcher::new(roots)
+        .map_err(|err| anyhow!("Failed to watch the paths! {}", err))?;
+
+    let mut writer = RecordWriter::new(stdout().lock(), output_format, true);
+    loop {
+        watcher.wait(SETTLE_TIME)?;
+        let pending: Vec<_> = std::mem::take(&mut watcher.pending).into_iter().collect();
+        if verbose {
+            eprintln!("Hashing {} changed file(s)", pending.len());
+        }
+
+        // Digest changed files in parallel, results are written in the order of paths.
+        // Files are read rather than mapped, since they may be truncated while being read.
+        let results: Vec<_> = pending
+            .into_par_iter()
+            .map(|path| {
+                if !watcher::is_file(&path) {
+                    return (path, None);
+                }
+                let record = fs::metadata(&path).and_then(|metadata| {
+                    digester.digest(Input::new(
+                        InputSource::File(path.clone()),
+                        Some(metadata.len()),
+                    ))
+                });
+                (path, Some(record))
+            })
+            .collect();
+
+        for (path, result) in results {
+            match result {
+                None => {
+                    if watcher.digests.remove(&path).is_some() {
+                        eprintln!("{}: REMOVED", path.display());
+                    }
+                }
+                Some(Err(err)) => {
+                    eprintln!("{}: {}", path.display(), err);
+                    watcher.digests.insert(path, None);
+                }
+                Some(Ok(record)) => {
+                    let previous = watcher.digests.insert(path, Some(record.digest.clone()));
+                    if previous.flatten().as_ref() != Some(&record.digest) {
+                        writer.write(&record)?;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn watch(_: &Digester, _: &[PathBuf], _: OutputFormat, _: bool) -> anyhow::Result<()> {
+    Err(anyhow!("--watch is only supported on Linux (inotify)"))
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod test {
+    use std::fs;
+
+    use super::watcher::*;
+    use super::*;
+
+    #[test]
+    fn parses_events() {
+        let mut buffer = vec![];
+        for (descriptor, mask, name) in [(1i32, libc::IN_MODIFY, "file"), (2, libc::IN_IGNORED, "")]
+        {
+            let length = match name.is_empty() {
+                true => 0,
+                false => 16,
+            };
+            buffer.extend(descriptor.to_ne_bytes());
+            buffer.extend(mask.to_ne_bytes());
+            buffer.extend(0u32.to_ne_bytes());
+            buffer.extend((length as u32).to_ne_bytes());
+            let mut name = name.as_bytes().to_vec();
+            name.resize(length, 0);
+            buffer.extend(name);
+        }
+
+        assert_eq!(
+            parse_events(&buffer),
+            [
+                Event {
+                    descriptor: 1,
+                    mask: libc::IN_MODIFY,
+                    name: Some("file".into())
+                },
+                Event {
+                    descriptor: 2,
+                    mask: libc::IN_IGNORED,
+                    name: None
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn watches_trees_and_files() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir(root.path().join("dir")).unwrap();
+        fs::write(root.path().join("dir/a"), "a").unwrap();
+        fs::write(root.path().join("b"), "b").unwrap();
+        fs::write(root.path().join("c"), "c").unwrap();
+
+        let roots = [root.path().join("dir"), root.path().join("b")];
+        let mut watcher = Watcher::new(&roots).unwrap();
+        let settle_time = Duration::from_millis(50);
+        watcher.wait(settle_time).unwrap();
+        assert_eq!(
+            std::mem::take(&mut watcher.pending),
+            [root.path().join("b"), root.path().join("dir/a")].into()
+        );
+
+        // Files created in new directories are picked up, unwatched files are not
+        fs::write(root.path().join("c"), "changed").unwrap();
+        fs::create_dir(root.path().join("dir/new")).unwrap();
+        fs::write(root.path().join("dir/new/d"), "d").unwrap();
+        fs::write(root.path().join("b.tmp"), "replaced").unwrap();
+        fs::rename(root.path().join("b.tmp"), root.path().join("b")).unwrap();
+        watcher.wait(settle_time).unwrap();
+        assert_eq!(
+            watcher.pending,
+            [root.path().join("b"), root.path().join("dir/new/d")].into()
+        );
+    }
+}
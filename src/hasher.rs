@@ -0,0 +1,204 @@
+use std::{
+    hash::{BuildHasher, Hasher},
+    sync::Arc,
+};
+
+use crate::algorithms::{Algorithm, Incremental};
+
+/// [`BuildHasher`] creating [`HashyHasher`]s for an algorithm, so that it can
+/// be used in a `HashMap` or for sharding.
+///
+/// ```
+/// use std::collections::HashMap;
+/// use hashy::{algorithms::xxhash::Xxh64, hasher::BuildHashyHasher};
+///
+/// let mut map = HashMap::with_hasher(BuildHashyHasher::new(Xxh64::new(42)));
+/// map.insert("key", 1);
+/// ```
+#[derive(Clone)]
+pub struct BuildHashyHasher {
+    algorithm: Arc<dyn Algorithm>,
+}
+
+impl BuildHashyHasher {
+    /// Wraps an algorithm, which every algorithm of this crate can be.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the algorithm has no incremental state.
+    pub fn new(algorithm: impl Algorithm + 'static) -> Self {
+        assert!(
+            algorithm.incremental().is_some(),
+            "the algorithm has no incremental state"
+        );
+
+        Self {
+            algorithm: Arc::new(algorithm),
+        }
+    }
+}
+
+impl BuildHasher for BuildHashyHasher {
+    type Hasher = HashyHasher;
+
+    fn build_hasher(&self) -> HashyHasher {
+        HashyHasher {
+            state: self
+                .algorithm
+                .incremental()
+                .expect("algorithms are checked to be incremental"),
+        }
+    }
+}
+
+/// [`Hasher`] wrapping the incremental state of an algorithm, whose output is the
+/// digest truncated to a `u64`.
+///
+/// The first 8 bytes of the digest are read as a big-endian integer, so 64-bit
+/// hashes like XXH64 or SipHash output their own value and shorter digests
+/// are zero-extended.
+pub struct HashyHasher {
+    state: Box<dyn Incremental>,
+}
+
+impl Hasher for HashyHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.state.update(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        truncate(&self.state.finalize())
+    }
+}
+
+/// Reads up to the first 8 bytes of the digest as a big-endian integer
+fn truncate(digest: &[u8]) -> u64 {
+    digest
+        .iter()
+        .take(8)
+        .fold(0, |value, &byte| value << 8 | byte as u64)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::algorithms::{
+        adler32::Adler32,
+        crc::{Crc, CRC_32_ISO_HDLC},
+        fletcher::Fletcher,
+        fnv::{Fnv, FnvVariant, FnvWidth},
+        md2::Md2,
+        md5::Md5,
+        md6::Md6,
+        sha1::Sha1,
+        sha2::{Sha2, Sha2Variant},
+        sha3::{Sha3, Sha3Variant},
+        siphash::SipHash,
+        xxhash::{Xxh3, Xxh3Variant, Xxh64},
+    };
+
+    use crate::chunked_stream::ChunkedStream;
+
+    use super::*;
+
+    fn hash_pieces(build_hasher: &BuildHashyHasher, pieces: &[&[u8]]) -> u64 {
+        let mut hasher = build_hasher.build_hasher();
+        for piece in pieces {
+            hasher.write(piece);
+        }
+        hasher.finish()
+    }
+
+    #[test]
+    fn finish_matches_digest() {
+        let key = std::array::from_fn(|i| i as u8);
+
+        for (build_hasher, expected) in [
+            (BuildHashyHasher::new(Xxh64::new(0)), 0x44bc2cf5ad770999),
+            (BuildHashyHasher::new(SipHash::new(key)), 0x5dbcfa53aa2007a5),
+            (
+                BuildHashyHasher::new(Fnv::new(FnvVariant::Fnv1a, FnvWidth::_64, None)),
+                0xe71fa2190541574b,
+            ),
+            // Truncated to the first 8 bytes
+            (
+                BuildHashyHasher::new(Sha2::new(Sha2Variant::_256)),
+                0xba7816bf8f01cfea,
+            ),
+            // Zero-extended
+            (BuildHashyHasher::new(Crc::new(CRC_32_ISO_HDLC)), 0x352441c2),
+        ] {
+            assert_eq!(hash_pieces(&build_hasher, &[b"abc"]), expected);
+        }
+    }
+
+    #[test]
+    fn pieces_hash_like_whole_input() {
+        let data: Vec<u8> = (0..300).map(|i| (i * 31 % 251) as u8).collect();
+        let pieces: Vec<&[u8]> = data.chunks(7).collect();
+
+        for build_hasher in [
+            BuildHashyHasher::new(Xxh64::new(3)),
+            BuildHashyHasher::new(SipHash::new([7; 16])),
+            BuildHashyHasher::new(Xxh3::new(Xxh3Variant::_128, 0)),
+        ] {
+            assert_eq!(
+                hash_pieces(&build_hasher, &pieces),
+                hash_pieces(&build_hasher, &[&data])
+            );
+        }
+    }
+
+    #[test]
+    fn incremental_states_match_digests() {
+        // Long enough for several blocks of every algorithm, in odd-sized pieces
+        let data: Vec<u8> = (0..5000).map(|i| (i * 31 % 251) as u8).collect();
+
+        let algorithms: [Box<dyn Algorithm>; 10] = [
+            Box::new(Adler32),
+            Box::new(Fletcher::_32),
+            Box::new(Crc::new(CRC_32_ISO_HDLC)),
+            Box::new(Md2),
+            Box::new(Md5),
+            Box::new(Md6::new(256, None)),
+            Box::new(Sha1),
+            Box::new(Sha2::new(Sha2Variant::_512)),
+            Box::new(Sha3::new(Sha3Variant::_256)),
+            Box::new(Xxh3::new(Xxh3Variant::_64, 7)),
+        ];
+        for algorithm in algorithms {
+            let mut state = algorithm.incremental().unwrap();
+            for piece in data.chunks(333) {
+                state.update(piece);
+            }
+
+            let expected = algorithm
+                .digest(ChunkedStream::Bytes(data.clone()))
+                .unwrap();
+            assert_eq!(state.finalize(), expected);
+        }
+    }
+
+    #[test]
+    fn finish_does_not_consume_state() {
+        let mut hasher = BuildHashyHasher::new(Xxh64::new(0)).build_hasher();
+        hasher.write(b"ab");
+        let first = hasher.finish();
+        assert_eq!(hasher.finish(), first);
+
+        hasher.write(b"c");
+        assert_eq!(hasher.finish(), 0x44bc2cf5ad770999);
+    }
+
+    #[test]
+    fn works_in_hash_map() {
+        let mut map = HashMap::with_hasher(BuildHashyHasher::new(SipHash::new([1; 16])));
+        for i in 0..100 {
+            map.insert(i.to_string(), i);
+        }
+
+        assert_eq!(map.len(), 100);
+        assert_eq!(map.get("42"), Some(&42));
+    }
+}
@@ -0,0 +1,11 @@
+//! Hashing algorithms behind the `hashy` CLI, usable as a library.
+//!
+//! Algorithms digest a whole [`chunked_stream::ChunkedStream`] through the
+//! [`algorithms::Algorithm`] trait, and can be used with `std` collections
+//! through the adapters in [`hasher`]. Streams can be split into
+//! content-defined chunks with [`chunking::FastCdc`].
+
+pub mod algorithms;
+pub mod chunked_stream;
+pub mod chunking;
+pub mod hasher;
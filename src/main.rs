@@ -1,4 +1,4 @@
-use std::process;
+use std::{io, process};
 
 use anyhow::anyhow;
 use cli::{
@@ -7,21 +7,25 @@ use cli::{
 };
 use structopt::StructOpt;
 
-mod algorithms;
-mod chunked_stream;
 mod cli;
 
 fn main() -> anyhow::Result<()> {
     let opts = Opts::from_args();
 
     if opts.verbose {
-        println!("Input options: {:?}", opts);
+        eprintln!("Input options: {:?}", opts);
     }
 
     let command: Command = opts.try_into().map_err(|err| match err {
-        CommandParseError::FileDoesNotExist => anyhow!("Path does not exist!"),
-        CommandParseError::PathIsDirectory => anyhow!("Path cannot be a directory!"),
-        CommandParseError::InvalidPath(io_err) => anyhow!("Invalid path! {}", io_err),
+        CommandParseError::FileDoesNotExist(path) => {
+            anyhow!("Path does not exist! {}", path.display())
+        }
+        CommandParseError::PathIsDirectory(path) => {
+            anyhow!("Path cannot be a directory! {}", path.display())
+        }
+        CommandParseError::InvalidPath(path, io_err) => {
+            anyhow!("Invalid path! {}: {}", path.display(), io_err)
+        }
         CommandParseError::InvalidAlgorithm(parse_error_message) => {
             anyhow!("Invalid algorithm! {}", parse_error_message)
         }
@@ -29,10 +33,35 @@ fn main() -> anyhow::Result<()> {
             Opts::clap().print_help().unwrap();
             process::exit(2);
         }
+        CommandParseError::ConflictingOptions(message) => {
+            anyhow!("Conflicting options! {}", message)
+        }
+        CommandParseError::OffsetOutOfRange(offset) => {
+            anyhow!(
+                "Offset out of range! {} is past the end of the input",
+                offset
+            )
+        }
+        CommandParseError::InvalidCheckpoint(path, io_err) => {
+            anyhow!("Invalid checkpoint! {}: {}", path.display(), io_err)
+        }
         CommandParseError::NotImplemented => {
             anyhow!("Command unimplemented! Please consult the maintainer of this CLI.")
         }
     })?;
 
-    command.execute()
+    match command.execute() {
+        // Output piped into a command which stopped reading, such as `head`
+        Err(err) if is_broken_pipe(&err) => Ok(()),
+        result => result,
+    }
+}
+
+/// Whether the error comes from writing to a closed pipe
+fn is_broken_pipe(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause
+            .downcast_ref::<io::Error>()
+            .is_some_and(|err| err.kind() == io::ErrorKind::BrokenPipe)
+    })
 }